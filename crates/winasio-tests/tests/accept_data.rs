@@ -0,0 +1,83 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Accepts that complete with the client's first bytes.
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::time::{Duration, Instant};
+
+use winasio::iocp::{OpResult, ThreadPool};
+use winasio::net::{ReadOutcome, TcpListener, TcpListenerOptions};
+
+#[test]
+fn the_first_bytes_arrive_with_the_connection() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let mut client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    client.write_all(b"hello").unwrap();
+
+    let (result, buf) = common::block_on(listener.accept_with_data(Vec::with_capacity(64)));
+    let (stream, peer, received) = result.expect("accept");
+    assert_eq!(peer, client.local_addr().unwrap());
+    assert_eq!(received, 5);
+    assert_eq!(buf, b"hello");
+
+    // The rest of the conversation is an ordinary stream.
+    client.write_all(b"more").unwrap();
+    let OpResult(read, buf) = common::block_on(stream.read(vec![0u8; 4]));
+    assert_eq!(read.expect("read"), ReadOutcome::Bytes(4));
+    assert_eq!(&buf, b"more");
+}
+
+#[test]
+fn a_client_that_closes_without_sending_completes_with_no_bytes() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    client.shutdown(Shutdown::Write).unwrap();
+
+    let (result, buf) = common::block_on(listener.accept_with_data(Vec::with_capacity(16)));
+    let (_stream, _peer, received) = result.expect("accept");
+    assert_eq!(received, 0);
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn a_silent_client_is_turned_away_and_the_next_one_accepted() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind_with(
+        &ThreadPool,
+        common::v4_local(),
+        TcpListenerOptions::new().silent_accept_limit(Duration::from_secs(1)),
+    )
+    .expect("bind");
+
+    // Both wait in the backlog; the silent one is first in line.
+    let mut silent = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let mut speaker = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    speaker.write_all(b"hi").unwrap();
+
+    let started = Instant::now();
+    let (result, buf) = common::block_on(listener.accept_with_data(Vec::with_capacity(16)));
+    let (_stream, peer, received) = result.expect("accept");
+    assert_eq!(peer, speaker.local_addr().unwrap());
+    assert_eq!((received, buf.as_slice()), (2, &b"hi"[..]));
+
+    // The sweep waits at least the limit, and the silent client's connection
+    // was closed rather than left pending.
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    silent
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut byte = [0u8; 1];
+    match silent.read(&mut byte) {
+        Ok(0) | Err(_) => {}
+        Ok(n) => panic!("the silent client was sent {n} bytes"),
+    }
+}
@@ -21,6 +21,7 @@ pub mod tls_config;
 pub mod tls_lock;
 
 use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
@@ -116,6 +117,11 @@ fn sanitize_pipe_component(input: &str) -> String {
         .collect()
 }
 
+/// The IPv4 loopback address with port 0, for binding to any free port.
+pub fn v4_local() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)
+}
+
 /// A URL group together with the session it borrows.
 ///
 /// `UrlGroup<'a>` borrows its `ServerSession`, which a plain struct cannot
@@ -0,0 +1,96 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Name resolution and connecting by host name.
+//!
+//! Only names the machine answers for itself are used, so these tests do not
+//! depend on a DNS server being reachable. `.invalid` is reserved by RFC 6761
+//! and never resolves.
+
+mod common;
+
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::task::{Context, Poll, Waker};
+
+use winasio::iocp::ThreadPool;
+use winasio::net::{lookup_host, ConnectError, SocketError, TcpListener, TcpStream};
+
+#[test]
+fn localhost_resolves_to_loopback_with_the_port() {
+    let addrs = common::block_on(lookup_host("localhost", 4321)).expect("resolve");
+    assert!(!addrs.is_empty());
+    for addr in &addrs {
+        assert!(addr.ip().is_loopback(), "{addr} is not loopback");
+        assert_eq!(addr.port(), 4321);
+    }
+}
+
+#[test]
+fn an_invalid_name_is_not_found() {
+    let err = common::block_on(lookup_host("winasio-test.invalid", 80)).expect_err("no such host");
+    assert!(matches!(err, SocketError::HostNotFound), "got {err:?}");
+}
+
+#[test]
+fn dropping_a_pending_lookup_is_safe() {
+    let mut lookup = Box::pin(lookup_host("winasio-drop-test.invalid", 80));
+    let mut cx = Context::from_waker(Waker::noop());
+    // Either still pending, which the drop cancels, or already answered.
+    let _ = lookup.as_mut().poll(&mut cx);
+    drop(lookup);
+    // A later lookup still works, so the cancelled one left nothing behind.
+    let addrs = common::block_on(lookup_host("127.0.0.1", 1)).expect("resolve");
+    assert_eq!(addrs, [SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1)]);
+}
+
+#[test]
+fn connect_host_reaches_a_local_listener() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(
+        &ThreadPool,
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0),
+    )
+    .expect("bind");
+    let port = listener.local_addr().port();
+
+    let stream =
+        common::block_on(TcpStream::connect_host(&ThreadPool, "127.0.0.1", port)).expect("connect");
+    let (_server, peer) = common::block_on(listener.accept()).expect("accept");
+    assert_eq!(stream.local_addr().unwrap(), peer);
+}
+
+#[test]
+fn connect_host_reports_every_refused_address() {
+    let _guard = winasio::net::socket_guard();
+    // Bind and drop, for a port nothing is listening on.
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .expect("probe port")
+        .port();
+
+    let err = common::block_on(TcpStream::connect_host(&ThreadPool, "127.0.0.1", port))
+        .expect_err("nothing listens there");
+    let attempts = err.attempts();
+    assert_eq!(attempts.len(), 1, "got {err:?}");
+    assert_eq!(attempts[0].0.port(), port);
+    assert!(matches!(attempts[0].1, SocketError::ConnectionRefused));
+}
+
+#[test]
+fn connect_host_reports_a_resolution_failure() {
+    let _guard = winasio::net::socket_guard();
+    let err = common::block_on(TcpStream::connect_host(
+        &ThreadPool,
+        "winasio-test.invalid",
+        80,
+    ))
+    .expect_err("no such host");
+    assert!(
+        matches!(err, ConnectError::Resolve(SocketError::HostNotFound)),
+        "got {err:?}"
+    );
+}
@@ -0,0 +1,164 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Length-prefixed frames over pipes and sockets.
+//!
+//! The header codec itself is unit-tested inside the crate; these tests cover
+//! buffering across reads and how each transport's way of ending maps onto a
+//! clean end or a truncated frame.
+
+mod common;
+
+use std::future::Future;
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use winasio::framing::{Endian, FrameError, FrameOptions, Framed, LengthHeader};
+use winasio::io::TransferError;
+use winasio::iocp::{OpResult, ThreadPool, ThreadPoolIo};
+use winasio::net::TcpListener;
+use winasio::pipe::{ClientOptions, NamedPipe, ServerOptions};
+
+fn assert_pending<F: Future>(future: &mut Pin<Box<F>>) {
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(
+        matches!(future.as_mut().poll(&mut cx), Poll::Pending),
+        "operation must be pending"
+    );
+}
+
+fn pipe_pair(name: &str) -> (NamedPipe<ThreadPoolIo>, NamedPipe<ThreadPoolIo>) {
+    let server = ServerOptions::new(name).create(&ThreadPool).unwrap();
+    let mut accept = Box::pin(server.connect());
+    assert_pending(&mut accept);
+    let client = ClientOptions::new(name).connect(&ThreadPool).unwrap();
+    let server = common::block_on(accept).unwrap();
+    (server, client)
+}
+
+#[test]
+fn frames_round_trip_over_a_pipe_with_every_header() {
+    for (i, header) in [
+        LengthHeader::U16(Endian::Little),
+        LengthHeader::U32(Endian::Big),
+        LengthHeader::Varint,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let name = common::unique_pipe_name(&format!("frames_round_trip_{i}"));
+        let (server, client) = pipe_pair(&name);
+        let mut options = FrameOptions::new();
+        options.header(header);
+        let mut sender = options.framed(client);
+        let mut receiver = options.framed(server);
+
+        // Larger than a pipe buffer, so frames straddle many reads.
+        let large: Vec<u8> = (0..60_000u32).map(|n| (n % 251) as u8).collect();
+        let frames = [b"first".to_vec(), Vec::new(), large, b"last".to_vec()];
+
+        let send = async {
+            for frame in frames.clone() {
+                sender.send_frame(frame).await.unwrap();
+            }
+            drop(sender);
+        };
+        let recv = async {
+            let mut got = Vec::new();
+            while let Some(frame) = receiver.recv_frame().await.unwrap() {
+                got.push(frame);
+            }
+            got
+        };
+        let ((), got) = common::block_on(async { futures::join!(send, recv) });
+        assert_eq!(got, frames, "{header:?}");
+    }
+}
+
+#[test]
+fn a_pipe_closed_inside_a_frame_is_truncation() {
+    let name = common::unique_pipe_name("a_pipe_closed_inside_a_frame_is_truncation");
+    let (server, client) = pipe_pair(&name);
+    let mut receiver = Framed::new(server);
+
+    // One whole frame, then a header promising ten bytes and only three.
+    let OpResult(written, _) =
+        common::block_on(client.write(vec![0, 0, 0, 2, b'o', b'k', 0, 0, 0, 10, 1, 2, 3]));
+    assert_eq!(written.unwrap(), 13);
+    drop(client);
+
+    assert_eq!(
+        common::block_on(receiver.recv_frame()).unwrap().unwrap(),
+        b"ok"
+    );
+    assert!(matches!(
+        common::block_on(receiver.recv_frame()),
+        Err(FrameError::Transfer(TransferError::UnexpectedEof))
+    ));
+}
+
+#[test]
+fn frames_over_the_limit_are_refused_both_ways() {
+    let name = common::unique_pipe_name("frames_over_the_limit_are_refused_both_ways");
+    let (server, client) = pipe_pair(&name);
+    let mut options = FrameOptions::new();
+    options.max_frame_len(16);
+    let mut receiver = options.framed(server);
+
+    // Refused before anything is written.
+    let mut sender = options.framed(client);
+    assert!(matches!(
+        common::block_on(sender.send_frame(vec![0; 17])),
+        Err(FrameError::TooLarge { len: 17 })
+    ));
+
+    // A peer announcing a huge frame is refused on the header alone.
+    let (client, _) = sender.into_parts();
+    let OpResult(written, _) = common::block_on(client.write(vec![0xff, 0xff, 0xff, 0xff]));
+    assert_eq!(written.unwrap(), 4);
+    assert!(matches!(
+        common::block_on(receiver.recv_frame()),
+        Err(FrameError::TooLarge { len: 0xffff_ffff })
+    ));
+}
+
+#[test]
+fn a_socket_shut_down_between_frames_ends_cleanly_and_inside_one_is_truncation() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(
+        &ThreadPool,
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0),
+    )
+    .expect("bind");
+    let addr = listener.local_addr();
+
+    for (tail, truncated) in [(&[][..], false), (&[0, 0, 0, 5, b'a'][..], true)] {
+        // The backlog completes the connect before the accept is posted.
+        let mut client = std::net::TcpStream::connect(addr).expect("client connects");
+        let (server, _peer) = common::block_on(listener.accept()).expect("accept");
+        let mut receiver = Framed::new(server);
+
+        client.write_all(&[0, 0, 0, 2, b'o', b'k']).unwrap();
+        client.write_all(tail).unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+
+        assert_eq!(
+            common::block_on(receiver.recv_frame()).unwrap().unwrap(),
+            b"ok"
+        );
+        let end = common::block_on(receiver.recv_frame());
+        if truncated {
+            assert!(matches!(
+                end,
+                Err(FrameError::Transfer(TransferError::UnexpectedEof))
+            ));
+        } else {
+            assert!(end.unwrap().is_none());
+        }
+    }
+}
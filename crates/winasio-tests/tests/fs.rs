@@ -14,11 +14,11 @@ use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
-use winasio::fs::{File, OpenOptions, ReadOutcome, SetupError};
+use winasio::fs::{File, OpenOptions, ReadOutcome, SetupError, TempFile};
 use winasio::iocp::{
     OpResult, Proactor, Registrar, RegistrationError, Submitter, ThreadPool, ThreadPoolIo, WriteAt,
 };
-use windows::Win32::Foundation::ERROR_INVALID_PARAMETER;
+use windows::Win32::Foundation::{ERROR_ACCESS_DENIED, ERROR_INVALID_PARAMETER};
 use windows::Win32::Storage::FileSystem::{
     FILE_ATTRIBUTE_TEMPORARY, FILE_FLAG_OVERLAPPED, FILE_SHARE_NONE, FILE_SHARE_READ,
     FILE_SHARE_WRITE,
@@ -158,6 +158,290 @@ fn open_options_share_mode_and_custom_attributes_have_effect() {
     let _ = std::fs::remove_file(attr_path);
 }
 
+#[test]
+fn open_options_append_writes_land_at_end_of_file() {
+    let path = temp_path("append");
+    create_contents(&path, b"head");
+
+    let mut options = OpenOptions::new();
+    options.append(true);
+    let file = options.open(&ThreadPool, &path).unwrap();
+    let OpResult(written, _) = common::block_on(file.write_at(0, b"-tail".to_vec()));
+    assert_eq!(written.unwrap(), 5);
+    drop(file);
+
+    assert_eq!(std::fs::read(&path).unwrap(), b"head-tail");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn open_options_delete_on_close_and_directories() {
+    let path = temp_path("delete-on-close");
+    let mut options = OpenOptions::new();
+    options
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .temporary(true)
+        .delete_on_close(true);
+    let file = options.open(&ThreadPool, &path).unwrap();
+    assert!(path.exists());
+    drop(file);
+    assert!(!path.exists());
+
+    let dir = temp_path("backup-dir");
+    std::fs::create_dir(&dir).unwrap();
+    let mut options = OpenOptions::new();
+    options.read(true).backup_semantics(true);
+    drop(options.open(&ThreadPool, &dir).unwrap());
+    std::fs::remove_dir(dir).unwrap();
+}
+
+#[test]
+fn open_options_security_descriptor_is_validated() {
+    let path = temp_path("sddl");
+    let mut options = OpenOptions::new();
+    options
+        .write(true)
+        .create_new(true)
+        .security_descriptor_sddl("not sddl");
+    assert!(matches!(
+        options.open(&ThreadPool, &path),
+        Err(SetupError::InvalidSecurityDescriptor(_))
+    ));
+    assert!(!path.exists());
+
+    // Owner-only full access: the creator can still open the file afterwards.
+    options.security_descriptor_sddl("D:P(A;;FA;;;OW)");
+    drop(options.open(&ThreadPool, &path).unwrap());
+    assert_eq!(std::fs::read(&path).unwrap(), b"");
+    std::fs::remove_file(path).unwrap();
+}
+
+fn sparse_ranges_body<R: FileTestRegistrar>(registrar: &R, tag: &str) {
+    const MIB: u64 = 1 << 20;
+    const HOLE: std::ops::Range<u64> = (64 << 10)..(576 << 10);
+
+    let path = temp_path(tag);
+    let file = open_read_write(registrar, &path);
+    let OpResult(written, _) = registrar.drive(file.write_at(0, vec![0xAB; MIB as usize]));
+    assert_eq!(written.unwrap(), MIB as usize);
+
+    let dense = registrar.drive(file.allocated_ranges()).unwrap();
+    assert_eq!(dense, vec![0..MIB]);
+
+    registrar.drive(file.set_sparse(true)).unwrap();
+    registrar
+        .drive(file.zero_range(HOLE.start, HOLE.end - HOLE.start))
+        .unwrap();
+
+    let ranges = registrar.drive(file.allocated_ranges()).unwrap();
+    assert!(!ranges.is_empty());
+    for range in &ranges {
+        assert!(
+            range.end <= HOLE.start || range.start >= HOLE.end,
+            "{range:?} overlaps the punched hole"
+        );
+    }
+    assert!(ranges.windows(2).all(|pair| pair[0].end <= pair[1].start));
+
+    let OpResult(read, hole) = registrar.drive(file.read_at(HOLE.start, Vec::with_capacity(4096)));
+    assert_eq!(read.unwrap(), ReadOutcome::Bytes(4096));
+    assert!(hole.iter().all(|&b| b == 0));
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), MIB);
+
+    file.preallocate(4 * MIB).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), MIB);
+
+    drop(file);
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn preallocating_less_than_the_file_keeps_its_data() {
+    let path = temp_path("preallocate-short");
+    let file = open_read_write(&ThreadPool, &path);
+    let OpResult(written, _) = common::block_on(file.write_at(0, vec![7u8; 8192]));
+    assert_eq!(written.unwrap(), 8192);
+
+    file.preallocate(100).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), 8192);
+    let OpResult(read, data) = common::block_on(file.read_at(0, Vec::with_capacity(8192)));
+    assert_eq!(read.unwrap(), ReadOutcome::Bytes(8192));
+    assert!(data.iter().all(|&b| b == 7));
+
+    drop(file);
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn sparse_ranges_thread_pool() {
+    sparse_ranges_body(&ThreadPool, "sparse-pool");
+}
+
+#[test]
+fn sparse_ranges_caller_driven() {
+    let proactor = Rc::new(Proactor::new().unwrap());
+    sparse_ranges_body(&proactor, "sparse-proactor");
+}
+
+fn read_many_body<R: FileTestRegistrar>(registrar: &R, tag: &str) {
+    const PAGE: u64 = 4096;
+    let path = temp_path(tag);
+    let contents: Vec<u8> = (0..16 * PAGE).map(|i| (i / PAGE) as u8).collect();
+    create_contents(&path, &contents);
+    let mut options = OpenOptions::new();
+    options.read(true);
+    let file = options.open(registrar, &path).unwrap();
+
+    // Pages in reverse, plus one range that runs past end-of-file.
+    let mut ranges: Vec<_> = (0..16).rev().map(|p| p * PAGE..(p + 1) * PAGE).collect();
+    ranges.push(16 * PAGE..17 * PAGE);
+    let buffers = ranges.iter().map(|_| Vec::with_capacity(2 * PAGE as usize));
+
+    let mut reads = file.read_many(ranges.clone(), buffers).unwrap();
+    reads.max_in_flight(4);
+    assert_eq!(reads.remaining(), ranges.len());
+
+    let mut seen = Vec::new();
+    while let Some((range, OpResult(read, buf))) = registrar.drive(reads.next()) {
+        if range.start == 16 * PAGE {
+            assert_eq!(read.unwrap(), ReadOutcome::Eof);
+        } else {
+            assert_eq!(read.unwrap(), ReadOutcome::Bytes(PAGE as usize));
+            assert_eq!(buf, &contents[range.start as usize..range.end as usize]);
+        }
+        seen.push(range);
+    }
+    assert_eq!(reads.remaining(), 0);
+    seen.sort_by_key(|range| range.start);
+    ranges.sort_by_key(|range| range.start);
+    assert_eq!(seen, ranges);
+
+    drop(reads);
+    drop(file);
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn read_many_thread_pool() {
+    read_many_body(&ThreadPool, "read-many-pool");
+}
+
+#[test]
+fn read_many_caller_driven() {
+    let proactor = Rc::new(Proactor::new().unwrap());
+    read_many_body(&proactor, "read-many-proactor");
+}
+
+#[test]
+fn read_many_rejects_unpaired_buffers() {
+    let path = temp_path("read-many-unpaired");
+    create_contents(&path, b"contents");
+    let mut options = OpenOptions::new();
+    options.read(true);
+    let file = options.open(&ThreadPool, &path).unwrap();
+
+    let unpaired = file
+        .read_many([0..4, 4..8], [Vec::<u8>::with_capacity(4)])
+        .expect_err("two ranges, one buffer");
+    assert_eq!(unpaired.code(), ERROR_INVALID_PARAMETER.to_hresult());
+
+    drop(file);
+    let _ = std::fs::remove_file(path);
+}
+
+fn temp_file_body<R: FileTestRegistrar>(registrar: &R, tag: &str) {
+    let dir = temp_path(tag);
+    std::fs::create_dir(&dir).unwrap();
+    let target = dir.join("config.json");
+    std::fs::write(&target, b"old").unwrap();
+
+    // Dropped without persisting: the file disappears.
+    let abandoned = TempFile::new_in(registrar, &dir).unwrap();
+    let abandoned_path = abandoned.path().to_path_buf();
+    assert!(abandoned_path.exists());
+    drop(abandoned);
+    assert!(!abandoned_path.exists());
+
+    // A failed persist hands the file back, still delete-on-close.
+    let temp = TempFile::new_in(registrar, &dir).unwrap();
+    let failed = temp
+        .persist(dir.join("missing").join("config.json"))
+        .unwrap_err();
+    let temp = failed.file;
+    let temp_path = temp.path().to_path_buf();
+
+    let payload = b"new contents".to_vec();
+    let (written, _, _) = registrar
+        .drive(temp.file().write_all(0, payload))
+        .into_parts();
+    written.unwrap();
+    let file = temp.persist(&target).unwrap();
+    assert!(!temp_path.exists());
+    drop(file);
+    assert_eq!(std::fs::read(&target).unwrap(), b"new contents");
+
+    let leftovers: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(leftovers.len(), 1, "only the persisted target remains");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn temp_file_persists_atomically_thread_pool() {
+    temp_file_body(&ThreadPool, "temp-pool");
+}
+
+#[test]
+fn temp_file_persists_atomically_caller_driven() {
+    let proactor = Rc::new(Proactor::new().unwrap());
+    temp_file_body(&proactor, "temp-proactor");
+}
+
+#[test]
+fn persist_as_flushes_and_renames_a_plain_file() {
+    let dir = temp_path("persist-plain");
+    std::fs::create_dir(&dir).unwrap();
+    let staged = dir.join("staged.bin");
+    let target = dir.join("data.bin");
+    std::fs::write(&target, b"old").unwrap();
+
+    // `DELETE` access for the rename, but never delete-on-close.
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true).delete_access(true);
+    let file = options.open(&ThreadPool, &staged).unwrap();
+
+    let payload: Vec<u8> = (0..64 * 1024).map(|i| i as u8).collect();
+    let (written, _, _) = common::block_on(file.write_all(0, payload.clone())).into_parts();
+    written.unwrap();
+    file.sync_all().unwrap();
+    file.persist_as(&target).unwrap();
+    assert!(!staged.exists());
+    drop(file);
+
+    assert_eq!(std::fs::read(&target).unwrap(), payload);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn persist_as_needs_delete_access() {
+    let dir = temp_path("persist-no-delete");
+    std::fs::create_dir(&dir).unwrap();
+    let staged = dir.join("staged.bin");
+    let target = dir.join("data.bin");
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    let file = options.open(&ThreadPool, &staged).unwrap();
+    let error = file.persist_as(&target).expect_err("opened without DELETE");
+    assert_eq!(error.code(), ERROR_ACCESS_DENIED.to_hresult());
+    assert!(staged.exists());
+    assert!(!target.exists());
+    drop(file);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
 #[test]
 fn eof_zero_byte_and_failed_write_return_buffers() {
     let path = temp_path("eof");
@@ -0,0 +1,165 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! `close_gracefully`: shut down, drain, and reset only when the peer does not
+//! finish.
+
+mod common;
+
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use winasio::iocp::{OpResult, ThreadPool};
+use winasio::net::{
+    CloseOutcome, ReadOutcome, TcpListener, UnixListener, UnixSocketAddr, UnixStream,
+    DEFAULT_DRAIN_LIMIT,
+};
+
+#[test]
+fn a_peer_that_finishes_gets_an_orderly_close() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let mut client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (server, _) = common::block_on(listener.accept()).expect("accept");
+
+    std::thread::scope(|scope| {
+        let peer = scope.spawn(move || {
+            // Still sending when the server starts closing; then it reads to
+            // the end of the stream, as a well-behaved peer does, and closes.
+            client.write_all(b"late data").unwrap();
+            let mut rest = Vec::new();
+            client
+                .read_to_end(&mut rest)
+                .expect("an orderly end, not a reset");
+            rest
+        });
+        let outcome =
+            common::block_on(server.close_gracefully(Duration::from_secs(5))).expect("close");
+        assert_eq!(outcome, CloseOutcome::Graceful { discarded: 9 });
+        assert!(outcome.is_graceful());
+        assert!(peer.join().unwrap().is_empty());
+    });
+}
+
+#[test]
+fn a_peer_that_never_closes_is_reset_at_the_deadline() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let mut client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (server, _) = common::block_on(listener.accept()).expect("accept");
+
+    let started = Instant::now();
+    let outcome =
+        common::block_on(server.close_gracefully(Duration::from_millis(200))).expect("close");
+    assert_eq!(outcome, CloseOutcome::TimedOut { discarded: 0 });
+    assert!(started.elapsed() >= Duration::from_millis(200));
+
+    // The shutdown still reached the client before the reset.
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut byte = [0u8; 1];
+    match client.read(&mut byte) {
+        Ok(0) | Err(_) => {}
+        Ok(n) => panic!("the client was sent {n} bytes"),
+    }
+}
+
+#[test]
+fn a_read_timeout_does_not_cut_the_drain_short() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (server, _) = common::block_on(listener.accept()).expect("accept");
+    server
+        .set_read_timeout(Some(Duration::from_millis(50)))
+        .unwrap();
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            // Silent for several read timeouts, then an orderly close.
+            std::thread::sleep(Duration::from_millis(300));
+            drop(client);
+        });
+        let outcome =
+            common::block_on(server.close_gracefully(Duration::from_secs(5))).expect("close");
+        assert_eq!(outcome, CloseOutcome::Graceful { discarded: 0 });
+    });
+}
+
+#[test]
+fn a_peer_that_keeps_sending_is_reset_past_the_drain_limit() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let mut client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (server, _) = common::block_on(listener.accept()).expect("accept");
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            // Fails once the server resets; that is the point.
+            let chunk = vec![0u8; 64 * 1024];
+            while client.write_all(&chunk).is_ok() {}
+        });
+        let outcome =
+            common::block_on(server.close_gracefully(Duration::from_secs(10))).expect("close");
+        match outcome {
+            CloseOutcome::DrainLimit { discarded } => assert!(discarded > DEFAULT_DRAIN_LIMIT),
+            other => panic!("expected the drain limit, got {other:?}"),
+        }
+    });
+}
+
+#[test]
+fn the_drain_limit_can_be_chosen() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let mut client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (server, _) = common::block_on(listener.accept()).expect("accept");
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let chunk = vec![0u8; 4 * 1024];
+            while client.write_all(&chunk).is_ok() {}
+        });
+        let outcome = common::block_on(server.close_gracefully_with(Duration::from_secs(10), 1024))
+            .expect("close");
+        match outcome {
+            CloseOutcome::DrainLimit { discarded } => {
+                assert!(discarded > 1024);
+                assert!(discarded < DEFAULT_DRAIN_LIMIT, "the chosen limit applied");
+            }
+            other => panic!("expected the drain limit, got {other:?}"),
+        }
+    });
+}
+
+#[test]
+fn unix_streams_close_gracefully_too() {
+    let _guard = winasio::net::socket_guard();
+    let mut path = std::env::temp_dir();
+    path.push(format!("winasio-graceful-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let addr = UnixSocketAddr::from_pathname(&path).expect("a temp path fits");
+
+    let listener = UnixListener::bind(&ThreadPool, &addr).expect("bind");
+    let client = common::block_on(UnixStream::connect(&ThreadPool, &addr)).expect("connect");
+    let (server, _) = common::block_on(listener.accept()).expect("accept");
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let OpResult(written, _) = common::block_on(client.write(b"bye".to_vec()));
+            written.expect("write");
+            let OpResult(read, _) = common::block_on(client.read(vec![0u8; 8]));
+            assert_eq!(read.expect("read"), ReadOutcome::ClosedPeer);
+        });
+        let outcome =
+            common::block_on(server.close_gracefully(Duration::from_secs(5))).expect("close");
+        assert_eq!(outcome, CloseOutcome::Graceful { discarded: 3 });
+    });
+
+    drop(listener);
+    let _ = std::fs::remove_file(&path);
+}
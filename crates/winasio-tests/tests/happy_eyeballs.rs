@@ -0,0 +1,92 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Racing connects with `HappyEyeballs`.
+//!
+//! The attempt ordering and delays are unit-tested in the crate without
+//! sockets; these check the driver against real connects on loopback.
+
+mod common;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use winasio::iocp::ThreadPool;
+use winasio::net::{ConnectError, HappyEyeballs, SocketError, TcpListener};
+
+fn v4(port: u16) -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port)
+}
+
+/// A loopback port nothing is listening on.
+fn dead_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .expect("probe port")
+        .port()
+}
+
+#[test]
+fn a_refused_address_hands_over_without_waiting_out_the_delay() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, v4(0)).expect("bind");
+    let live = listener.local_addr();
+
+    let started = Instant::now();
+    let stream = common::block_on(
+        HappyEyeballs::new()
+            .delay(Duration::from_secs(20))
+            .connect(&ThreadPool, [v4(dead_port()), live]),
+    )
+    .expect("connect");
+    // Well under the delay: the refusal released the second attempt.
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(stream.peer_addr().unwrap(), live);
+}
+
+#[test]
+fn a_silent_address_is_raced_past() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, v4(0)).expect("bind");
+    let live = listener.local_addr();
+    // TEST-NET-1 (RFC 5737): nothing answers, so the attempt either hangs
+    // until it is cancelled or fails as unreachable.
+    let silent = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), live.port());
+
+    let started = Instant::now();
+    let stream = common::block_on(
+        HappyEyeballs::new()
+            .delay(Duration::from_millis(50))
+            .connect(&ThreadPool, [silent, live]),
+    )
+    .expect("connect");
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(stream.peer_addr().unwrap(), live);
+}
+
+#[test]
+fn every_failure_is_reported_in_start_order() {
+    let _guard = winasio::net::socket_guard();
+    let port = dead_port();
+    let v6 = SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), port);
+
+    let err = common::block_on(HappyEyeballs::new().connect(&ThreadPool, [v4(port), v4(port), v6]))
+        .expect_err("nothing listens there");
+    // Interleaved: the first answer's family, then the other, then the rest.
+    let tried: Vec<_> = err.attempts().iter().map(|(addr, _)| *addr).collect();
+    assert_eq!(tried, [v4(port), v6, v4(port)], "got {err:?}");
+    assert!(matches!(
+        err.attempts()[0].1,
+        SocketError::ConnectionRefused
+    ));
+}
+
+#[test]
+fn no_addresses_is_its_own_error() {
+    let err = common::block_on(HappyEyeballs::new().connect(&ThreadPool, Vec::<SocketAddr>::new()))
+        .expect_err("nothing to connect to");
+    assert!(matches!(err, ConnectError::NoAddresses), "got {err:?}");
+}
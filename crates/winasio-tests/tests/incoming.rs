@@ -0,0 +1,74 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Listeners with several `AcceptEx` operations outstanding.
+
+mod common;
+
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use winasio::iocp::{Proactor, ThreadPool};
+use winasio::net::{TcpListener, UnixListener, UnixSocketAddr, UnixStream};
+
+#[test]
+fn a_burst_larger_than_the_depth_is_accepted_in_full() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let addr = listener.local_addr();
+    let mut incoming = listener.incoming(4);
+
+    // Connect everyone before accepting anyone: the burst lands on four
+    // posted accepts and the backlog behind them.
+    let clients: Vec<_> = (0..16)
+        .map(|_| std::net::TcpStream::connect(addr).expect("connect"))
+        .collect();
+    let expected: HashSet<_> = clients.iter().map(|c| c.local_addr().unwrap()).collect();
+
+    let mut peers = HashSet::new();
+    for _ in 0..clients.len() {
+        let (stream, peer) = common::block_on(incoming.next()).expect("accept");
+        assert_eq!(stream.peer_addr().unwrap(), peer);
+        peers.insert(peer);
+    }
+    assert_eq!(peers, expected);
+}
+
+#[test]
+fn incoming_runs_on_a_caller_driven_listener() {
+    let _guard = winasio::net::socket_guard();
+    let proactor = Rc::new(Proactor::new().expect("proactor"));
+    let listener = TcpListener::bind(&proactor, common::v4_local()).expect("bind");
+    let mut incoming = listener.incoming(2);
+
+    let clients: Vec<_> = (0..3)
+        .map(|_| std::net::TcpStream::connect(listener.local_addr()).expect("connect"))
+        .collect();
+    for _ in 0..clients.len() {
+        common::drive_proactor(&proactor, incoming.next()).expect("accept");
+    }
+}
+
+#[test]
+fn unix_listeners_keep_several_accepts_outstanding() {
+    let _guard = winasio::net::socket_guard();
+    let mut path = std::env::temp_dir();
+    path.push(format!("winasio-incoming-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let addr = UnixSocketAddr::from_pathname(&path).expect("a temp path fits");
+
+    let listener = UnixListener::bind(&ThreadPool, &addr).expect("bind");
+    let mut incoming = listener.incoming(3);
+    for _ in 0..5 {
+        let _client = common::block_on(UnixStream::connect(&ThreadPool, &addr)).expect("connect");
+        let (_server, peer) = common::block_on(incoming.next()).expect("accept");
+        assert!(peer.is_unnamed());
+    }
+
+    drop(incoming);
+    drop(listener);
+    let _ = std::fs::remove_file(&path);
+}
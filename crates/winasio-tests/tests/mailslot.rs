@@ -0,0 +1,135 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Mailslot servers and clients on both completion backends.
+
+mod common;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use windows::Win32::Foundation::ERROR_INSUFFICIENT_BUFFER;
+
+use winasio::iocp::{OpResult, Proactor, ThreadPool};
+use winasio::mailslot::{MailslotClient, MailslotOptions, MailslotTarget, SetupError};
+
+fn assert_pending<F: Future>(future: &mut Pin<Box<F>>) {
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(
+        matches!(future.as_mut().poll(&mut cx), Poll::Pending),
+        "operation must be pending"
+    );
+}
+
+#[test]
+fn messages_queue_in_order_and_are_read_whole() {
+    let name = common::unique_pipe_name("messages_queue_in_order");
+    let server = MailslotOptions::new(name.as_str())
+        .create(&ThreadPool)
+        .unwrap();
+    assert_eq!(server.next_message_size().unwrap(), None);
+    assert_eq!(server.message_count().unwrap(), 0);
+
+    let client = MailslotClient::open(&ThreadPool, &name).unwrap();
+    for message in [&b"first"[..], b"", b"third one"] {
+        let OpResult(written, _) = common::block_on(client.write(message.to_vec()));
+        assert_eq!(written.unwrap(), message.len());
+    }
+    assert_eq!(server.message_count().unwrap(), 3);
+    assert_eq!(server.next_message_size().unwrap(), Some(5));
+
+    assert_eq!(common::block_on(server.read_message()).unwrap(), b"first");
+    assert_eq!(common::block_on(server.read_message()).unwrap(), b"");
+    assert_eq!(server.next_message_size().unwrap(), Some(9));
+    assert_eq!(
+        common::block_on(server.read_message()).unwrap(),
+        b"third one"
+    );
+    assert_eq!(server.message_count().unwrap(), 0);
+}
+
+#[test]
+fn a_message_that_does_not_fit_stays_queued() {
+    let name = common::unique_pipe_name("a_message_that_does_not_fit");
+    let server = MailslotOptions::new(name.as_str())
+        .create(&ThreadPool)
+        .unwrap();
+    let client = MailslotClient::open(&ThreadPool, &name).unwrap();
+    let OpResult(written, _) = common::block_on(client.write(b"too long".to_vec()));
+    written.unwrap();
+
+    let OpResult(read, _) = common::block_on(server.read(Vec::with_capacity(4)));
+    assert_eq!(
+        read.unwrap_err().code(),
+        ERROR_INSUFFICIENT_BUFFER.to_hresult()
+    );
+    assert_eq!(server.next_message_size().unwrap(), Some(8));
+
+    let OpResult(read, buffer) = common::block_on(server.read(Vec::with_capacity(8)));
+    assert_eq!(read.unwrap(), 8);
+    assert_eq!(buffer, b"too long");
+}
+
+#[test]
+fn a_waiting_read_message_grows_for_a_large_message() {
+    let name = common::unique_pipe_name("a_waiting_read_message_grows");
+    let server = MailslotOptions::new(name.as_str())
+        .create(&ThreadPool)
+        .unwrap();
+    let client = MailslotClient::open(&ThreadPool, &name).unwrap();
+
+    // Nothing is queued when the read starts, so its first buffer is too
+    // small for what arrives.
+    let mut read = Box::pin(server.read_message());
+    assert_pending(&mut read);
+    let large: Vec<u8> = (0..20_000u32).map(|n| (n % 251) as u8).collect();
+    let OpResult(written, _) = common::block_on(client.write(large.clone()));
+    assert_eq!(written.unwrap(), large.len());
+
+    assert_eq!(common::block_on(read).unwrap(), large);
+}
+
+#[test]
+fn mailslots_work_caller_driven() {
+    let name = common::unique_pipe_name("mailslots_work_caller_driven");
+    let proactor = Rc::new(Proactor::new().unwrap());
+    let server = MailslotOptions::new(name.as_str())
+        .create(&proactor)
+        .unwrap();
+    let client = MailslotClient::open(&proactor, &name).unwrap();
+
+    let read = server.read_message();
+    let OpResult(written, _) =
+        common::drive_proactor(&proactor, client.write(b"status: up".to_vec()));
+    written.unwrap();
+    assert_eq!(
+        common::drive_proactor(&proactor, read).unwrap(),
+        b"status: up"
+    );
+}
+
+#[test]
+fn names_are_exclusive_and_must_exist_to_be_opened() {
+    let name = common::unique_pipe_name("names_are_exclusive");
+    assert!(matches!(
+        MailslotClient::open(&ThreadPool, &name),
+        Err(SetupError::NotFound)
+    ));
+
+    let _server = MailslotOptions::new(name.as_str())
+        .create(&ThreadPool)
+        .unwrap();
+    assert!(MailslotOptions::new(name.as_str())
+        .create(&ThreadPool)
+        .is_err());
+
+    assert!(matches!(
+        MailslotClient::open_target(&ThreadPool, &MailslotTarget::Computer("*".into()), &name),
+        Err(SetupError::InvalidName)
+    ));
+}
@@ -7,7 +7,9 @@
 mod common;
 
 use std::future::Future;
+use std::os::windows::io::AsRawHandle;
 use std::pin::Pin;
+use std::process::{Command, Stdio};
 use std::rc::Rc;
 use std::sync::Arc;
 use std::task::{Context, Poll, Waker};
@@ -17,10 +19,16 @@ use winasio::iocp::{
     live_operations, OpResult, Proactor, Registrar, RegistrationError, ThreadPool, ThreadPoolIo,
 };
 use winasio::pipe::{
-    AccessDirection, ClientOptions, NamedPipe, ReadOutcome, ServerOptions, SetupError,
-    MAX_NAME_COMPONENT_LEN,
+    self, AccessDirection, ClientOptions, NamedPipe, PairEnd, PairOptions, PipeListener, PipeMode,
+    ReadOutcome, ServerOptions, SetupError, MAX_NAME_COMPONENT_LEN,
 };
-use windows::Win32::Foundation::{ERROR_INVALID_PARAMETER, ERROR_OPERATION_ABORTED};
+use windows::Win32::Foundation::{
+    CloseHandle, GetHandleInformation, ERROR_INVALID_PARAMETER, ERROR_OPERATION_ABORTED, HANDLE,
+    HANDLE_FLAG_INHERIT,
+};
+use windows::Win32::Security::TOKEN_QUERY;
+use windows::Win32::System::Pipes::PIPE_UNLIMITED_INSTANCES;
+use windows::Win32::System::Threading::{GetCurrentThread, OpenThreadToken};
 
 fn assert_pending<F: Future>(future: &mut Pin<Box<F>>) {
     let mut cx = Context::from_waker(Waker::noop());
@@ -103,6 +111,42 @@ fn byte_exchange_both_directions() {
     assert_eq!(got, b"response");
 }
 
+fn thread_has_token() -> bool {
+    let mut token = HANDLE::default();
+    // SAFETY: the pseudo-handle for the current thread is always valid and
+    // `token` is a valid out pointer; an opened token is closed at once.
+    unsafe {
+        let opened = OpenThreadToken(GetCurrentThread(), TOKEN_QUERY, true, &mut token).is_ok();
+        if opened {
+            let _ = CloseHandle(token);
+        }
+        opened
+    }
+}
+
+#[test]
+fn peer_identity_and_impersonation() {
+    let name = common::unique_pipe_name("peer_identity_and_impersonation");
+    let (server, client) = connected_pair(&name);
+
+    let me = std::process::id();
+    assert_eq!(server.client_process_id().unwrap(), me);
+    assert_eq!(client.server_process_id().unwrap(), me);
+    server.client_session_id().unwrap();
+
+    // Windows only allows impersonation once the server has read something.
+    let OpResult(written, _) = common::block_on(client.write(b"hello".to_vec()));
+    written.unwrap();
+    let OpResult(read, _) = common::block_on(server.read(Vec::with_capacity(8)));
+    assert_eq!(read.unwrap(), ReadOutcome::Bytes(5));
+
+    assert!(!thread_has_token());
+    let guard = server.impersonate().unwrap();
+    assert!(thread_has_token(), "impersonation installs a thread token");
+    drop(guard);
+    assert!(!thread_has_token(), "dropping the guard reverts to self");
+}
+
 #[test]
 fn disconnect_and_reuse_serves_two_clients() {
     let name = common::unique_pipe_name("disconnect_and_reuse_serves_two_clients");
@@ -176,6 +220,135 @@ fn server_options_cover_limits_and_first_instance() {
     drop(first);
 }
 
+#[test]
+fn server_options_apply_security_descriptors() {
+    let denied = common::unique_pipe_name("server_options_sddl_denied");
+    let mut options = ServerOptions::new(denied.as_str());
+    options.security_descriptor_sddl("D:(D;;GA;;;WD)");
+    let server = options.create(&ThreadPool).unwrap();
+    assert!(matches!(
+        ClientOptions::new(denied.as_str()).connect(&ThreadPool),
+        Err(SetupError::AccessDenied)
+    ));
+    drop(server);
+
+    let allowed = common::unique_pipe_name("server_options_sddl_allowed");
+    let mut options = ServerOptions::new(allowed.as_str());
+    options
+        .security_descriptor_sddl("D:(A;;GA;;;WD)")
+        .reject_remote_clients(true)
+        .low_integrity(true);
+    let server = options.create(&ThreadPool).unwrap();
+    let mut accept = Box::pin(server.connect());
+    assert_pending(&mut accept);
+    let client = ClientOptions::new(allowed.as_str())
+        .connect(&ThreadPool)
+        .unwrap();
+    let server = common::block_on(accept).unwrap();
+    drop((server, client));
+}
+
+#[test]
+fn server_options_report_invalid_security_settings() {
+    let name = common::unique_pipe_name("server_options_sddl_invalid");
+    let mut options = ServerOptions::new(name.as_str());
+    options.security_descriptor_sddl("D:(not an ace)");
+    assert!(matches!(
+        options.create(&ThreadPool),
+        Err(SetupError::InvalidSecurityDescriptor(_))
+    ));
+
+    options
+        .security_descriptor_sddl("D:(A;;GA;;;WD)S:(ML;;NW;;;ME)")
+        .low_integrity(true);
+    assert!(matches!(
+        options.create(&ThreadPool),
+        Err(SetupError::InvalidOptions(_))
+    ));
+}
+
+trait ListenerTestRegistrar: Registrar + Clone {
+    fn drive<F: Future>(&self, future: F) -> F::Output;
+}
+
+impl ListenerTestRegistrar for ThreadPool {
+    fn drive<F: Future>(&self, future: F) -> F::Output {
+        common::block_on(future)
+    }
+}
+
+impl ListenerTestRegistrar for Rc<Proactor> {
+    fn drive<F: Future>(&self, future: F) -> F::Output {
+        common::drive_proactor(self.as_ref(), future)
+    }
+}
+
+fn listener_body<R: ListenerTestRegistrar>(registrar: &R, name: &str) {
+    let mut options = ServerOptions::new(name);
+    options
+        .max_instances(PIPE_UNLIMITED_INSTANCES)
+        .first_instance(true);
+    let mut listener = PipeListener::bind(registrar, &options, 2).unwrap();
+    assert_eq!(listener.waiting(), 2);
+
+    // Every client tags itself with one byte as soon as it connects, so an
+    // accepted server can tell which client it got.
+    let connect_and_tag = |tag: u8| {
+        let client = ClientOptions::new(name).connect(&ThreadPool).unwrap();
+        let OpResult(written, _) = common::block_on(client.write(vec![tag]));
+        written.unwrap();
+        client
+    };
+
+    // Both waiting instances take a client before anyone calls accept.
+    let mut clients: Vec<_> = (0..2).map(connect_and_tag).collect();
+
+    let mut tags = Vec::new();
+    let mut servers = Vec::new();
+    for round in 0..5u8 {
+        let server = registrar.drive(listener.accept()).unwrap();
+        assert_eq!(listener.waiting(), 2, "each accept replaces its instance");
+        // A client arriving right after an accept never sees a missing name.
+        clients.push(connect_and_tag(round + 2));
+
+        let OpResult(read, tag) = registrar.drive(server.read(Vec::with_capacity(1)));
+        assert_eq!(read.unwrap(), ReadOutcome::Bytes(1));
+        tags.push(tag[0]);
+        servers.push(server);
+    }
+
+    tags.sort_unstable();
+    tags.dedup();
+    assert_eq!(tags.len(), 5, "every accept hands out a different client");
+    assert!(tags.iter().all(|&tag| tag < 7));
+    drop((servers, clients, listener));
+}
+
+#[test]
+fn pipe_listener_keeps_instances_waiting_thread_pool() {
+    let name = common::unique_pipe_name("pipe_listener_thread_pool");
+    listener_body(&ThreadPool, &name);
+}
+
+#[test]
+fn pipe_listener_keeps_instances_waiting_caller_driven() {
+    let name = common::unique_pipe_name("pipe_listener_caller_driven");
+    let proactor = Rc::new(Proactor::new().unwrap());
+    listener_body(&proactor, &name);
+}
+
+#[test]
+fn pipe_listener_rejects_a_limit_with_no_room_for_connections() {
+    let name = common::unique_pipe_name("pipe_listener_limit");
+    let mut options = ServerOptions::new(name.as_str());
+    options.max_instances(2);
+    assert!(matches!(
+        PipeListener::bind(&ThreadPool, &options, 2),
+        Err(SetupError::InvalidOptions(_))
+    ));
+    assert!(PipeListener::bind(&ThreadPool, &options, 1).is_ok());
+}
+
 #[test]
 fn client_busy_is_reported_without_waiting() {
     let name = common::unique_pipe_name("client_busy_is_reported_without_waiting");
@@ -222,6 +395,101 @@ fn byte_mode_is_default_on_both_ends() {
     assert_eq!(got, b"1234");
 }
 
+#[test]
+fn pairs_are_connected_without_a_name_or_a_connect() {
+    let (a, b) = pipe::pair(&ThreadPool).unwrap();
+    let OpResult(written, _) = common::block_on(a.write(b"ping".to_vec()));
+    assert_eq!(written.unwrap(), 4);
+    let OpResult(read, got) = common::block_on(b.read(Vec::with_capacity(8)));
+    assert_eq!(read.unwrap(), ReadOutcome::Bytes(4));
+    assert_eq!(got, b"ping");
+    let OpResult(written, _) = common::block_on(b.write(b"pong".to_vec()));
+    assert_eq!(written.unwrap(), 4);
+    let OpResult(read, got) = common::block_on(a.read(Vec::with_capacity(8)));
+    assert_eq!(read.unwrap(), ReadOutcome::Bytes(4));
+    assert_eq!(got, b"pong");
+
+    // One-way message pair: the second end reads whole messages and cannot
+    // write back.
+    let mut options = PairOptions::new();
+    options
+        .access(AccessDirection::Outbound)
+        .mode(PipeMode::Message);
+    let (a, b) = options.pair(&ThreadPool).unwrap();
+    for message in [&b"one"[..], b"", b"three"] {
+        let OpResult(written, _) = common::block_on(a.write_message(message.to_vec()));
+        assert_eq!(written.unwrap(), message.len());
+    }
+    for message in [&b"one"[..], b"", b"three"] {
+        assert_eq!(common::block_on(b.read_message(64)).unwrap(), message);
+    }
+    let OpResult(written, returned) = common::block_on(b.write(b"back".to_vec()));
+    assert!(written.is_err());
+    assert_eq!(returned, b"back");
+}
+
+fn is_inheritable(handle: HANDLE) -> bool {
+    let mut flags = 0u32;
+    // SAFETY: the caller's handle is alive for the call.
+    unsafe { GetHandleInformation(handle, &mut flags) }.unwrap();
+    flags & HANDLE_FLAG_INHERIT.0 != 0
+}
+
+#[test]
+fn pairs_make_only_the_chosen_end_inheritable() {
+    let (a, b) = pipe::pair(&ThreadPool).unwrap();
+    assert!(!is_inheritable(a.handle()));
+    assert!(!is_inheritable(b.handle()));
+
+    let mut options = PairOptions::new();
+    options.inheritable(Some(PairEnd::First));
+    let (a, b) = options.pair(&ThreadPool).unwrap();
+    assert!(is_inheritable(a.handle()));
+    assert!(!is_inheritable(b.handle()));
+
+    options.inheritable(Some(PairEnd::Second));
+    let (a, b) = options.pair(&ThreadPool).unwrap();
+    assert!(!is_inheritable(a.handle()));
+    assert!(is_inheritable(b.handle()));
+    let (ours, theirs) = options.pair_for_child(&ThreadPool).unwrap();
+    assert!(!is_inheritable(ours.handle()));
+    assert!(is_inheritable(HANDLE(theirs.as_raw_handle())));
+
+    // Still connected either way.
+    let OpResult(written, _) = common::block_on(a.write(b"ping".to_vec()));
+    assert_eq!(written.unwrap(), 4);
+    let OpResult(read, got) = common::block_on(b.read(Vec::with_capacity(8)));
+    assert_eq!(read.unwrap(), ReadOutcome::Bytes(4));
+    assert_eq!(got, b"ping");
+}
+
+#[test]
+fn a_child_end_serves_as_stdio_without_being_inheritable() {
+    let mut options = PairOptions::new();
+    options.access(AccessDirection::Outbound);
+    let (ours, theirs) = options.pair_for_child(&ThreadPool).unwrap();
+    assert!(!is_inheritable(HANDLE(theirs.as_raw_handle())));
+
+    let mut command = Command::new("cmd");
+    command
+        .args(["/C", "sort"])
+        .stdin(theirs)
+        .stdout(Stdio::piped());
+    let child = command.spawn().unwrap();
+    // The command holds this process's copy of the child's end; until it is
+    // dropped, `sort` would never see end of input.
+    drop(command);
+
+    let written = common::block_on(ours.write_all(b"pear\r\napple\r\n".to_vec()));
+    assert!(written.result.is_ok());
+    drop(ours);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let sorted = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(sorted.lines().collect::<Vec<_>>(), ["apple", "pear"]);
+}
+
 #[test]
 fn name_validation_and_bare_name_interop() {
     let too_long = "x".repeat(MAX_NAME_COMPONENT_LEN + 1);
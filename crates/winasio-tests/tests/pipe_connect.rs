@@ -133,6 +133,98 @@ fn busy_connect_is_distinct_from_not_found() {
     drop((server, first));
 }
 
+#[test]
+fn connect_wait_times_out_on_an_absent_pipe() {
+    let absent = common::unique_pipe_name("connect_wait_absent");
+    let started = Instant::now();
+    let result = common::block_on(
+        ClientOptions::new(&absent).connect_wait(&ThreadPool, Duration::from_millis(100)),
+    );
+    assert!(matches!(result, Err(SetupError::TimedOut)));
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(100));
+    assert!(
+        elapsed < Duration::from_secs(2),
+        "the deadline bounds the wait"
+    );
+
+    // Failures that waiting cannot fix are not retried.
+    let started = Instant::now();
+    assert!(matches!(
+        common::block_on(
+            ClientOptions::new("bad\\name").connect_wait(&ThreadPool, Duration::from_secs(5))
+        ),
+        Err(SetupError::InvalidName)
+    ));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn connect_wait_rides_out_a_server_restart() {
+    let name = common::unique_pipe_name("connect_wait_server_restart");
+    let client = {
+        let name = name.clone();
+        std::thread::spawn(move || {
+            common::block_on(
+                ClientOptions::new(&name).connect_wait(&ThreadPool, Duration::from_secs(5)),
+            )
+        })
+    };
+
+    // The client starts before any instance exists.
+    std::thread::sleep(Duration::from_millis(50));
+    let server = ServerOptions::new(&name).create(&ThreadPool).unwrap();
+    let server = common::block_on(server.connect()).unwrap();
+    let client = client.join().unwrap().unwrap();
+    drop((server, client));
+}
+
+#[test]
+fn connect_wait_accepts_an_unbounded_timeout() {
+    let name = common::unique_pipe_name("connect_wait_unbounded");
+    let client = {
+        let name = name.clone();
+        std::thread::spawn(move || {
+            common::block_on(ClientOptions::new(&name).connect_wait(&ThreadPool, Duration::MAX))
+        })
+    };
+
+    std::thread::sleep(Duration::from_millis(50));
+    let server = ServerOptions::new(&name).create(&ThreadPool).unwrap();
+    let server = common::block_on(server.connect()).unwrap();
+    let client = client.join().expect("no overflow panic").unwrap();
+    drop((server, client));
+}
+
+#[test]
+fn caller_driven_connect_wait_retries_a_busy_pipe() {
+    let name = common::unique_pipe_name("caller_driven_connect_wait_busy");
+    let mut options = ServerOptions::new(&name);
+    options.max_instances(1);
+    let server = options.create(&ThreadPool).unwrap();
+    let holder = ClientOptions::new(&name).connect(&ThreadPool).unwrap();
+    let server = common::block_on(server.connect()).unwrap();
+
+    // Another thread frees the only instance while this one waits.
+    let release = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        drop(holder);
+        let server = server.disconnect().unwrap();
+        common::block_on(server.connect()).unwrap()
+    });
+
+    let proactor = Rc::new(Proactor::new().unwrap());
+    let mut waiting = ClientOptions::new(&name);
+    waiting.retry_backoff(Duration::from_millis(2), Duration::from_millis(20));
+    let client = common::drive_proactor(
+        &proactor,
+        waiting.connect_wait(&proactor, Duration::from_secs(4)),
+    )
+    .unwrap();
+    let server = release.join().unwrap();
+    drop((server, client));
+}
+
 #[test]
 fn access_denied_direction_mismatch_is_distinct() {
     let name = common::unique_pipe_name("access_denied_direction_mismatch_is_distinct");
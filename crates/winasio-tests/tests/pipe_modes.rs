@@ -12,7 +12,8 @@ use std::task::{Context, Poll, Waker};
 
 use winasio::iocp::{OpResult, ThreadPool, ThreadPoolIo};
 use winasio::pipe::{
-    AccessDirection, ClientOptions, NamedPipe, PipeMode, ReadOutcome, ServerOptions, SetupError,
+    AccessDirection, ClientOptions, MessageError, NamedPipe, PipeMode, ReadOutcome, ServerOptions,
+    SetupError,
 };
 
 fn assert_pending<F: Future>(future: &mut Pin<Box<F>>) {
@@ -76,6 +77,99 @@ fn oversized_message_reports_more_data_then_remainder() {
     assert_eq!(got, b"defgh");
 }
 
+#[test]
+fn whole_messages_are_reassembled_and_oversized_ones_drained() {
+    let name = common::unique_pipe_name("whole_messages_are_reassembled");
+    // Room for every message at once, so the writes complete before any read.
+    let mut server_options = ServerOptions::new(name.as_str());
+    server_options
+        .pipe_type(PipeMode::Message)
+        .out_buffer_size(1 << 16);
+    let mut client_options = ClientOptions::new(name.as_str());
+    client_options.read_mode(PipeMode::Message);
+    let (server, client) = connected_pair(&name, &server_options, &client_options);
+
+    let large: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+    for message in [large.clone(), Vec::new(), vec![7u8; 9000], b"tail".to_vec()] {
+        let len = message.len();
+        let OpResult(written, _) = common::block_on(server.write_message(message));
+        assert_eq!(written.unwrap(), len);
+    }
+
+    assert_eq!(client.peek_message_len().unwrap(), Some(large.len()));
+    let got = common::block_on(client.read_message(large.len())).unwrap();
+    assert_eq!(got, large);
+
+    let empty = common::block_on(client.read_message(0)).unwrap();
+    assert!(empty.is_empty());
+
+    assert_eq!(client.peek_message_len().unwrap(), Some(9000));
+    assert!(matches!(
+        common::block_on(client.read_message(4096)),
+        Err(MessageError::TooLarge { len: 9000 })
+    ));
+
+    // The oversized message was drained, so the next read is aligned.
+    let got = common::block_on(client.read_message(64)).unwrap();
+    assert_eq!(got, b"tail");
+    assert_eq!(client.peek_message_len().unwrap(), None);
+
+    drop(server);
+    assert!(matches!(
+        common::block_on(client.read_message(64)),
+        Err(MessageError::ClosedPeer)
+    ));
+}
+
+#[test]
+fn transact_sends_a_request_and_returns_the_reply() {
+    let name = common::unique_pipe_name("transact_sends_a_request_and_returns_the_reply");
+    let (server, client) = message_pair(&name);
+
+    for (request, reply) in [
+        (&b"ping"[..], &b"pong"[..]),
+        (&b"status"[..], &b"all systems go"[..]),
+    ] {
+        let mut transact = Box::pin(client.transact(request.to_vec(), Vec::with_capacity(8)));
+        assert_pending(&mut transact);
+
+        let OpResult(read, got) = common::block_on(server.read(Vec::with_capacity(64)));
+        assert_eq!(read.unwrap(), ReadOutcome::Bytes(request.len()));
+        assert_eq!(got, request);
+        let OpResult(written, _) = common::block_on(server.write_message(reply.to_vec()));
+        assert_eq!(written.unwrap(), reply.len());
+
+        let OpResult(outcome, (sent, response)) = common::block_on(transact);
+        assert_eq!(sent, request);
+        if reply.len() <= 8 {
+            assert_eq!(outcome.unwrap(), ReadOutcome::Bytes(reply.len()));
+            assert_eq!(response, reply);
+        } else {
+            // The rest of the reply stays queued as the same message.
+            assert_eq!(outcome.unwrap(), ReadOutcome::MoreData(8));
+            assert_eq!(response, &reply[..8]);
+            let rest = common::block_on(client.read_message(64)).unwrap();
+            assert_eq!(rest, &reply[8..]);
+        }
+    }
+}
+
+#[test]
+fn transact_reports_a_server_that_leaves_as_a_closed_peer() {
+    let name = common::unique_pipe_name("transact_reports_a_server_that_leaves");
+    let (server, client) = message_pair(&name);
+
+    let mut transact = Box::pin(client.transact(b"ping".to_vec(), Vec::with_capacity(8)));
+    assert_pending(&mut transact);
+    let OpResult(read, _) = common::block_on(server.read(Vec::with_capacity(64)));
+    assert_eq!(read.unwrap(), ReadOutcome::Bytes(4));
+    drop(server);
+
+    let OpResult(outcome, (_, response)) = common::block_on(transact);
+    assert_eq!(outcome.unwrap(), ReadOutcome::ClosedPeer);
+    assert!(response.is_empty());
+}
+
 #[test]
 fn byte_mode_oversized_payload_is_ordinary_partial_transfer() {
     let name = common::unique_pipe_name("byte_mode_oversized_payload_is_ordinary_partial_transfer");
@@ -0,0 +1,92 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Typed socket options, on streams and through listener options.
+//!
+//! The peer is a blocking `std` socket wherever the option's effect is
+//! visible on the wire, so this crate's option code is not checked against
+//! itself.
+
+mod common;
+
+use std::io::Read;
+use std::time::Duration;
+
+use winasio::iocp::ThreadPool;
+use winasio::net::{Keepalive, SocketError, SocketOptions, TcpListener, TcpListenerOptions};
+
+#[test]
+fn accepted_connections_get_the_listener_options() {
+    let _guard = winasio::net::socket_guard();
+    let mut keepalive = Keepalive::new();
+    keepalive.idle(Duration::from_secs(45)).retries(4);
+    let mut accepted = SocketOptions::new();
+    accepted.nodelay(true).keepalive(keepalive);
+    let listener = TcpListener::bind_with(
+        &ThreadPool,
+        common::v4_local(),
+        TcpListenerOptions::new().accepted(accepted),
+    )
+    .expect("bind");
+
+    let _client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (stream, _) = common::block_on(listener.accept()).expect("accept");
+
+    assert!(stream.nodelay().unwrap());
+    let socket = stream.socket();
+    assert!(socket.keepalive().unwrap());
+    assert_eq!(socket.keepalive_idle().unwrap(), Duration::from_secs(45));
+    assert_eq!(socket.keepalive_retries().unwrap(), 4);
+}
+
+#[test]
+fn an_exclusive_listener_cannot_be_shared() {
+    let _guard = winasio::net::socket_guard();
+    let first = TcpListener::bind_with(
+        &ThreadPool,
+        common::v4_local(),
+        TcpListenerOptions::new().exclusive_address_use(true),
+    )
+    .expect("bind");
+    assert!(first.socket().exclusive_address_use().unwrap());
+
+    let second = TcpListener::bind_with(
+        &ThreadPool,
+        first.local_addr(),
+        TcpListenerOptions::new().reuse_address(true),
+    );
+    assert!(matches!(second, Err(SocketError::AddressInUse)));
+}
+
+#[test]
+fn an_abortive_linger_resets_the_peer() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let mut client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (stream, _) = common::block_on(listener.accept()).expect("accept");
+
+    stream.set_linger(Some(Duration::ZERO)).unwrap();
+    assert_eq!(stream.linger().unwrap(), Some(Duration::ZERO));
+    drop(stream);
+
+    let mut buf = [0u8; 1];
+    let err = client.read(&mut buf).expect_err("a reset, not a clean end");
+    assert_eq!(err.kind(), std::io::ErrorKind::ConnectionReset);
+}
+
+#[test]
+fn buffer_sizes_and_ttl_read_back() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let socket = listener.socket();
+
+    socket.set_recv_buffer_size(128 * 1024).unwrap();
+    assert_eq!(socket.recv_buffer_size().unwrap(), 128 * 1024);
+    socket.set_send_buffer_size(32 * 1024).unwrap();
+    assert_eq!(socket.send_buffer_size().unwrap(), 32 * 1024);
+    socket.set_ttl(17).unwrap();
+    assert_eq!(socket.ttl().unwrap(), 17);
+}
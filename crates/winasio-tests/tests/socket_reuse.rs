@@ -0,0 +1,199 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Sockets kept across connections with `DisconnectEx(TF_REUSE_SOCKET)`.
+//!
+//! In every test the *client* closes first. The side that closes first holds
+//! `TIME_WAIT`, and a reused socket in `TIME_WAIT` makes its next `AcceptEx`
+//! or `ConnectEx` wait it out — minutes, not a test's worth of time.
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+use winasio::iocp::{registrations, OpResult, ThreadPool, ThreadPoolIo};
+use winasio::net::{
+    live_sockets, ReadOutcome, ReusableSocket, TcpListener, TcpListenerOptions, TcpStream,
+};
+
+/// Wait for the peer's close on `stream`, then disconnect it for reuse.
+fn reuse_after_peer_close(stream: TcpStream<ThreadPoolIo>) -> ReusableSocket<ThreadPoolIo> {
+    let OpResult(read, _) = common::block_on(stream.read(vec![0u8; 1]));
+    assert_eq!(read.expect("read"), ReadOutcome::ClosedPeer);
+    common::block_on(stream.disconnect_for_reuse()).expect("disconnect")
+}
+
+#[test]
+fn a_recycled_socket_accepts_the_next_client_without_a_new_socket() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+
+    let client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (server, _) = common::block_on(listener.accept()).expect("accept");
+    let raw = server.socket().raw();
+    drop(client);
+    let reusable = reuse_after_peer_close(server);
+    assert_eq!(reusable.socket().raw(), raw);
+
+    let before = live_sockets();
+    let registered = registrations();
+    listener.recycle(reusable).expect("same family");
+    let mut client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (server, peer) = common::block_on(listener.accept()).expect("accept");
+
+    assert_eq!(server.socket().raw(), raw, "the recycled socket was used");
+    assert_eq!(
+        live_sockets(),
+        before,
+        "no socket was created for the accept"
+    );
+    assert_eq!(registrations(), registered, "nor registered again");
+    assert_eq!(peer, client.local_addr().unwrap());
+    client.write_all(b"again").unwrap();
+    let OpResult(read, buf) = common::block_on(server.read(vec![0u8; 5]));
+    assert_eq!(read.expect("read"), ReadOutcome::Bytes(5));
+    assert_eq!(&buf, b"again");
+}
+
+#[test]
+fn a_disconnected_socket_connects_again() {
+    let _guard = winasio::net::socket_guard();
+    let first = std::net::TcpListener::bind(common::v4_local()).expect("bind");
+    let second = std::net::TcpListener::bind(common::v4_local()).expect("bind");
+
+    let stream = common::block_on(TcpStream::connect(&ThreadPool, first.local_addr().unwrap()))
+        .expect("connect");
+    let raw = stream.socket().raw();
+    drop(first.accept().expect("accept"));
+    let reusable = reuse_after_peer_close(stream);
+
+    let before = live_sockets();
+    let registered = registrations();
+    let stream = common::block_on(TcpStream::connect_reusing(
+        reusable,
+        second.local_addr().unwrap(),
+    ))
+    .expect("connect on the reused socket");
+    assert_eq!(stream.socket().raw(), raw);
+    assert_eq!(live_sockets(), before);
+    assert_eq!(registrations(), registered);
+
+    let (mut peer, _) = second.accept().expect("accept");
+    let OpResult(written, _) = common::block_on(stream.write(b"hi".to_vec()));
+    assert_eq!(written.expect("write"), 2);
+    let mut buf = [0u8; 2];
+    peer.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hi");
+}
+
+#[test]
+fn a_socket_still_in_use_is_not_disconnected_for_reuse() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let _client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (server, _) = common::block_on(listener.accept()).expect("accept");
+
+    let before = live_sockets();
+    let held = server.socket().clone();
+    common::block_on(server.disconnect_for_reuse()).expect_err("a clone is still held");
+    assert_eq!(
+        live_sockets(),
+        before,
+        "the held clone keeps the socket open"
+    );
+    drop(held);
+    assert_eq!(
+        live_sockets(),
+        before - 1,
+        "and the failed stream closed it"
+    );
+}
+
+#[test]
+fn a_full_recycle_pool_closes_the_surplus() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind_with(
+        &ThreadPool,
+        common::v4_local(),
+        TcpListenerOptions::new().recycle_limit(1),
+    )
+    .expect("bind");
+
+    let mut reusable = Vec::new();
+    for _ in 0..2 {
+        let client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+        let (server, _) = common::block_on(listener.accept()).expect("accept");
+        drop(client);
+        reusable.push(reuse_after_peer_close(server));
+    }
+    let pooled = reusable[0].socket().raw();
+
+    let before = live_sockets();
+    for socket in reusable {
+        listener.recycle(socket).expect("accepted, same family");
+    }
+    assert_eq!(
+        live_sockets(),
+        before - 1,
+        "the socket past the limit was closed"
+    );
+
+    // The pooled socket serves the next accept.
+    let _client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (server, _) = common::block_on(listener.accept()).expect("accept on the pooled socket");
+    assert_eq!(server.socket().raw(), pooled);
+    assert_eq!(live_sockets(), before - 1, "no socket was created for it");
+}
+
+#[test]
+fn a_listener_refuses_a_socket_that_connected_out() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let peer = std::net::TcpListener::bind(common::v4_local()).expect("bind");
+
+    let stream = common::block_on(TcpStream::connect(&ThreadPool, peer.local_addr().unwrap()))
+        .expect("connect");
+    drop(peer.accept().expect("accept"));
+    let reusable = reuse_after_peer_close(stream);
+    let raw = reusable.socket().raw();
+
+    let refused = listener
+        .recycle(reusable)
+        .expect_err("still bound to its local port");
+
+    // Accepts still work, on a new socket rather than the refused one.
+    let _client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (server, _) = common::block_on(listener.accept()).expect("accept");
+    assert_ne!(server.socket().raw(), raw);
+
+    // The refused socket can still connect out again.
+    let again = std::net::TcpListener::bind(common::v4_local()).expect("bind");
+    let stream = common::block_on(TcpStream::connect_reusing(
+        refused,
+        again.local_addr().unwrap(),
+    ))
+    .expect("connect on the refused socket");
+    assert_eq!(stream.socket().raw(), raw);
+}
+
+#[test]
+fn a_listener_hands_back_a_socket_of_the_wrong_family() {
+    let _guard = winasio::net::socket_guard();
+    let v6 = SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 0);
+    let listener = TcpListener::bind(&ThreadPool, v6).expect("bind");
+    let v4 = std::net::TcpListener::bind(common::v4_local()).expect("bind");
+
+    let stream = common::block_on(TcpStream::connect(&ThreadPool, v4.local_addr().unwrap()))
+        .expect("connect");
+    drop(v4.accept().expect("accept"));
+    let reusable = reuse_after_peer_close(stream);
+
+    let refused = listener
+        .recycle(reusable)
+        .expect_err("v4 socket, v6 listener");
+    assert!(common::block_on(TcpStream::connect_reusing(refused, listener.local_addr())).is_err());
+}
@@ -0,0 +1,151 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Connect, accept, read and write deadlines.
+//!
+//! The point of each test is the pair of promises a deadline makes: it fails
+//! as `SocketError::TimedOut` rather than as a cancellation, and it gives the
+//! caller's state back — the buffer, or the connection itself.
+
+mod common;
+
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use winasio::iocp::{OpResult, Proactor, ThreadPool};
+use winasio::net::{ReadOutcome, SocketError, TcpListener, TcpStream};
+
+#[test]
+fn a_timed_out_read_returns_its_buffer_and_leaves_the_stream_usable() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let mut client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (stream, _) = common::block_on(listener.accept()).expect("accept");
+
+    stream
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    assert_eq!(stream.read_timeout(), Some(Duration::from_millis(100)));
+
+    let started = Instant::now();
+    let OpResult(result, buf) = common::block_on(stream.read(vec![0u8; 16]));
+    let err = result.expect_err("the client sent nothing");
+    assert!(matches!(
+        SocketError::from_win32(err),
+        SocketError::TimedOut
+    ));
+    assert!(started.elapsed() >= Duration::from_millis(100));
+    assert_eq!(buf.len(), 16, "the buffer comes back");
+
+    client.write_all(b"late").unwrap();
+    let OpResult(result, buf) = common::block_on(stream.read(buf));
+    assert_eq!(result.expect("read"), ReadOutcome::Bytes(4));
+    assert_eq!(&buf[..4], b"late");
+}
+
+#[test]
+fn a_read_timeout_works_on_a_caller_driven_stream() {
+    let _guard = winasio::net::socket_guard();
+    let proactor = Rc::new(Proactor::new().expect("proactor"));
+    let listener = TcpListener::bind(&proactor, common::v4_local()).expect("bind");
+    let _client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (stream, _) = common::drive_proactor(&proactor, listener.accept()).expect("accept");
+
+    stream
+        .set_read_timeout(Some(Duration::from_millis(50)))
+        .unwrap();
+    let OpResult(result, buf) = common::drive_proactor(&proactor, stream.read(vec![0u8; 8]));
+    assert!(matches!(
+        SocketError::from_win32(result.expect_err("nothing was sent")),
+        SocketError::TimedOut
+    ));
+    assert_eq!(buf.len(), 8);
+}
+
+#[test]
+fn accept_timeout_times_out_and_then_still_accepts() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+
+    let err = common::block_on(listener.accept_timeout(Duration::from_millis(50)))
+        .expect_err("nobody connected");
+    assert!(matches!(err, SocketError::TimedOut), "got {err:?}");
+
+    let _client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (_stream, peer) =
+        common::block_on(listener.accept_timeout(Duration::from_secs(5))).expect("accept");
+    assert!(peer.ip().is_loopback());
+}
+
+#[test]
+fn connect_timeout_gives_up_on_a_silent_host() {
+    let _guard = winasio::net::socket_guard();
+    // TEST-NET-1 (RFC 5737): nothing answers. Without a route the connect
+    // fails as unreachable before the deadline, which is also not a hang.
+    let silent = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 9);
+
+    let started = Instant::now();
+    let err = common::block_on(TcpStream::connect_timeout(
+        &ThreadPool,
+        silent,
+        Duration::from_millis(200),
+    ))
+    .expect_err("nothing answers");
+    assert!(
+        matches!(err, SocketError::TimedOut | SocketError::Unreachable),
+        "got {err:?}"
+    );
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn connect_timeout_connects_when_there_is_time() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let stream = common::block_on(TcpStream::connect_timeout(
+        &ThreadPool,
+        listener.local_addr(),
+        Duration::from_secs(5),
+    ))
+    .expect("connect");
+    assert_eq!(stream.peer_addr().unwrap(), listener.local_addr());
+}
+
+#[test]
+fn a_timeout_of_duration_max_means_no_deadline() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let stream = common::block_on(TcpStream::connect_timeout(
+        &ThreadPool,
+        listener.local_addr(),
+        Duration::MAX,
+    ))
+    .expect("connect");
+    let (server, _) = common::block_on(listener.accept_timeout(Duration::MAX)).expect("accept");
+
+    server.set_read_timeout(Some(Duration::MAX)).unwrap();
+    stream.set_write_timeout(Some(Duration::MAX)).unwrap();
+    let OpResult(written, _) = common::block_on(stream.write(b"hi".to_vec()));
+    assert_eq!(written.expect("write"), 2);
+    let OpResult(read, buf) = common::block_on(server.read(vec![0u8; 2]));
+    assert_eq!(read.expect("read"), ReadOutcome::Bytes(2));
+    assert_eq!(&buf, b"hi");
+}
+
+#[test]
+fn a_zero_timeout_is_refused() {
+    let _guard = winasio::net::socket_guard();
+    let listener = TcpListener::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let _client = std::net::TcpStream::connect(listener.local_addr()).expect("connect");
+    let (stream, _) = common::block_on(listener.accept()).expect("accept");
+
+    assert!(stream.set_read_timeout(Some(Duration::ZERO)).is_err());
+    assert!(stream.set_write_timeout(Some(Duration::ZERO)).is_err());
+    assert_eq!(stream.read_timeout(), None);
+    assert_eq!(stream.write_timeout(), None);
+}
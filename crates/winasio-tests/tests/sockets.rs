@@ -1510,3 +1510,68 @@ fn wait_until_readable<S: winasio::iocp::Submitter>(proactor: &Proactor, stream:
         );
     }
 }
+
+/// `TransmitFile`: a file range framed by a header and a trailer, followed by
+/// the transport-level disconnect, read back by a plain `std` client.
+#[test]
+fn send_file_frames_a_range_and_disconnects() {
+    let _guard = winasio::net::socket_guard();
+    let path = std::env::temp_dir().join(format!(
+        "winasio-sockets-send-file-{}.bin",
+        std::process::id()
+    ));
+    let contents: Vec<u8> = (0..=255u8).cycle().take(64 * 1024).collect();
+    std::fs::write(&path, &contents).expect("write fixture");
+    let mut open = winasio::fs::OpenOptions::new();
+    open.read(true).sequential_scan(true);
+    // The file is registered with the other backend on purpose: `TransmitFile`
+    // reads it in the kernel, so its registration does not matter.
+    let file = open.open(&ThreadPool, &path).expect("open fixture");
+
+    let proactor = Rc::new(Proactor::new().expect("proactor"));
+    let listener = TcpListener::bind(&proactor, v4_any()).expect("bind");
+    let addr = listener.local_addr();
+    let handle = std::thread::spawn(move || {
+        use std::io::Read;
+        let mut c = client::connect(addr);
+        let mut received = Vec::new();
+        c.read_to_end(&mut received).expect("client reads to end");
+        received
+    });
+    let (stream, _) = drive_proactor(&proactor, listener.accept()).expect("accept");
+
+    let range = 1000..41000;
+    let mut options = winasio::net::SendFileOptions::new();
+    options.disconnect(true);
+    let OpResult(sent, (head, tail)) = drive_proactor(
+        &proactor,
+        stream.send_file_with(
+            &file,
+            range.clone(),
+            Some(b"HEAD".to_vec()),
+            Some(b"TAIL".to_vec()),
+            &options,
+        ),
+    );
+    assert_eq!(sent.expect("send_file"), 4 + 40000 + 4);
+    assert_eq!(head.as_deref(), Some(&b"HEAD"[..]));
+    assert_eq!(tail.as_deref(), Some(&b"TAIL"[..]));
+
+    let received = handle.join().expect("client thread");
+    let mut expected = b"HEAD".to_vec();
+    expected.extend_from_slice(&contents[range.start as usize..range.end as usize]);
+    expected.extend_from_slice(b"TAIL");
+    assert_eq!(received, expected);
+
+    let mut reuse_only = winasio::net::SendFileOptions::new();
+    reuse_only.reuse_socket(true);
+    let OpResult(refused, _) = drive_proactor(
+        &proactor,
+        stream.send_file_with(&file, 0..1, None::<Vec<u8>>, None, &reuse_only),
+    );
+    assert!(refused.is_err(), "TF_REUSE_SOCKET needs TF_DISCONNECT");
+
+    drop(stream);
+    drop(file);
+    let _ = std::fs::remove_file(path);
+}
@@ -0,0 +1,114 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Owned read and write halves of sockets and pipes.
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use winasio::iocp::{OpResult, ThreadPool, ThreadPoolIo};
+use winasio::net::{ReadOutcome, TcpListener, TcpStream};
+
+fn tcp_pair(
+    listener: &TcpListener<ThreadPoolIo>,
+) -> (TcpStream<ThreadPoolIo>, std::net::TcpStream) {
+    // The backlog completes the connect before the accept is posted.
+    let client = std::net::TcpStream::connect(listener.local_addr()).expect("client connects");
+    let (server, _peer) = common::block_on(listener.accept()).expect("accept");
+    (server, client)
+}
+
+fn listener() -> TcpListener<ThreadPoolIo> {
+    TcpListener::bind(
+        &ThreadPool,
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0),
+    )
+    .expect("bind")
+}
+
+#[test]
+fn socket_halves_work_from_different_threads_and_the_writer_half_closes() {
+    let _guard = winasio::net::socket_guard();
+    let listener = listener();
+    let (server, mut client) = tcp_pair(&listener);
+    let (read, write) = server.into_split();
+
+    let reader = std::thread::spawn(move || {
+        let done = common::block_on(read.read_to_end(Vec::new()));
+        done.result.expect("read to the peer's close");
+        done.buffer
+    });
+    let writer = std::thread::spawn(move || {
+        let done = common::block_on(write.write_all(b"from the server".to_vec()));
+        done.result.expect("write");
+        // Dropping the half sends FIN.
+    });
+
+    writer.join().unwrap();
+    let mut received = Vec::new();
+    client
+        .read_to_end(&mut received)
+        .expect("client sees a clean close");
+    assert_eq!(received, b"from the server");
+
+    // The read half still receives after the write half has gone.
+    client.write_all(b"from the client").unwrap();
+    client.shutdown(std::net::Shutdown::Write).unwrap();
+    assert_eq!(reader.join().unwrap(), b"from the client");
+}
+
+#[test]
+fn reunited_halves_are_the_original_stream_without_a_shutdown() {
+    let _guard = winasio::net::socket_guard();
+    let listener = listener();
+    let (first, mut client) = tcp_pair(&listener);
+    let (second, _other_client) = tcp_pair(&listener);
+    let (read, write) = first.into_split();
+    let (other_read, other_write) = second.into_split();
+
+    let mismatched = read.reunite(other_write).unwrap_err();
+    let (read, other_write) = (mismatched.0, mismatched.1);
+    drop((other_read, other_write));
+
+    let server = write.reunite(read).expect("halves of one stream reunite");
+    let OpResult(written, _) = common::block_on(server.write(b"still open".to_vec()));
+    assert_eq!(written.unwrap(), 10);
+    let mut received = [0u8; 10];
+    client.read_exact(&mut received).unwrap();
+    assert_eq!(&received, b"still open");
+
+    client.write_all(b"!").unwrap();
+    let OpResult(outcome, buffer) = common::block_on(server.read(Vec::with_capacity(8)));
+    assert!(matches!(outcome.unwrap(), ReadOutcome::Bytes(1)));
+    assert_eq!(buffer, b"!");
+}
+
+#[test]
+fn a_pipe_stays_open_until_both_halves_are_dropped() {
+    let (first, second) = winasio::pipe::pair(&ThreadPool).expect("pair");
+    let (read, write) = first.into_split();
+
+    let done = common::block_on(write.write_all(b"ping".to_vec()));
+    done.result.unwrap();
+    let done = common::block_on(second.read_exact(Vec::with_capacity(4)));
+    done.result.unwrap();
+    assert_eq!(done.buffer, b"ping");
+
+    // No half-close: the peer can still write to the read half.
+    drop(write);
+    let done = common::block_on(second.write_all(b"pong".to_vec()));
+    done.result.unwrap();
+    let done = common::block_on(read.read_exact(Vec::with_capacity(4)));
+    done.result.unwrap();
+    assert_eq!(done.buffer, b"pong");
+
+    drop(read);
+    let done = common::block_on(second.read_to_end(Vec::new()));
+    done.result.expect("the peer sees the pipe close");
+    assert!(done.buffer.is_empty());
+}
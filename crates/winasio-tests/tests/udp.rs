@@ -0,0 +1,251 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! UDP sockets on both completion backends.
+
+mod common;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::rc::Rc;
+
+use windows::Win32::Networking::WinSock::WSAEMSGSIZE;
+
+use winasio::iocp::{OpResult, Proactor, ThreadPool};
+use winasio::net::{RecvMeta, UdpSocket};
+
+use common::drive_proactor;
+
+#[test]
+fn datagrams_round_trip_with_their_sender_on_thread_pool() {
+    let _guard = winasio::net::socket_guard();
+    let a = UdpSocket::bind(&ThreadPool, common::v4_local()).expect("bind a");
+    let b = UdpSocket::bind(&ThreadPool, common::v4_local()).expect("bind b");
+    let a_addr = a.local_addr().unwrap();
+    let b_addr = b.local_addr().unwrap();
+    assert_ne!(a_addr.port(), 0);
+
+    let OpResult(sent, _) = common::block_on(a.send_to(b"ping".to_vec(), b_addr));
+    assert_eq!(sent.expect("send_to"), 4);
+
+    let OpResult(received, buf) = common::block_on(b.recv_from(Vec::with_capacity(16)));
+    assert_eq!(received.expect("recv_from"), (4, a_addr));
+    assert_eq!(buf, b"ping");
+}
+
+#[test]
+fn datagrams_round_trip_over_ipv6_on_own_port() {
+    let _guard = winasio::net::socket_guard();
+    let proactor = Rc::new(Proactor::new().expect("proactor"));
+    let local = SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 0);
+    let a = UdpSocket::bind(&proactor, local).expect("bind a");
+    let b = UdpSocket::bind(&proactor, local).expect("bind b");
+    let a_addr = a.local_addr().unwrap();
+    let b_addr = b.local_addr().unwrap();
+
+    // Start the receive first, so it is the completion path that delivers it.
+    let receive = b.recv_from(Vec::with_capacity(16));
+    let OpResult(sent, _) = drive_proactor(&proactor, a.send_to(b"pong".to_vec(), b_addr));
+    assert_eq!(sent.expect("send_to"), 4);
+
+    let OpResult(received, buf) = drive_proactor(&proactor, receive);
+    assert_eq!(received.expect("recv_from"), (4, a_addr));
+    assert_eq!(buf, b"pong");
+}
+
+#[test]
+fn connected_sockets_send_and_recv_without_addresses() {
+    let _guard = winasio::net::socket_guard();
+    let a = UdpSocket::bind(&ThreadPool, common::v4_local()).expect("bind a");
+    let b = UdpSocket::bind(&ThreadPool, common::v4_local()).expect("bind b");
+    let a_addr = a.local_addr().unwrap();
+    let b_addr = b.local_addr().unwrap();
+
+    let OpResult(sent, _) = common::block_on(a.send(b"x".to_vec()));
+    assert!(
+        sent.is_err(),
+        "an unconnected socket has no peer to send to"
+    );
+    assert!(a.peer_addr().is_err());
+
+    a.connect(b_addr).expect("connect a");
+    b.connect(a_addr).expect("connect b");
+    assert_eq!(a.peer_addr().unwrap(), b_addr);
+
+    let OpResult(sent, _) = common::block_on(a.send(b"hello".to_vec()));
+    assert_eq!(sent.expect("send"), 5);
+    let OpResult(received, buf) = common::block_on(b.recv(Vec::with_capacity(16)));
+    assert_eq!(received.expect("recv"), 5);
+    assert_eq!(buf, b"hello");
+}
+
+#[test]
+fn a_zero_length_datagram_is_received_as_zero_bytes() {
+    let _guard = winasio::net::socket_guard();
+    let a = UdpSocket::bind(&ThreadPool, common::v4_local()).expect("bind a");
+    let b = UdpSocket::bind(&ThreadPool, common::v4_local()).expect("bind b");
+    let b_addr = b.local_addr().unwrap();
+
+    let OpResult(sent, _) = common::block_on(a.send_to(Vec::new(), b_addr));
+    assert_eq!(sent.expect("send_to"), 0);
+    let OpResult(sent, _) = common::block_on(a.send_to(b"after".to_vec(), b_addr));
+    sent.expect("send_to");
+
+    let OpResult(received, buf) = common::block_on(b.recv_from(Vec::with_capacity(16)));
+    assert_eq!(received.expect("recv_from").0, 0);
+    assert!(buf.is_empty());
+
+    // The socket carries on: an empty datagram is not an end of anything.
+    let OpResult(received, buf) = common::block_on(b.recv_from(Vec::with_capacity(16)));
+    assert_eq!(received.expect("recv_from").0, 5);
+    assert_eq!(buf, b"after");
+}
+
+#[test]
+fn a_datagram_larger_than_the_buffer_is_truncated_with_wsaemsgsize() {
+    let _guard = winasio::net::socket_guard();
+    let a = UdpSocket::bind(&ThreadPool, common::v4_local()).expect("bind a");
+    let b = UdpSocket::bind(&ThreadPool, common::v4_local()).expect("bind b");
+    let b_addr = b.local_addr().unwrap();
+
+    for payload in [&b"too long"[..], b"next"] {
+        let OpResult(sent, _) = common::block_on(a.send_to(payload.to_vec(), b_addr));
+        sent.expect("send_to");
+    }
+
+    let OpResult(received, buf) = common::block_on(b.recv_from(Vec::with_capacity(3)));
+    assert_eq!(
+        received.unwrap_err().code(),
+        windows::core::HRESULT::from_win32(WSAEMSGSIZE.0 as u32)
+    );
+    assert_eq!(buf, b"too", "the part that fitted is handed back");
+
+    // The rest of the truncated datagram is gone, not queued.
+    let OpResult(received, buf) = common::block_on(b.recv_from(Vec::with_capacity(16)));
+    assert_eq!(received.expect("recv_from").0, 4);
+    assert_eq!(buf, b"next");
+}
+
+fn exchange_msg(a: &UdpSocket<winasio::iocp::ThreadPoolIo>, to: SocketAddr) -> RecvMeta {
+    let OpResult(sent, _) = common::block_on(a.send_to(b"query".to_vec(), to));
+    sent.expect("send_to");
+    let OpResult(received, buf) = common::block_on(a.recv_msg(Vec::with_capacity(16)));
+    let meta = received.expect("recv_msg");
+    assert_eq!(buf, b"query");
+    meta
+}
+
+#[test]
+fn recv_msg_reports_nothing_extra_until_asked() {
+    let _guard = winasio::net::socket_guard();
+    let a = UdpSocket::bind(&ThreadPool, common::v4_local()).expect("bind");
+    let own = a.local_addr().unwrap();
+
+    let meta = exchange_msg(&a, own);
+    assert_eq!(meta.len(), 5);
+    assert_eq!(meta.source(), own);
+    assert_eq!(meta.destination(), None);
+    assert_eq!(meta.interface_index(), None);
+    assert_eq!(meta.hop_limit(), None);
+}
+
+#[test]
+fn recv_msg_reports_the_destination_of_a_wildcard_socket() {
+    let _guard = winasio::net::socket_guard();
+    // Bound to the wildcard, so only packet information says which local
+    // address the datagram was sent to.
+    let a = UdpSocket::bind(
+        &ThreadPool,
+        SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+    )
+    .expect("bind");
+    a.set_recv_packet_info(true).expect("IP_PKTINFO");
+    a.set_recv_hop_limit(true).expect("IP_RECVTTL");
+    let port = a.local_addr().unwrap().port();
+
+    let meta = exchange_msg(&a, SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port));
+    assert_eq!(meta.destination(), Some(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+    assert!(meta.interface_index().is_some());
+    assert!(meta.hop_limit().is_some_and(|ttl| ttl > 0));
+}
+
+#[test]
+fn recv_msg_reports_ipv6_packet_info() {
+    let _guard = winasio::net::socket_guard();
+    let a = UdpSocket::bind(
+        &ThreadPool,
+        SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+    )
+    .expect("bind");
+    a.set_recv_packet_info(true).expect("IPV6_PKTINFO");
+    a.set_recv_hop_limit(true).expect("IPV6_HOPLIMIT");
+    let port = a.local_addr().unwrap().port();
+
+    let meta = exchange_msg(&a, SocketAddr::new(Ipv6Addr::LOCALHOST.into(), port));
+    assert_eq!(meta.destination(), Some(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+    assert!(meta.interface_index().is_some());
+    assert!(meta.hop_limit().is_some());
+}
+
+#[test]
+fn multicast_is_delivered_to_a_member_with_the_group_as_destination() {
+    let _guard = winasio::net::socket_guard();
+    let group = Ipv4Addr::new(239, 255, 77, 41);
+    let member = UdpSocket::bind(
+        &ThreadPool,
+        SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+    )
+    .expect("bind");
+    member
+        .join_multicast_v4(group, Ipv4Addr::LOCALHOST)
+        .expect("join");
+    member.set_recv_packet_info(true).expect("IP_PKTINFO");
+    let port = member.local_addr().unwrap().port();
+
+    let sender = UdpSocket::bind(&ThreadPool, common::v4_local()).expect("bind sender");
+    sender
+        .set_multicast_interface_v4(Ipv4Addr::LOCALHOST)
+        .expect("IP_MULTICAST_IF");
+    sender
+        .set_multicast_loop_v4(true)
+        .expect("IP_MULTICAST_LOOP");
+    let OpResult(sent, _) =
+        common::block_on(sender.send_to(b"announce".to_vec(), SocketAddr::new(group.into(), port)));
+    sent.expect("send_to");
+
+    let OpResult(received, buf) = common::block_on(member.recv_msg(Vec::with_capacity(16)));
+    let meta = received.expect("recv_msg");
+    assert_eq!(buf, b"announce");
+    assert_eq!(meta.destination(), Some(IpAddr::V4(group)));
+
+    member
+        .leave_multicast_v4(group, Ipv4Addr::LOCALHOST)
+        .expect("leave");
+    // Leaving twice is refused: the membership is gone.
+    assert!(member
+        .leave_multicast_v4(group, Ipv4Addr::LOCALHOST)
+        .is_err());
+}
+
+#[test]
+fn broadcast_can_be_switched_and_family_options_stay_on_their_family() {
+    let _guard = winasio::net::socket_guard();
+    let v4 = UdpSocket::bind(&ThreadPool, common::v4_local()).expect("bind v4");
+    v4.set_broadcast(true).expect("SO_BROADCAST on");
+    v4.set_broadcast(false).expect("SO_BROADCAST off");
+    v4.set_multicast_ttl_v4(4).expect("IP_MULTICAST_TTL");
+    v4.set_recv_ecn(true).expect("IP_RECVECN");
+
+    let v6 = UdpSocket::bind(&ThreadPool, SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 0))
+        .expect("bind v6");
+    v6.set_multicast_hops_v6(4).expect("IPV6_MULTICAST_HOPS");
+    v6.set_multicast_loop_v6(false)
+        .expect("IPV6_MULTICAST_LOOP");
+    assert!(
+        v6.join_multicast_v4(Ipv4Addr::new(239, 255, 77, 42), Ipv4Addr::UNSPECIFIED)
+            .is_err(),
+        "an IPv4 membership has no meaning on a v6-only socket"
+    );
+}
@@ -0,0 +1,322 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Length-prefixed frames over a byte stream.
+//!
+//! [`Framed`] wraps a [`NamedPipe`], [`TcpStream`] or [`UnixStream`] and turns
+//! its byte stream into a sequence of frames, each preceded by its length in
+//! the configured [`LengthHeader`]. Both ends must agree on the header.
+//!
+//! # Ending
+//!
+//! [`Framed::recv_frame`] tells a stream that ended cleanly from one that was
+//! cut short:
+//!
+//! * The peer closing between frames — whatever the whole-payload helpers
+//!   would classify as [`TransferError::ClosedPeer`] — is the normal end and
+//!   resolves to `Ok(None)`.
+//! * The same close inside a header or payload is truncation and resolves to
+//!   [`FrameError::Transfer`] with [`TransferError::UnexpectedEof`]: the
+//!   frame's own length said more was coming.
+//!
+//! # Limits
+//!
+//! A header announcing more than [`FrameOptions::max_frame_len`] is rejected
+//! before any of the payload is buffered, so a hostile peer cannot make the
+//! receiver allocate an arbitrary amount.
+//!
+//! The header encoding in [`header`] performs no I/O and can be tested on any
+//! host.
+
+pub mod header;
+
+use std::future::Future;
+
+use crate::fs::ReadOutcome;
+use crate::io::{classify_platform_error, TailBuf, TransferError, TransferResult, WholePayloadIo};
+use crate::iocp::{OpResult, Submitter};
+use crate::net::{TcpStream, UnixStream};
+use crate::pipe::NamedPipe;
+
+pub use header::{Endian, HeaderError, LengthHeader};
+
+/// Frame limit when none is configured.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 8 << 20;
+
+/// Smallest read issued while filling the receive buffer.
+const FRAME_CHUNK: usize = 4096;
+
+mod sealed {
+    use std::future::Future;
+
+    use crate::fs::ReadOutcome;
+    use crate::io::TransferResult;
+    use crate::iocp::OpResult;
+
+    pub trait Sealed {
+        fn read_append(
+            &self,
+            buffer: Vec<u8>,
+            limit: usize,
+        ) -> impl Future<Output = OpResult<ReadOutcome, Vec<u8>>>;
+
+        fn write_all(&self, buffer: Vec<u8>) -> impl Future<Output = TransferResult<Vec<u8>>>;
+    }
+}
+
+/// A stream [`Framed`] can run over.
+///
+/// Implemented by [`NamedPipe`], [`TcpStream`] and [`UnixStream`]; it cannot
+/// be implemented outside this crate.
+pub trait FrameIo: sealed::Sealed {}
+
+/// Read into `buffer`'s spare capacity after its contents, at most `limit`
+/// bytes.
+async fn read_append<T: WholePayloadIo>(
+    io: &T,
+    mut buffer: Vec<u8>,
+    limit: usize,
+) -> OpResult<ReadOutcome, Vec<u8>> {
+    buffer.reserve(limit);
+    let len = buffer.len();
+    let OpResult(result, tail) = io.read_once(0, TailBuf::new(buffer, len, limit)).await;
+    OpResult(result, tail.into_inner())
+}
+
+impl<S: Submitter> sealed::Sealed for NamedPipe<S> {
+    fn read_append(
+        &self,
+        buffer: Vec<u8>,
+        limit: usize,
+    ) -> impl Future<Output = OpResult<ReadOutcome, Vec<u8>>> {
+        read_append(self, buffer, limit)
+    }
+
+    fn write_all(&self, buffer: Vec<u8>) -> impl Future<Output = TransferResult<Vec<u8>>> {
+        crate::io::write_all(self, 0, buffer)
+    }
+}
+
+impl<S: Submitter> FrameIo for NamedPipe<S> {}
+
+impl<S: Submitter> sealed::Sealed for TcpStream<S> {
+    fn read_append(
+        &self,
+        buffer: Vec<u8>,
+        limit: usize,
+    ) -> impl Future<Output = OpResult<ReadOutcome, Vec<u8>>> {
+        read_append(self, buffer, limit)
+    }
+
+    fn write_all(&self, buffer: Vec<u8>) -> impl Future<Output = TransferResult<Vec<u8>>> {
+        crate::io::write_all(self, 0, buffer)
+    }
+}
+
+impl<S: Submitter> FrameIo for TcpStream<S> {}
+
+impl<S: Submitter> sealed::Sealed for UnixStream<S> {
+    fn read_append(
+        &self,
+        buffer: Vec<u8>,
+        limit: usize,
+    ) -> impl Future<Output = OpResult<ReadOutcome, Vec<u8>>> {
+        read_append(self, buffer, limit)
+    }
+
+    fn write_all(&self, buffer: Vec<u8>) -> impl Future<Output = TransferResult<Vec<u8>>> {
+        crate::io::write_all(self, 0, buffer)
+    }
+}
+
+impl<S: Submitter> FrameIo for UnixStream<S> {}
+
+/// Why a frame could not be sent or received.
+#[derive(Debug)]
+pub enum FrameError {
+    /// The frame is longer than the configured limit or than the header can
+    /// describe. On receive, the stream is left inside that frame.
+    TooLarge {
+        /// The frame's length.
+        len: u64,
+    },
+    /// The peer sent bytes that are not a valid header.
+    InvalidHeader,
+    /// The stream failed, or ended in the middle of a frame.
+    Transfer(TransferError),
+}
+
+impl From<HeaderError> for FrameError {
+    fn from(value: HeaderError) -> Self {
+        match value {
+            HeaderError::TooLarge { len } => FrameError::TooLarge { len },
+            HeaderError::Malformed => FrameError::InvalidHeader,
+        }
+    }
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::TooLarge { len } => write!(f, "frame of {len} bytes is too large"),
+            FrameError::InvalidHeader => write!(f, "invalid frame header"),
+            FrameError::Transfer(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+/// Header and limit for a [`Framed`] stream.
+#[derive(Debug, Clone)]
+pub struct FrameOptions {
+    header: LengthHeader,
+    max_frame_len: usize,
+}
+
+impl Default for FrameOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameOptions {
+    /// Big-endian `u32` headers and a limit of [`DEFAULT_MAX_FRAME_LEN`].
+    pub fn new() -> Self {
+        FrameOptions {
+            header: LengthHeader::U32(Endian::Big),
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+        }
+    }
+
+    /// Select the length header.
+    pub fn header(&mut self, header: LengthHeader) -> &mut Self {
+        self.header = header;
+        self
+    }
+
+    /// Largest frame accepted in either direction.
+    ///
+    /// The header's own range applies as well: `u16` headers never carry
+    /// more than 65535 bytes whatever this is set to.
+    pub fn max_frame_len(&mut self, len: usize) -> &mut Self {
+        self.max_frame_len = len;
+        self
+    }
+
+    /// Frame `io` with these options.
+    pub fn framed<T: FrameIo>(&self, io: T) -> Framed<T> {
+        Framed {
+            io,
+            options: self.clone(),
+            buffer: Vec::new(),
+        }
+    }
+
+    fn limit(&self) -> u64 {
+        (self.max_frame_len as u64).min(self.header.max_frame_len())
+    }
+}
+
+/// A stream carrying length-prefixed frames.
+pub struct Framed<T> {
+    io: T,
+    options: FrameOptions,
+    /// Bytes received but not yet returned as a frame.
+    buffer: Vec<u8>,
+}
+
+impl<T: FrameIo> Framed<T> {
+    /// Frame `io` with [`FrameOptions::new`].
+    pub fn new(io: T) -> Self {
+        FrameOptions::new().framed(io)
+    }
+
+    /// Send `payload` as one frame.
+    ///
+    /// If the returned future is dropped before resolving, part of the frame
+    /// may already have been sent and the stream can no longer be framed.
+    pub async fn send_frame(&mut self, payload: Vec<u8>) -> Result<(), FrameError> {
+        let len = payload.len() as u64;
+        if len > self.options.limit() {
+            return Err(FrameError::TooLarge { len });
+        }
+        let mut frame = Vec::with_capacity(header::MAX_HEADER_LEN + payload.len());
+        self.options.header.encode(len, &mut frame)?;
+        frame.extend_from_slice(&payload);
+        let (result, _, _) = self.io.write_all(frame).await.into_parts();
+        result.map_err(FrameError::Transfer)
+    }
+
+    /// Receive the next frame, or `None` once the peer has closed the stream
+    /// between frames.
+    ///
+    /// See the [module documentation](crate::framing) for how an end inside a frame is
+    /// reported. If the returned future is dropped before resolving, bytes
+    /// already received are lost and the stream can no longer be framed.
+    pub async fn recv_frame(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        loop {
+            let mut wanted = FRAME_CHUNK;
+            if let Some((len, header_len)) = self.options.header.decode(&self.buffer)? {
+                if len > self.options.limit() {
+                    return Err(FrameError::TooLarge { len });
+                }
+                let end = header_len.saturating_add(len as usize);
+                if self.buffer.len() >= end {
+                    let frame = self.buffer[header_len..end].to_vec();
+                    self.buffer.drain(..end);
+                    return Ok(Some(frame));
+                }
+                wanted = wanted.max(end - self.buffer.len());
+            }
+
+            let buffer = std::mem::take(&mut self.buffer);
+            let OpResult(result, buffer) = self.io.read_append(buffer, wanted).await;
+            self.buffer = buffer;
+            match result {
+                // An empty message on a message-type pipe carries nothing.
+                Ok(ReadOutcome::Bytes(_) | ReadOutcome::MoreData(_)) => continue,
+                Ok(ReadOutcome::Eof | ReadOutcome::ClosedPeer) => {}
+                Err(e) => match classify_platform_error(e) {
+                    TransferError::ClosedPeer => {}
+                    other => return Err(FrameError::Transfer(other)),
+                },
+            }
+            return if self.buffer.is_empty() {
+                Ok(None)
+            } else {
+                Err(FrameError::Transfer(TransferError::UnexpectedEof))
+            };
+        }
+    }
+}
+
+impl<T> Framed<T> {
+    /// The wrapped stream.
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /// The options this stream was framed with.
+    pub fn options(&self) -> &FrameOptions {
+        &self.options
+    }
+
+    /// Unwrap the stream, with any bytes received past the last frame
+    /// returned.
+    pub fn into_parts(self) -> (T, Vec<u8>) {
+        (self.io, self.buffer)
+    }
+}
+
+impl<T> std::fmt::Debug for Framed<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Framed")
+            .field("options", &self.options)
+            .field("buffered", &self.buffer.len())
+            .finish_non_exhaustive()
+    }
+}
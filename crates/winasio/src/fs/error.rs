@@ -7,7 +7,10 @@
 //! Setup error classification for file and pipe builders.
 //!
 //! The categories are intentionally small and matchable; callers should not
-//! need to decode HRESULT values for the expected setup failures.
+//! need to decode HRESULT values for the expected setup failures. The enum is
+//! `#[non_exhaustive]` so that a new category can be split out of `Win32`
+//! without breaking callers; match the variants you handle and keep a
+//! wildcard arm for the rest.
 
 use windows::core::{Error, HRESULT};
 use windows::Win32::Foundation::{
@@ -19,6 +22,7 @@ use crate::iocp::RegistrationError;
 
 /// A setup failure reported by an open, create, or connect operation.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum SetupError {
     /// The named object was not found.
     NotFound,
@@ -30,6 +34,12 @@ pub enum SetupError {
     InvalidName,
     /// The handle is already registered with a completion mechanism.
     AlreadyRegistered,
+    /// The builder's settings contradict each other; nothing was opened.
+    InvalidOptions(&'static str),
+    /// The SDDL security descriptor was rejected by Windows.
+    InvalidSecurityDescriptor(Error),
+    /// A waiting operation gave up when its deadline passed.
+    TimedOut,
     /// Any other Win32 failure.
     Win32(Error),
 }
@@ -76,6 +86,11 @@ impl std::fmt::Display for SetupError {
                     "handle is already registered with a completion mechanism"
                 )
             }
+            SetupError::InvalidOptions(reason) => write!(f, "invalid options: {reason}"),
+            SetupError::InvalidSecurityDescriptor(e) => {
+                write!(f, "invalid security descriptor: {e}")
+            }
+            SetupError::TimedOut => write!(f, "timed out"),
             SetupError::Win32(e) => write!(f, "{e}"),
         }
     }
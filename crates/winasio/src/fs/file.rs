@@ -24,16 +24,33 @@
 //! ```
 
 use std::future::Future;
+use std::ops::Range;
+use std::path::Path;
 
-use windows::Win32::Foundation::HANDLE;
+use windows::core::{Error, Result};
+use windows::Win32::Foundation::{ERROR_INVALID_PARAMETER, ERROR_MORE_DATA, HANDLE};
+use windows::Win32::Storage::FileSystem::{
+    FileAllocationInfo, FlushFileBuffers, GetFileSizeEx, SetFileInformationByHandle,
+    FILE_ALLOCATION_INFO,
+};
+use windows::Win32::System::Ioctl::{
+    FSCTL_QUERY_ALLOCATED_RANGES, FSCTL_SET_SPARSE, FSCTL_SET_ZERO_DATA,
+};
 use windows::Win32::System::IO::CancelIoEx;
 
 use crate::iocp::{
-    Handle, IntoInner, IoBuf, IoBufMut, OpResult, ReadHandleAt, Submitter, WriteHandleAt,
+    DeviceControl, Handle, IntoInner, IoBuf, IoBufMut, OpResult, ReadHandleAt, Submit, Submitter,
+    WriteHandleAt,
 };
 
+use super::many::ReadMany;
+use super::persist::rename_replacing;
+use super::sparse::{allocated_query_input, decode_allocated, zero_data_input, RANGE_RECORD};
 use super::{ReadOutcome, SetupError};
 
+/// Ranges requested per `FSCTL_QUERY_ALLOCATED_RANGES` round trip.
+const RANGE_BATCH: usize = 64;
+
 struct Inner<S> {
     handle: Handle,
     submitter: S,
@@ -95,6 +112,11 @@ impl<S: Submitter> File<S> {
         self.open().handle.raw()
     }
 
+    /// A shared reference to the handle, for operations that outlive a borrow.
+    pub(crate) fn shared_handle(&self) -> Handle {
+        self.open().handle.clone()
+    }
+
     /// Start a positional read.
     ///
     /// If the returned future is dropped before resolving, cancellation is
@@ -107,10 +129,7 @@ impl<S: Submitter> File<S> {
     where
         B: IoBufMut + Send,
     {
-        let open = self.open();
-        let submitted =
-            open.submitter
-                .submit(ReadHandleAt::new(open.handle.clone(), offset, buffer));
+        let submitted = self.submit_read_at(offset, buffer);
         async move {
             let OpResult(result, op) = submitted.await;
             let (result, buffer) = op.finish(result);
@@ -118,6 +137,45 @@ impl<S: Submitter> File<S> {
         }
     }
 
+    /// Read several ranges concurrently.
+    ///
+    /// `ranges` and `buffers` are paired in order. Each read fills at most the
+    /// range's length from the start of its buffer, so give every buffer at
+    /// least that much capacity. Nothing is submitted until the returned
+    /// [`ReadMany`] is first polled, and at most [`ReadMany::max_in_flight`]
+    /// reads are outstanding at once.
+    ///
+    /// Fails with `ERROR_INVALID_PARAMETER`, before anything is read, if
+    /// `ranges` and `buffers` have different lengths. Pairing them up to the
+    /// shorter one would drop reads or buffers without a word.
+    pub fn read_many<B>(
+        &self,
+        ranges: impl IntoIterator<Item = Range<u64>>,
+        buffers: impl IntoIterator<Item = B>,
+    ) -> Result<ReadMany<'_, S, B>>
+    where
+        B: IoBufMut + Send,
+    {
+        let ranges: Vec<_> = ranges.into_iter().collect();
+        let buffers: Vec<_> = buffers.into_iter().collect();
+        if ranges.len() != buffers.len() {
+            return Err(Error::from_hresult(ERROR_INVALID_PARAMETER.to_hresult()));
+        }
+        Ok(ReadMany::new(
+            self,
+            ranges.into_iter().zip(buffers).collect(),
+        ))
+    }
+
+    pub(super) fn submit_read_at<B>(&self, offset: u64, buffer: B) -> Submit<ReadHandleAt<B>>
+    where
+        B: IoBufMut + Send,
+    {
+        let open = self.open();
+        open.submitter
+            .submit(ReadHandleAt::new(open.handle.clone(), offset, buffer))
+    }
+
     /// Start a positional write.
     ///
     /// If the returned future is dropped before resolving, cancellation is
@@ -191,6 +249,156 @@ impl<S: Submitter> File<S> {
         crate::io::read_to_end(self, offset, buffer)
     }
 
+    /// Mark the file sparse, or clear the mark.
+    ///
+    /// Requires write access. Once sparse, ranges cleared with
+    /// [`File::zero_range`] release their storage instead of being written as
+    /// zeros. Clearing the mark fails while the file still has holes.
+    pub fn set_sparse(&self, sparse: bool) -> impl Future<Output = Result<()>> {
+        // `FILE_SET_SPARSE_BUFFER` is a single `BOOLEAN`.
+        let submitted = self.control(FSCTL_SET_SPARSE, vec![u8::from(sparse)], Vec::new());
+        async move { submitted.await.0.map(|_| ()) }
+    }
+
+    /// Zero `len` bytes starting at `offset`.
+    ///
+    /// On a sparse file this deallocates whole allocation units inside the
+    /// range and zero-fills the partial units at its edges; on other files it
+    /// writes zeros. Reads of the range return zeros either way. The file size
+    /// is not changed.
+    pub fn zero_range(&self, offset: u64, len: u64) -> impl Future<Output = Result<()>> {
+        let submitted = zero_data_input(offset, len)
+            .map(|input| self.control(FSCTL_SET_ZERO_DATA, input, Vec::new()));
+        async move { submitted?.await.0.map(|_| ()) }
+    }
+
+    /// List the ranges of the file that are backed by storage.
+    ///
+    /// Everything outside the returned ranges reads as zeros. A file that is
+    /// not sparse reports a single range covering its whole size. Ranges come
+    /// back in ascending order and are gathered in batches, so a file with
+    /// many extents takes several round trips.
+    pub fn allocated_ranges(&self) -> impl Future<Output = Result<Vec<Range<u64>>>> + '_ {
+        async move {
+            let mut size = 0i64;
+            // SAFETY: the handle is owned by this file and `size` is a valid
+            // out pointer for the duration of the call.
+            unsafe { GetFileSizeEx(self.handle(), &mut size) }?;
+            let end = size as u64;
+
+            let mut ranges = Vec::new();
+            let mut start = 0;
+            let mut output = Vec::with_capacity(RANGE_BATCH * RANGE_RECORD);
+            while start < end {
+                output.clear();
+                let input = allocated_query_input(start, end)?;
+                let (result, returned) = self
+                    .control(FSCTL_QUERY_ALLOCATED_RANGES, input, output)
+                    .await;
+                output = returned;
+                let before = ranges.len();
+                decode_allocated(&output, &mut ranges);
+                match result? {
+                    ReadOutcome::MoreData(_) => match ranges.last() {
+                        // Resume after the last extent this batch reported.
+                        Some(last) if ranges.len() > before => start = last.end,
+                        _ => return Err(Error::from_hresult(ERROR_MORE_DATA.to_hresult())),
+                    },
+                    _ => break,
+                }
+            }
+            Ok(ranges)
+        }
+    }
+
+    /// Reserve storage for `len` bytes without changing the file size.
+    ///
+    /// Later writes within the reservation do not fail for lack of space. This
+    /// one is synchronous: Windows offers allocation only through
+    /// `SetFileInformationByHandle`, which has no overlapped form, and it
+    /// returns as soon as the file system has updated its allocation.
+    ///
+    /// A `len` below the current size reserves nothing further. It is raised
+    /// to the size rather than passed on, because an allocation smaller than
+    /// the file truncates it.
+    pub fn preallocate(&self, len: u64) -> Result<()> {
+        let len = i64::try_from(len)
+            .map_err(|_| Error::from_hresult(ERROR_INVALID_PARAMETER.to_hresult()))?;
+        let mut size = 0i64;
+        // SAFETY: the handle is owned by this file and `size` is a valid out
+        // pointer for the duration of the call.
+        unsafe { GetFileSizeEx(self.handle(), &mut size) }?;
+        let info = FILE_ALLOCATION_INFO {
+            AllocationSize: len.max(size),
+        };
+        // SAFETY: `info` is a valid `FILE_ALLOCATION_INFO` that outlives the
+        // call, and its size is passed alongside it.
+        unsafe {
+            SetFileInformationByHandle(
+                self.handle(),
+                FileAllocationInfo,
+                (&info as *const FILE_ALLOCATION_INFO).cast(),
+                std::mem::size_of::<FILE_ALLOCATION_INFO>() as u32,
+            )
+        }
+    }
+
+    /// Flush the file's data and metadata to the device.
+    ///
+    /// This is a synchronous `FlushFileBuffers`: Windows has no overlapped
+    /// form of it, so the calling thread blocks until the device reports the
+    /// writes durable. Only writes that have completed are covered; await
+    /// every outstanding write first. The handle needs write access.
+    pub fn sync_all(&self) -> Result<()> {
+        // SAFETY: the handle is owned by this file for the duration of the
+        // call.
+        unsafe { FlushFileBuffers(self.handle()) }
+    }
+
+    /// Atomically move this file to `path`, replacing any file already there.
+    ///
+    /// Renames through the open handle with `FileRenameInfoEx` and POSIX
+    /// semantics, so the name is swapped in one step even while other handles
+    /// to the replaced file are open, and readers see either the old contents
+    /// or the new ones. The file must have been opened with write and `DELETE`
+    /// access — see [`OpenOptions::delete_access`](super::OpenOptions::delete_access)
+    /// — and the target must be on the same volume.
+    ///
+    /// The data is flushed with [`File::sync_all`] before the rename.
+    /// Without that, the rename can reach the disk ahead of the data, and a
+    /// crash leaves the target empty or truncated. Every write must have
+    /// completed before persisting, since only completed writes are flushed.
+    ///
+    /// Only the name changes: a file opened delete-on-close is still deleted
+    /// when closed. [`TempFile::persist`](super::TempFile::persist) cancels
+    /// that for the files it creates.
+    pub fn persist_as(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.sync_all()?;
+        // SAFETY: the handle is owned by this file for the duration of the
+        // call; missing `DELETE` access is reported as an error, not UB.
+        unsafe { rename_replacing(self.handle(), path.as_ref()) }
+    }
+
+    fn control<I, O>(
+        &self,
+        code: u32,
+        input: I,
+        output: O,
+    ) -> impl Future<Output = (Result<ReadOutcome>, O)>
+    where
+        I: IoBuf + Send,
+        O: IoBufMut + Send,
+    {
+        let open = self.open();
+        let submitted =
+            open.submitter
+                .submit(DeviceControl::new(open.handle.clone(), code, input, output));
+        async move {
+            let OpResult(result, op) = submitted.await;
+            op.finish(result)
+        }
+    }
+
     fn open(&self) -> &Inner<S> {
         self.inner.as_ref().expect("file state is present")
     }
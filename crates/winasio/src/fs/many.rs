@@ -0,0 +1,273 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Pipelined positional reads.
+//!
+//! [`File::read_many`] keeps several positional reads outstanding on one
+//! handle, so the device sees a queue rather than one request at a time. The
+//! set is driven by polling it; there is no task per read and no runtime
+//! dependency.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::io::TailBuf;
+use crate::iocp::{IoBufMut, OpResult, ReadHandleAt, Submit, Submitter};
+
+use super::{File, ReadOutcome};
+
+const DEFAULT_MAX_IN_FLIGHT: usize = 32;
+
+type InFlight<B> = (Range<u64>, Submit<ReadHandleAt<TailBuf<B>>>);
+
+/// A set of positional reads, yielded as they complete.
+///
+/// Created by [`File::read_many`]. Each item pairs the range that was asked
+/// for with the read's result and its buffer. Reads are yielded in the order
+/// their completions arrive, not the order they were submitted: each
+/// outstanding read has its own waker, and a completion queues that read for
+/// the next poll.
+///
+/// Dropping the set requests cancellation of every outstanding read; as with
+/// any dropped operation, their buffers are not returned.
+pub struct ReadMany<'a, S: Submitter, B: IoBufMut + Send> {
+    file: &'a File<S>,
+    queued: VecDeque<(Range<u64>, B)>,
+    /// Outstanding reads by slot. A finished read leaves its slot `None` and
+    /// its index on `free`, so neither submission nor completion shifts the
+    /// others.
+    slots: Vec<Option<InFlight<B>>>,
+    /// One waker per slot, made once and reused by every read in that slot.
+    wakers: Vec<Waker>,
+    free: Vec<usize>,
+    in_flight: usize,
+    ready: Arc<ReadyQueue>,
+    max_in_flight: usize,
+}
+
+impl<'a, S: Submitter, B: IoBufMut + Send> ReadMany<'a, S, B> {
+    pub(super) fn new(file: &'a File<S>, queued: VecDeque<(Range<u64>, B)>) -> Self {
+        ReadMany {
+            file,
+            queued,
+            slots: Vec::new(),
+            wakers: Vec::new(),
+            free: Vec::new(),
+            in_flight: 0,
+            ready: Arc::default(),
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+        }
+    }
+
+    /// Bound the number of reads outstanding at once. Defaults to 32.
+    ///
+    /// Zero is treated as one. Lowering the bound while reads are outstanding
+    /// takes effect as they complete.
+    pub fn max_in_flight(&mut self, limit: usize) -> &mut Self {
+        self.max_in_flight = limit.max(1);
+        self
+    }
+
+    /// Reads not yet yielded, whether submitted or still queued.
+    pub fn remaining(&self) -> usize {
+        self.queued.len() + self.in_flight
+    }
+
+    /// Wait for the next read to complete.
+    ///
+    /// Resolves to `None` once every read has been yielded.
+    pub async fn next(&mut self) -> Option<(Range<u64>, OpResult<ReadOutcome, B>)> {
+        std::future::poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    /// Poll for the next completed read, submitting queued reads while there
+    /// is room under the in-flight bound.
+    pub fn poll_next(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<(Range<u64>, OpResult<ReadOutcome, B>)>> {
+        self.ready.set_waker(cx.waker());
+        self.fill();
+        // Only reads that were woken, or never polled, are polled again.
+        while let Some(index) = self.ready.pop() {
+            // A read can be woken more than once before it is polled; the
+            // later entries find its slot already empty.
+            let Some((_, submitted)) = self.slots[index].as_mut() else {
+                continue;
+            };
+            let mut slot_cx = Context::from_waker(&self.wakers[index]);
+            if let Poll::Ready(OpResult(result, op)) = Pin::new(submitted).poll(&mut slot_cx) {
+                let (range, _) = self.slots[index].take().expect("polled above");
+                self.free.push(index);
+                self.in_flight -= 1;
+                let (result, window) = op.finish(result);
+                // Keep the queue full before handing control back.
+                self.fill();
+                return Poll::Ready(Some((range, OpResult(result, window.into_inner()))));
+            }
+        }
+        if self.in_flight == 0 {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.in_flight < self.max_in_flight {
+            let Some((range, buffer)) = self.queued.pop_front() else {
+                break;
+            };
+            let len = usize::try_from(range.end.saturating_sub(range.start)).unwrap_or(usize::MAX);
+            let submitted = self
+                .file
+                .submit_read_at(range.start, TailBuf::new(buffer, 0, len));
+            let index = match self.free.pop() {
+                Some(index) => {
+                    self.slots[index] = Some((range, submitted));
+                    index
+                }
+                None => {
+                    let index = self.slots.len();
+                    self.slots.push(Some((range, submitted)));
+                    self.wakers.push(Waker::from(Arc::new(SlotWaker {
+                        index,
+                        ready: self.ready.clone(),
+                    })));
+                    index
+                }
+            };
+            self.in_flight += 1;
+            // Polled once so that it registers its slot's waker. The caller
+            // is already polling, so the task is not woken for it.
+            self.ready.queue(index);
+        }
+    }
+}
+
+/// Slots whose reads are ready to be polled, in the order they were woken.
+#[derive(Default)]
+struct ReadyQueue {
+    state: Mutex<ReadyState>,
+}
+
+#[derive(Default)]
+struct ReadyState {
+    indices: VecDeque<usize>,
+    /// The task polling the set, woken whenever a slot is queued.
+    waker: Option<Waker>,
+}
+
+impl ReadyQueue {
+    fn set_waker(&self, waker: &Waker) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match &state.waker {
+            Some(current) if current.will_wake(waker) => {}
+            _ => state.waker = Some(waker.clone()),
+        }
+    }
+
+    fn queue(&self, index: usize) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.indices.push_back(index);
+    }
+
+    fn push(&self, index: usize) {
+        let waker = {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            state.indices.push_back(index);
+            state.waker.clone()
+        };
+        // Woken outside the lock: a waker that polls inline re-enters
+        // `poll_next`, which takes it again.
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn pop(&self) -> Option<usize> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.indices.pop_front()
+    }
+}
+
+/// The waker handed to one slot's read: it queues the slot, then wakes the
+/// task polling the set.
+struct SlotWaker {
+    index: usize,
+    ready: Arc<ReadyQueue>,
+}
+
+impl Wake for SlotWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.ready.push(self.index);
+    }
+}
+
+impl<S: Submitter, B: IoBufMut + Send> std::fmt::Debug for ReadMany<'_, S, B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadMany")
+            .field("queued", &self.queued.len())
+            .field("in_flight", &self.in_flight)
+            .field("max_in_flight", &self.max_in_flight)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Count(AtomicUsize);
+
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn slots_are_polled_in_the_order_they_were_woken() {
+        let ready = Arc::new(ReadyQueue::default());
+        let task = Arc::new(Count(AtomicUsize::new(0)));
+        ready.set_waker(&Waker::from(task.clone()));
+        let wakers: Vec<Waker> = (0..3)
+            .map(|index| {
+                Waker::from(Arc::new(SlotWaker {
+                    index,
+                    ready: ready.clone(),
+                }))
+            })
+            .collect();
+
+        wakers[2].wake_by_ref();
+        wakers[0].wake_by_ref();
+        wakers[1].wake_by_ref();
+        assert_eq!(task.0.load(Ordering::SeqCst), 3);
+        let order: Vec<_> = std::iter::from_fn(|| ready.pop()).collect();
+        assert_eq!(order, [2, 0, 1]);
+    }
+
+    #[test]
+    fn queueing_a_new_read_does_not_wake_the_task() {
+        let ready = ReadyQueue::default();
+        let task = Arc::new(Count(AtomicUsize::new(0)));
+        ready.set_waker(&Waker::from(task.clone()));
+        ready.queue(7);
+        assert_eq!(task.0.load(Ordering::SeqCst), 0);
+        assert_eq!(ready.pop(), Some(7));
+        assert_eq!(ready.pop(), None);
+    }
+}
@@ -49,17 +49,26 @@
 
 mod error;
 mod file;
+mod many;
 mod options;
 /// Read outcome classification shared by file and pipe reads.
 pub mod outcome;
+mod persist;
+pub(crate) mod security;
+mod sparse;
+mod temp;
 /// Test-only helpers for exercising teardown paths.
 #[cfg(feature = "test-util")]
 pub mod test_util;
 
 pub use error::SetupError;
 pub use file::File;
+pub use many::ReadMany;
 pub use options::OpenOptions;
 pub use outcome::ReadOutcome;
+pub use temp::{PersistError, TempFile};
+
+pub(crate) use temp::unique_name;
 
 /// A [`File`] using the system thread-pool backend.
 pub type ThreadPoolFile = File<crate::iocp::ThreadPoolIo>;
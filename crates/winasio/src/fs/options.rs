@@ -12,17 +12,31 @@ use std::path::Path;
 use windows::core::PCWSTR;
 use windows::Win32::Foundation::GENERIC_WRITE;
 use windows::Win32::Storage::FileSystem::{
-    CreateFileW, CREATE_ALWAYS, CREATE_NEW, FILE_ATTRIBUTE_NORMAL, FILE_CREATION_DISPOSITION,
-    FILE_FLAGS_AND_ATTRIBUTES, FILE_FLAG_OVERLAPPED, FILE_GENERIC_READ, FILE_GENERIC_WRITE,
-    FILE_SHARE_DELETE, FILE_SHARE_MODE, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_ALWAYS,
-    OPEN_EXISTING, TRUNCATE_EXISTING,
+    CreateFileW, CREATE_ALWAYS, CREATE_NEW, DELETE, FILE_APPEND_DATA, FILE_ATTRIBUTE_NORMAL,
+    FILE_ATTRIBUTE_TEMPORARY, FILE_CREATION_DISPOSITION, FILE_FLAGS_AND_ATTRIBUTES,
+    FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_DELETE_ON_CLOSE, FILE_FLAG_OVERLAPPED,
+    FILE_FLAG_RANDOM_ACCESS, FILE_FLAG_SEQUENTIAL_SCAN, FILE_GENERIC_READ, FILE_GENERIC_WRITE,
+    FILE_SHARE_DELETE, FILE_SHARE_MODE, FILE_SHARE_READ, FILE_SHARE_WRITE, FILE_WRITE_DATA,
+    OPEN_ALWAYS, OPEN_EXISTING, TRUNCATE_EXISTING,
 };
 
 use crate::iocp::{Handle, Registrar};
 
 use super::error::SetupError;
+use super::security::SecurityDescriptor;
 use super::File;
 
+/// The `CreateFileW` arguments an [`OpenOptions`] translates to.
+///
+/// Produced by [`OpenOptions::plan`], which performs every check that does
+/// not need the operating system, so the translation is testable on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct OpenPlan {
+    pub(crate) desired_access: u32,
+    pub(crate) creation: FILE_CREATION_DISPOSITION,
+    pub(crate) flags: FILE_FLAGS_AND_ATTRIBUTES,
+}
+
 /// Builder for opening an overlapped file.
 #[derive(Debug, Clone)]
 pub struct OpenOptions {
@@ -31,8 +45,16 @@ pub struct OpenOptions {
     create: bool,
     create_new: bool,
     truncate: bool,
+    append: bool,
+    delete_on_close: bool,
+    delete_access: bool,
+    temporary: bool,
+    sequential_scan: bool,
+    random_access: bool,
+    backup_semantics: bool,
     share_mode: FILE_SHARE_MODE,
     flags_and_attributes: FILE_FLAGS_AND_ATTRIBUTES,
+    security_descriptor: Option<String>,
 }
 
 impl OpenOptions {
@@ -44,8 +66,16 @@ impl OpenOptions {
             create: false,
             create_new: false,
             truncate: false,
+            append: false,
+            delete_on_close: false,
+            delete_access: false,
+            temporary: false,
+            sequential_scan: false,
+            random_access: false,
+            backup_semantics: false,
             share_mode: FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
             flags_and_attributes: FILE_ATTRIBUTE_NORMAL,
+            security_descriptor: None,
         }
     }
 
@@ -79,6 +109,81 @@ impl OpenOptions {
         self
     }
 
+    /// Open for appending.
+    ///
+    /// The handle is granted `FILE_APPEND_DATA` without `FILE_WRITE_DATA`, so
+    /// every write lands at the current end of the file whatever offset is
+    /// passed to [`File::write_at`]. Implies write access for everything other
+    /// than data; combining it with [`OpenOptions::truncate`] is rejected.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    /// Delete the file when the last handle to it is closed.
+    ///
+    /// Adds `FILE_FLAG_DELETE_ON_CLOSE` and the `DELETE` access it needs. Other
+    /// openers must share delete access for their opens to succeed.
+    pub fn delete_on_close(&mut self, delete_on_close: bool) -> &mut Self {
+        self.delete_on_close = delete_on_close;
+        self
+    }
+
+    /// Request `DELETE` access without deleting anything.
+    ///
+    /// [`File::persist_as`] renames through the handle, and a rename needs
+    /// `DELETE`. [`OpenOptions::delete_on_close`] requests it already.
+    pub fn delete_access(&mut self, delete_access: bool) -> &mut Self {
+        self.delete_access = delete_access;
+        self
+    }
+
+    /// Mark a newly created file as temporary.
+    ///
+    /// `FILE_ATTRIBUTE_TEMPORARY` asks the cache manager to avoid writing the
+    /// data back to disk while memory allows. It only applies when this open
+    /// creates the file.
+    pub fn temporary(&mut self, temporary: bool) -> &mut Self {
+        self.temporary = temporary;
+        self
+    }
+
+    /// Hint that the file will be read front to back.
+    ///
+    /// Mutually exclusive with [`OpenOptions::random_access`].
+    pub fn sequential_scan(&mut self, sequential_scan: bool) -> &mut Self {
+        self.sequential_scan = sequential_scan;
+        self
+    }
+
+    /// Hint that the file will be accessed at scattered offsets.
+    ///
+    /// Mutually exclusive with [`OpenOptions::sequential_scan`].
+    pub fn random_access(&mut self, random_access: bool) -> &mut Self {
+        self.random_access = random_access;
+        self
+    }
+
+    /// Open with backup semantics.
+    ///
+    /// Required to open a directory handle, and lets a caller holding the
+    /// backup or restore privilege bypass the file's access checks.
+    pub fn backup_semantics(&mut self, backup_semantics: bool) -> &mut Self {
+        self.backup_semantics = backup_semantics;
+        self
+    }
+
+    /// Apply a security descriptor, written as SDDL, to a newly created file.
+    ///
+    /// The string is converted when [`OpenOptions::open`] runs; a string
+    /// Windows rejects is reported as
+    /// [`SetupError::InvalidSecurityDescriptor`]. Opening an existing file
+    /// ignores the descriptor.
+    pub fn security_descriptor_sddl(&mut self, sddl: impl Into<String>) -> &mut Self {
+        self.security_descriptor = Some(sddl.into());
+        self
+    }
+
     /// Set the Windows share mode.
     pub fn share_mode(&mut self, share_mode: FILE_SHARE_MODE) -> &mut Self {
         self.share_mode = share_mode;
@@ -88,7 +193,8 @@ impl OpenOptions {
     /// Add custom Windows flags and attributes.
     ///
     /// `FILE_FLAG_OVERLAPPED` is always added by [`OpenOptions::open`] even if
-    /// it is not present here.
+    /// it is not present here. The first-class settings above are combined
+    /// with these bits, and the combination is checked as a whole.
     pub fn custom_flags_and_attributes(
         &mut self,
         flags_and_attributes: FILE_FLAGS_AND_ATTRIBUTES,
@@ -103,25 +209,31 @@ impl OpenOptions {
         registrar: &R,
         path: impl AsRef<Path>,
     ) -> Result<File<R::Io>, SetupError> {
+        let plan = self.plan()?;
         let wide = wide_null(path.as_ref())?;
-        let desired_access = self.desired_access();
-        let creation = self.creation_disposition();
-        let flags = self.flags_and_attributes | FILE_FLAG_OVERLAPPED;
+        let descriptor = self
+            .security_descriptor
+            .as_deref()
+            .map(SecurityDescriptor::from_sddl)
+            .transpose()?;
+        let attributes = descriptor.as_ref().map(|d| d.attributes(false));
 
-        // SAFETY: the path is explicitly NUL-terminated, and all other
-        // arguments are values owned by this builder.
+        // SAFETY: the path is explicitly NUL-terminated, the security
+        // attributes and the descriptor they point to outlive the call, and all
+        // other arguments are values owned by this builder.
         let raw = unsafe {
             CreateFileW(
                 PCWSTR(wide.as_ptr()),
-                desired_access,
+                plan.desired_access,
                 self.share_mode,
-                None,
-                creation,
-                flags,
+                attributes.as_ref().map(|a| a as *const _),
+                plan.creation,
+                plan.flags,
                 None,
             )
         }
         .map_err(SetupError::from_windows)?;
+        drop(descriptor);
 
         // SAFETY: `CreateFileW` returned a newly owned handle, and ownership of
         // closing it transfers into `Handle`.
@@ -135,17 +247,69 @@ impl OpenOptions {
         }
     }
 
+    /// Translate the builder into `CreateFileW` arguments.
+    ///
+    /// Contradictory combinations are rejected here, before anything reaches
+    /// the operating system, as [`SetupError::InvalidOptions`].
+    pub(crate) fn plan(&self) -> Result<OpenPlan, SetupError> {
+        if self.append && self.truncate {
+            return Err(SetupError::InvalidOptions(
+                "append and truncate are mutually exclusive",
+            ));
+        }
+        let flags = self.flags();
+        if flags.contains(FILE_FLAG_SEQUENTIAL_SCAN) && flags.contains(FILE_FLAG_RANDOM_ACCESS) {
+            return Err(SetupError::InvalidOptions(
+                "sequential_scan and random_access are mutually exclusive",
+            ));
+        }
+        Ok(OpenPlan {
+            desired_access: self.desired_access(),
+            creation: self.creation_disposition(),
+            flags,
+        })
+    }
+
     fn desired_access(&self) -> u32 {
         let mut access = 0;
         if self.read {
             access |= FILE_GENERIC_READ.0;
         }
-        if self.write {
+        if self.append {
+            // Everything `FILE_GENERIC_WRITE` grants except overwriting data,
+            // which is what confines writes to the end of the file.
+            access |= (FILE_GENERIC_WRITE.0 & !FILE_WRITE_DATA.0) | FILE_APPEND_DATA.0;
+        } else if self.write {
             access |= FILE_GENERIC_WRITE.0 | GENERIC_WRITE.0;
         }
+        if self.delete_on_close || self.delete_access {
+            access |= DELETE.0;
+        }
         access
     }
 
+    fn flags(&self) -> FILE_FLAGS_AND_ATTRIBUTES {
+        let mut flags = self.flags_and_attributes | FILE_FLAG_OVERLAPPED;
+        let typed = [
+            (self.delete_on_close, FILE_FLAG_DELETE_ON_CLOSE),
+            (self.temporary, FILE_ATTRIBUTE_TEMPORARY),
+            (self.sequential_scan, FILE_FLAG_SEQUENTIAL_SCAN),
+            (self.random_access, FILE_FLAG_RANDOM_ACCESS),
+            (self.backup_semantics, FILE_FLAG_BACKUP_SEMANTICS),
+        ];
+        for (enabled, bit) in typed {
+            if enabled {
+                flags = flags | bit;
+            }
+        }
+        // `FILE_ATTRIBUTE_NORMAL` is only valid on its own; any other
+        // attribute supersedes it.
+        if flags.contains(FILE_ATTRIBUTE_TEMPORARY) {
+            flags = FILE_FLAGS_AND_ATTRIBUTES(flags.0 & !FILE_ATTRIBUTE_NORMAL.0);
+        }
+        flags
+    }
+
     fn creation_disposition(&self) -> FILE_CREATION_DISPOSITION {
         if self.create_new {
             CREATE_NEW
@@ -175,3 +339,120 @@ fn wide_null(path: &Path) -> Result<Vec<u16>, SetupError> {
     wide.push(0);
     Ok(wide)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(configure: impl FnOnce(&mut OpenOptions)) -> Result<OpenPlan, SetupError> {
+        let mut options = OpenOptions::new();
+        configure(&mut options);
+        options.plan()
+    }
+
+    #[test]
+    fn overlapped_is_always_present() {
+        let plan = plan(|o| {
+            o.read(true)
+                .custom_flags_and_attributes(FILE_FLAGS_AND_ATTRIBUTES(0));
+        })
+        .unwrap();
+        assert!(plan.flags.contains(FILE_FLAG_OVERLAPPED));
+        assert_eq!(plan.creation, OPEN_EXISTING);
+    }
+
+    #[test]
+    fn append_grants_append_data_but_never_write_data() {
+        for write in [false, true] {
+            let plan = plan(|o| {
+                o.write(write).append(true);
+            })
+            .unwrap();
+            assert_ne!(plan.desired_access & FILE_APPEND_DATA.0, 0);
+            assert_eq!(plan.desired_access & FILE_WRITE_DATA.0, 0);
+            assert_eq!(plan.desired_access & GENERIC_WRITE.0, 0);
+        }
+    }
+
+    #[test]
+    fn append_and_truncate_are_rejected_together() {
+        let err = plan(|o| {
+            o.append(true).truncate(true).create(true);
+        })
+        .unwrap_err();
+        assert!(matches!(err, SetupError::InvalidOptions(_)));
+    }
+
+    #[test]
+    fn access_hints_are_mutually_exclusive_even_through_raw_bits() {
+        let typed = plan(|o| {
+            o.read(true).sequential_scan(true).random_access(true);
+        });
+        assert!(matches!(typed, Err(SetupError::InvalidOptions(_))));
+
+        let mixed = plan(|o| {
+            o.read(true)
+                .custom_flags_and_attributes(FILE_FLAG_RANDOM_ACCESS)
+                .sequential_scan(true);
+        });
+        assert!(matches!(mixed, Err(SetupError::InvalidOptions(_))));
+
+        let single = plan(|o| {
+            o.read(true).sequential_scan(true);
+        })
+        .unwrap();
+        assert!(single.flags.contains(FILE_FLAG_SEQUENTIAL_SCAN));
+        assert!(!single.flags.contains(FILE_FLAG_RANDOM_ACCESS));
+    }
+
+    #[test]
+    fn delete_on_close_requests_delete_access() {
+        let plan = plan(|o| {
+            o.read(true).write(true).create(true).delete_on_close(true);
+        })
+        .unwrap();
+        assert!(plan.flags.contains(FILE_FLAG_DELETE_ON_CLOSE));
+        assert_ne!(plan.desired_access & DELETE.0, 0);
+    }
+
+    #[test]
+    fn delete_access_requests_delete_without_the_flag() {
+        let plan = plan(|o| {
+            o.write(true).delete_access(true);
+        })
+        .unwrap();
+        assert!(!plan.flags.contains(FILE_FLAG_DELETE_ON_CLOSE));
+        assert_ne!(plan.desired_access & DELETE.0, 0);
+
+        let plain = plan(|o| {
+            o.write(true);
+        })
+        .unwrap();
+        assert_eq!(plain.desired_access & DELETE.0, 0);
+    }
+
+    #[test]
+    fn temporary_replaces_the_normal_attribute() {
+        let plan = plan(|o| {
+            o.write(true).create(true).temporary(true);
+        })
+        .unwrap();
+        assert!(plan.flags.contains(FILE_ATTRIBUTE_TEMPORARY));
+        assert_eq!(plan.flags.0 & FILE_ATTRIBUTE_NORMAL.0, 0);
+
+        let plain = plan(|o| {
+            o.write(true).create(true);
+        })
+        .unwrap();
+        assert!(plain.flags.contains(FILE_ATTRIBUTE_NORMAL));
+    }
+
+    #[test]
+    fn backup_semantics_sets_its_flag() {
+        let plan = plan(|o| {
+            o.read(true).backup_semantics(true);
+        })
+        .unwrap();
+        assert!(plan.flags.contains(FILE_FLAG_BACKUP_SEMANTICS));
+    }
+}
@@ -0,0 +1,172 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Atomic rename by handle.
+//!
+//! `FILE_RENAME_INFO` ends in a variable-length name, so it cannot be a Rust
+//! value; it is laid out here in a `u64`-aligned buffer. The two flag
+//! constants are defined locally rather than enabling
+//! `Win32_System_WindowsProgramming` for them.
+
+use std::mem::offset_of;
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+
+use windows::core::{Error, Result};
+use windows::Win32::Foundation::{ERROR_INVALID_PARAMETER, HANDLE};
+use windows::Win32::Storage::FileSystem::{
+    FileDispositionInfoEx, FileRenameInfoEx, SetFileInformationByHandle,
+    FILE_DISPOSITION_FLAG_DELETE, FILE_DISPOSITION_FLAG_ON_CLOSE, FILE_DISPOSITION_INFO_EX,
+    FILE_DISPOSITION_INFO_EX_FLAGS, FILE_RENAME_INFO,
+};
+
+/// `FILE_RENAME_FLAG_REPLACE_IF_EXISTS`.
+pub(crate) const RENAME_REPLACE_IF_EXISTS: u32 = 0x1;
+/// `FILE_RENAME_FLAG_POSIX_SEMANTICS`: the name is swapped even while other
+/// handles to the replaced file are open.
+pub(crate) const RENAME_POSIX_SEMANTICS: u32 = 0x2;
+
+/// A `FILE_RENAME_INFO` with its name inline.
+pub(crate) struct RenameInfo {
+    storage: Vec<u64>,
+    len: usize,
+}
+
+impl RenameInfo {
+    /// Lay out a rename to the absolute path `target` with `flags`.
+    pub(crate) fn new(target: &[u16], flags: u32) -> Result<Self> {
+        if target.is_empty() || target.contains(&0) {
+            return Err(invalid());
+        }
+        let name_bytes = std::mem::size_of_val(target);
+        let name_len = u32::try_from(name_bytes).map_err(|_| invalid())?;
+        let name_offset = offset_of!(FILE_RENAME_INFO, FileName);
+        // The name is followed by a NUL the structure does not count.
+        let len = name_offset + name_bytes + std::mem::size_of::<u16>();
+        let len = len.max(std::mem::size_of::<FILE_RENAME_INFO>());
+        let mut storage = vec![0u64; len.div_ceil(8)];
+
+        let info = storage.as_mut_ptr().cast::<FILE_RENAME_INFO>();
+        // SAFETY: `storage` is zeroed, `u64`-aligned and at least
+        // `size_of::<FILE_RENAME_INFO>()` bytes, so the header fields are in
+        // bounds; the name is copied into the tail sized for it above.
+        unsafe {
+            (*info).Anonymous.Flags = flags;
+            (*info).RootDirectory = HANDLE::default();
+            (*info).FileNameLength = name_len;
+            let name = storage
+                .as_mut_ptr()
+                .cast::<u8>()
+                .add(name_offset)
+                .cast::<u16>();
+            std::ptr::copy_nonoverlapping(target.as_ptr(), name, target.len());
+        }
+        Ok(RenameInfo { storage, len })
+    }
+
+    /// The buffer as `SetFileInformationByHandle` wants it.
+    pub(crate) fn as_bytes(&self) -> (*const core::ffi::c_void, u32) {
+        (self.storage.as_ptr().cast(), self.len as u32)
+    }
+}
+
+/// The absolute, NUL-free UTF-16 form of `path`.
+pub(crate) fn absolute_wide(path: &Path) -> Result<Vec<u16>> {
+    let absolute = std::path::absolute(path).map_err(|_| invalid())?;
+    Ok(absolute.as_os_str().encode_wide().collect())
+}
+
+/// Rename the file behind `handle` to `target`, replacing whatever is there.
+///
+/// # Safety
+///
+/// `handle` must be a live file handle opened with `DELETE` access.
+pub(crate) unsafe fn rename_replacing(handle: HANDLE, target: &Path) -> Result<()> {
+    let info = RenameInfo::new(
+        &absolute_wide(target)?,
+        RENAME_REPLACE_IF_EXISTS | RENAME_POSIX_SEMANTICS,
+    )?;
+    let (ptr, len) = info.as_bytes();
+    // SAFETY: the caller vouches for the handle; `info` outlives the call and
+    // `len` covers the header and the inline name.
+    unsafe { SetFileInformationByHandle(handle, FileRenameInfoEx, ptr, len) }
+}
+
+/// Set or clear the handle's delete-on-close state.
+///
+/// Clearing also undoes `FILE_FLAG_DELETE_ON_CLOSE` given at open time.
+///
+/// # Safety
+///
+/// `handle` must be a live file handle opened with `DELETE` access.
+pub(crate) unsafe fn set_delete_on_close(handle: HANDLE, delete: bool) -> Result<()> {
+    let mut flags = FILE_DISPOSITION_FLAG_ON_CLOSE;
+    if delete {
+        flags = FILE_DISPOSITION_INFO_EX_FLAGS(flags.0 | FILE_DISPOSITION_FLAG_DELETE.0);
+    }
+    let info = FILE_DISPOSITION_INFO_EX { Flags: flags };
+    // SAFETY: the caller vouches for the handle; `info` outlives the call and
+    // its size is passed alongside it.
+    unsafe {
+        SetFileInformationByHandle(
+            handle,
+            FileDispositionInfoEx,
+            std::ptr::from_ref(&info).cast(),
+            std::mem::size_of::<FILE_DISPOSITION_INFO_EX>() as u32,
+        )
+    }
+}
+
+fn invalid() -> Error {
+    Error::from_hresult(ERROR_INVALID_PARAMETER.to_hresult())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    #[test]
+    fn the_name_is_inline_and_counted_in_bytes() {
+        let name = wide("C:\\data\\config.json");
+        let info =
+            RenameInfo::new(&name, RENAME_REPLACE_IF_EXISTS | RENAME_POSIX_SEMANTICS).unwrap();
+        let (ptr, len) = info.as_bytes();
+        let header = ptr.cast::<FILE_RENAME_INFO>();
+        // SAFETY: `info` is alive and was laid out as a `FILE_RENAME_INFO`.
+        unsafe {
+            assert_eq!((*header).Anonymous.Flags, 0x3);
+            assert_eq!((*header).FileNameLength as usize, name.len() * 2);
+            let offset = offset_of!(FILE_RENAME_INFO, FileName);
+            let inline =
+                std::slice::from_raw_parts(ptr.cast::<u8>().add(offset).cast::<u16>(), name.len());
+            assert_eq!(inline, &name[..]);
+            assert!(len as usize >= offset + name.len() * 2 + 2);
+        }
+    }
+
+    #[test]
+    fn a_short_name_still_covers_the_whole_header() {
+        let info = RenameInfo::new(&wide("a"), 0).unwrap();
+        assert!(info.as_bytes().1 as usize >= std::mem::size_of::<FILE_RENAME_INFO>());
+    }
+
+    #[test]
+    fn empty_and_nul_names_are_rejected() {
+        let invalid = ERROR_INVALID_PARAMETER.to_hresult();
+        assert_eq!(RenameInfo::new(&[], 0).err().unwrap().code(), invalid);
+        assert_eq!(
+            RenameInfo::new(&[b'a' as u16, 0, b'b' as u16], 0)
+                .err()
+                .unwrap()
+                .code(),
+            invalid
+        );
+    }
+}
@@ -0,0 +1,71 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Security descriptors given as SDDL strings.
+//!
+//! Builders accept SDDL rather than raw `SECURITY_ATTRIBUTES` so that the
+//! descriptor's memory is owned here: it is converted immediately before the
+//! create call and freed right after, and never outlives the builder call.
+
+use windows::core::{Error, HSTRING};
+use windows::Win32::Foundation::{LocalFree, ERROR_INVALID_PARAMETER, HLOCAL};
+use windows::Win32::Security::Authorization::{
+    ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+};
+use windows::Win32::Security::{PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES};
+
+use super::error::SetupError;
+
+/// A self-relative security descriptor converted from SDDL.
+pub(crate) struct SecurityDescriptor {
+    raw: PSECURITY_DESCRIPTOR,
+}
+
+impl SecurityDescriptor {
+    /// Convert `sddl`, reporting any rejection as
+    /// [`SetupError::InvalidSecurityDescriptor`].
+    pub(crate) fn from_sddl(sddl: &str) -> Result<Self, SetupError> {
+        if sddl.contains('\0') {
+            return Err(SetupError::InvalidSecurityDescriptor(Error::from_hresult(
+                ERROR_INVALID_PARAMETER.to_hresult(),
+            )));
+        }
+        let wide = HSTRING::from(sddl);
+        let mut raw = PSECURITY_DESCRIPTOR::default();
+        // SAFETY: `wide` is NUL-terminated and outlives the call, and `raw` is
+        // a valid out pointer. On success the descriptor is owned by `Self` and
+        // released with `LocalFree` in drop.
+        unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                &wide,
+                SDDL_REVISION_1,
+                &mut raw,
+                None,
+            )
+        }
+        .map_err(SetupError::InvalidSecurityDescriptor)?;
+        Ok(SecurityDescriptor { raw })
+    }
+
+    /// Attributes pointing at this descriptor; valid while `self` lives.
+    pub(crate) fn attributes(&self, inherit_handle: bool) -> SECURITY_ATTRIBUTES {
+        SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: self.raw.0,
+            bInheritHandle: inherit_handle.into(),
+        }
+    }
+}
+
+impl Drop for SecurityDescriptor {
+    fn drop(&mut self) {
+        // SAFETY: the descriptor was allocated by the SDDL conversion with
+        // `LocalAlloc` and is freed exactly once, here.
+        unsafe {
+            let _ = LocalFree(Some(HLOCAL(self.raw.0)));
+        }
+    }
+}
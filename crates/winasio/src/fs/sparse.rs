@@ -0,0 +1,116 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Encoding for the sparse-file control codes.
+//!
+//! `FILE_ZERO_DATA_INFORMATION` and `FILE_ALLOCATED_RANGE_BUFFER` are both a
+//! pair of signed 64-bit offsets. They are built and parsed as bytes here so
+//! the control operation can own a plain `Vec<u8>` and the conversions, which
+//! carry the range checks, stay testable without a file system.
+
+use std::ops::Range;
+
+use windows::core::{Error, Result};
+use windows::Win32::Foundation::ERROR_INVALID_PARAMETER;
+
+/// Size of one encoded `(offset, length-or-end)` pair.
+pub(crate) const RANGE_RECORD: usize = 16;
+
+/// Encode the `[start, end)` byte range as a pair of `i64`s.
+///
+/// `second` chooses what the second field holds: the exclusive end for
+/// `FSCTL_SET_ZERO_DATA`, the length for `FSCTL_QUERY_ALLOCATED_RANGES`.
+fn encode(first: u64, second: u64) -> Result<Vec<u8>> {
+    let first = i64::try_from(first).map_err(|_| invalid_range())?;
+    let second = i64::try_from(second).map_err(|_| invalid_range())?;
+    let mut bytes = Vec::with_capacity(RANGE_RECORD);
+    bytes.extend_from_slice(&first.to_ne_bytes());
+    bytes.extend_from_slice(&second.to_ne_bytes());
+    Ok(bytes)
+}
+
+/// Input for `FSCTL_SET_ZERO_DATA` covering `len` bytes from `offset`.
+pub(crate) fn zero_data_input(offset: u64, len: u64) -> Result<Vec<u8>> {
+    let end = offset.checked_add(len).ok_or_else(invalid_range)?;
+    encode(offset, end)
+}
+
+/// Input for `FSCTL_QUERY_ALLOCATED_RANGES` over `[start, end)`.
+pub(crate) fn allocated_query_input(start: u64, end: u64) -> Result<Vec<u8>> {
+    let len = end.checked_sub(start).ok_or_else(invalid_range)?;
+    encode(start, len)
+}
+
+/// Append the ranges in an `FSCTL_QUERY_ALLOCATED_RANGES` reply to `out`.
+///
+/// A trailing partial record is ignored; Windows only returns whole records,
+/// so one can only appear if the output buffer was not a multiple of the
+/// record size.
+pub(crate) fn decode_allocated(bytes: &[u8], out: &mut Vec<Range<u64>>) {
+    for record in bytes.chunks_exact(RANGE_RECORD) {
+        let offset = i64::from_ne_bytes(record[..8].try_into().unwrap());
+        let len = i64::from_ne_bytes(record[8..].try_into().unwrap());
+        let (Ok(offset), Ok(len)) = (u64::try_from(offset), u64::try_from(len)) else {
+            continue;
+        };
+        out.push(offset..offset.saturating_add(len));
+    }
+}
+
+fn invalid_range() -> Error {
+    Error::from_hresult(ERROR_INVALID_PARAMETER.to_hresult())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use windows::Win32::System::Ioctl::{FILE_ALLOCATED_RANGE_BUFFER, FILE_ZERO_DATA_INFORMATION};
+
+    #[test]
+    fn records_match_the_windows_layouts() {
+        assert_eq!(
+            std::mem::size_of::<FILE_ZERO_DATA_INFORMATION>(),
+            RANGE_RECORD
+        );
+        assert_eq!(
+            std::mem::size_of::<FILE_ALLOCATED_RANGE_BUFFER>(),
+            RANGE_RECORD
+        );
+    }
+
+    #[test]
+    fn zero_data_carries_the_exclusive_end() {
+        let bytes = zero_data_input(4096, 8192).unwrap();
+        assert_eq!(&bytes[..8], &4096i64.to_ne_bytes());
+        assert_eq!(&bytes[8..], &12288i64.to_ne_bytes());
+    }
+
+    #[test]
+    fn allocated_query_carries_the_length() {
+        let bytes = allocated_query_input(100, 250).unwrap();
+        assert_eq!(&bytes[..8], &100i64.to_ne_bytes());
+        assert_eq!(&bytes[8..], &150i64.to_ne_bytes());
+    }
+
+    #[test]
+    fn ranges_windows_cannot_express_are_rejected() {
+        let invalid = ERROR_INVALID_PARAMETER.to_hresult();
+        assert_eq!(zero_data_input(u64::MAX, 1).unwrap_err().code(), invalid);
+        assert_eq!(zero_data_input(1 << 63, 0).unwrap_err().code(), invalid);
+        assert_eq!(allocated_query_input(10, 5).unwrap_err().code(), invalid);
+    }
+
+    #[test]
+    fn replies_decode_in_order_and_ignore_a_partial_tail() {
+        let mut bytes = encode(0, 4096).unwrap();
+        bytes.extend(encode(65536, 100).unwrap());
+        bytes.extend_from_slice(&[0u8; 7]);
+
+        let mut ranges = vec![1..2];
+        decode_allocated(&bytes, &mut ranges);
+        assert_eq!(ranges, vec![1..2, 0..4096, 65536..65636]);
+    }
+}
@@ -0,0 +1,186 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Temporary files that either vanish or atomically replace a target.
+//!
+//! The file is created with `FILE_FLAG_DELETE_ON_CLOSE`, so the kernel deletes
+//! it when the last handle closes — including when the process dies without
+//! running any destructor. [`TempFile::persist`] is the only way to keep it,
+//! and it moves the file over its target in one step: a reader of the target
+//! sees the old contents or the new ones, never a partial write.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use windows::core::Error;
+use windows::Win32::Foundation::{ERROR_ALREADY_EXISTS, ERROR_FILE_EXISTS};
+
+use crate::iocp::{Registrar, Submitter};
+
+use super::error::win32_code;
+use super::persist::set_delete_on_close;
+use super::{File, OpenOptions, SetupError};
+
+/// Names tried before giving up on finding an unused one.
+const NAME_ATTEMPTS: u32 = 16;
+
+/// Shared by every caller of [`unique_name`], whatever its prefix.
+static NEXT_NAME: AtomicU64 = AtomicU64::new(0);
+
+/// A delete-on-close file in a chosen directory.
+///
+/// Reach the file through [`TempFile::file`] for reads and writes. Dropping it
+/// closes the handle and the file is gone.
+pub struct TempFile<S: Submitter> {
+    file: File<S>,
+    path: PathBuf,
+}
+
+impl<S: Submitter> TempFile<S> {
+    /// Create a temporary file in [`std::env::temp_dir`].
+    pub fn new<R>(registrar: &R) -> Result<Self, SetupError>
+    where
+        R: Registrar<Io = S>,
+    {
+        Self::new_in(registrar, std::env::temp_dir())
+    }
+
+    /// Create a temporary file in `dir`, opened for reading and writing.
+    ///
+    /// Create it on the volume you mean to persist it to: the final rename
+    /// cannot cross volumes.
+    pub fn new_in<R>(registrar: &R, dir: impl AsRef<Path>) -> Result<Self, SetupError>
+    where
+        R: Registrar<Io = S>,
+    {
+        let mut options = OpenOptions::new();
+        options
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .delete_on_close(true);
+
+        let mut attempts = 1;
+        loop {
+            let path = dir
+                .as_ref()
+                .join(format!("{}.tmp", unique_name(".winasio")));
+            match options.open(registrar, &path) {
+                Ok(file) => return Ok(TempFile { file, path }),
+                Err(SetupError::Win32(e)) if is_name_taken(&e) && attempts < NAME_ATTEMPTS => {
+                    attempts += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// The open file.
+    pub fn file(&self) -> &File<S> {
+        &self.file
+    }
+
+    /// Where the file currently lives.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Atomically move the file over `path` and keep it.
+    ///
+    /// See [`File::persist_as`], which flushes the data before the rename.
+    /// The delete-on-close this file was created with is cancelled first, so
+    /// a crash before the rename leaves a stray file rather than a deleted
+    /// target. On failure the file is still delete-on-close and is handed
+    /// back inside the error, so the caller can retry or drop it.
+    pub fn persist(self, path: impl AsRef<Path>) -> Result<File<S>, PersistError<S>> {
+        // SAFETY: the handle is owned by `self.file` and was opened with
+        // `DELETE` access, which `delete_on_close` requests.
+        if let Err(error) = unsafe { set_delete_on_close(self.file.handle(), false) } {
+            return Err(PersistError { error, file: self });
+        }
+        match self.file.persist_as(path) {
+            Ok(()) => Ok(self.file),
+            Err(error) => {
+                // SAFETY: as above. Best effort: if re-arming fails too, the
+                // original error is the one worth reporting.
+                let _ = unsafe { set_delete_on_close(self.file.handle(), true) };
+                Err(PersistError { error, file: self })
+            }
+        }
+    }
+}
+
+impl<S: Submitter> std::fmt::Debug for TempFile<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TempFile")
+            .field("file", &self.file)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// A failed [`TempFile::persist`], with the file handed back.
+pub struct PersistError<S: Submitter> {
+    /// Why the rename failed.
+    pub error: Error,
+    /// The temporary file, still delete-on-close.
+    pub file: TempFile<S>,
+}
+
+impl<S: Submitter> std::fmt::Debug for PersistError<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PersistError")
+            .field("error", &self.error)
+            .field("file", &self.file)
+            .finish()
+    }
+}
+
+impl<S: Submitter> std::fmt::Display for PersistError<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to persist temporary file: {}", self.error)
+    }
+}
+
+impl<S: Submitter> std::error::Error for PersistError<S> {}
+
+/// A name no other call in this process returns, and unlikely to be taken by
+/// another process: `prefix`, the process id, a counter and the clock's
+/// nanoseconds.
+///
+/// Used for temporary files and for the private pipes behind
+/// [`crate::pipe::pair`]. Creating the object must still fail if the name
+/// exists, since uniqueness across processes is not guaranteed.
+pub(crate) fn unique_name(prefix: &str) -> String {
+    let n = NEXT_NAME.fetch_add(1, Ordering::Relaxed);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    format!("{prefix}-{}-{n}-{nanos:08x}", std::process::id())
+}
+
+fn is_name_taken(err: &Error) -> bool {
+    matches!(
+        win32_code(err),
+        Some(code) if code == ERROR_FILE_EXISTS.0 || code == ERROR_ALREADY_EXISTS.0
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_hidden_and_do_not_repeat() {
+        let a = unique_name(".winasio");
+        let b = unique_name(".winasio");
+        assert!(a.starts_with(".winasio-"));
+        assert_ne!(a, b);
+        // One counter for every prefix.
+        assert_ne!(unique_name("x")[1..], unique_name("y")[1..]);
+    }
+}
@@ -11,6 +11,9 @@
 //! [`crate::net::TcpStream`]. The helper loops themselves are defined once
 //! here, so all three share the same progress-accounting and failure
 //! classification.
+//!
+//! Streams split into owned read and write halves through [`Split`]; see
+//! [`crate::net::TcpStream::into_split`].
 
 use std::future::Future;
 
@@ -26,6 +29,11 @@ use windows::Win32::Networking::WinSock::{
 use crate::fs::ReadOutcome;
 use crate::iocp::{IoBuf, IoBufMut, OpResult, Submitter};
 
+mod split;
+
+pub(crate) use split::split;
+pub use split::{OwnedReadHalf, OwnedWriteHalf, ReuniteError, Split};
+
 const HELPER_CHUNK: usize = 4096;
 
 /// Failure categories reported by whole-payload helpers.
@@ -327,7 +335,7 @@ where
 /// through `RtlNtStatusToDosError`. Recognising only one spelling would make
 /// `read_to_end` return a `Win32` error for a perfectly ordinary close, and
 /// only on whichever path the test did not exercise.
-fn classify_platform_error(error: Error) -> TransferError {
+pub(crate) fn classify_platform_error(error: Error) -> TransferError {
     // Pipe spellings, plus the eight ways a TCP disconnection can be named.
     const CLOSED: [u32; 11] = [
         ERROR_BROKEN_PIPE.0,
@@ -357,14 +365,14 @@ fn classify_platform_error(error: Error) -> TransferError {
     }
 }
 
-struct TailBuf<B> {
+pub(crate) struct TailBuf<B> {
     inner: B,
     offset: usize,
     limit: usize,
 }
 
 impl<B> TailBuf<B> {
-    fn new(inner: B, offset: usize, limit: usize) -> Self {
+    pub(crate) fn new(inner: B, offset: usize, limit: usize) -> Self {
         TailBuf {
             inner,
             offset,
@@ -372,7 +380,7 @@ impl<B> TailBuf<B> {
         }
     }
 
-    fn into_inner(self) -> B {
+    pub(crate) fn into_inner(self) -> B {
         self.inner
     }
 }
@@ -0,0 +1,259 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Owned read and write halves of a stream.
+//!
+//! Every stream method already takes `&self`, so sharing one stream between a
+//! reading task and a writing task works; what the halves add is ownership
+//! that says which task does what, and a shutdown tied to the writer. Both
+//! halves hold the same stream through an `Arc`, and the stream is dropped,
+//! and its handle closed, when the second half goes.
+
+use std::future::Future;
+use std::net::Shutdown;
+use std::sync::Arc;
+
+use crate::fs::ReadOutcome;
+use crate::iocp::{IoBuf, IoBufMut, OpResult, Submitter};
+use crate::net::{TcpStream, UnixStream};
+use crate::pipe::NamedPipe;
+
+use super::TransferResult;
+
+mod sealed {
+    use std::future::Future;
+
+    use crate::fs::ReadOutcome;
+    use crate::io::TransferResult;
+    use crate::iocp::{IoBuf, IoBufMut, OpResult};
+
+    pub trait Sealed {
+        fn read<B>(&self, buffer: B) -> impl Future<Output = OpResult<ReadOutcome, B>>
+        where
+            B: IoBufMut + Send;
+
+        fn read_exact<B>(&self, buffer: B) -> impl Future<Output = TransferResult<B>>
+        where
+            B: IoBufMut + Send;
+
+        fn read_to_end(&self, buffer: Vec<u8>) -> impl Future<Output = TransferResult<Vec<u8>>>;
+
+        fn write<B>(&self, buffer: B) -> impl Future<Output = OpResult<usize, B>>
+        where
+            B: IoBuf + Send;
+
+        fn write_all<B>(&self, buffer: B) -> impl Future<Output = TransferResult<B>>
+        where
+            B: IoBuf + Send;
+
+        /// What dropping the write half does to the stream.
+        fn close_write(&self);
+    }
+}
+
+/// A stream that can be split into owned halves.
+///
+/// Implemented by [`TcpStream`], [`UnixStream`] and [`NamedPipe`]; it cannot
+/// be implemented outside this crate.
+pub trait Split: sealed::Sealed {}
+
+/// Forwards every operation to the stream's own method of the same name, and
+/// runs `$close` when the write half is dropped.
+macro_rules! forward_split {
+    ($stream:ident, |$this:ident| $close:block) => {
+        impl<S: Submitter> sealed::Sealed for $stream<S> {
+            fn read<B>(&self, buffer: B) -> impl Future<Output = OpResult<ReadOutcome, B>>
+            where
+                B: IoBufMut + Send,
+            {
+                $stream::read(self, buffer)
+            }
+
+            fn read_exact<B>(&self, buffer: B) -> impl Future<Output = TransferResult<B>>
+            where
+                B: IoBufMut + Send,
+            {
+                $stream::read_exact(self, buffer)
+            }
+
+            fn read_to_end(
+                &self,
+                buffer: Vec<u8>,
+            ) -> impl Future<Output = TransferResult<Vec<u8>>> {
+                $stream::read_to_end(self, buffer)
+            }
+
+            fn write<B>(&self, buffer: B) -> impl Future<Output = OpResult<usize, B>>
+            where
+                B: IoBuf + Send,
+            {
+                $stream::write(self, buffer)
+            }
+
+            fn write_all<B>(&self, buffer: B) -> impl Future<Output = TransferResult<B>>
+            where
+                B: IoBuf + Send,
+            {
+                $stream::write_all(self, buffer)
+            }
+
+            fn close_write(&self) {
+                let $this = self;
+                $close
+            }
+        }
+
+        impl<S: Submitter> Split for $stream<S> {}
+    };
+}
+
+// Sockets send FIN, so the peer reads a graceful close while this side can
+// still receive. A failure means the connection is already gone, which is
+// what the shutdown was going to tell the peer anyway.
+forward_split!(TcpStream, |stream| {
+    let _ = stream.shutdown(Shutdown::Write);
+});
+forward_split!(UnixStream, |stream| {
+    let _ = stream.shutdown(Shutdown::Write);
+});
+// A pipe has no half-close: the peer sees the pipe close only once both
+// halves are gone.
+forward_split!(NamedPipe, |_pipe| {});
+
+/// Split `stream` into halves sharing it.
+pub(crate) fn split<T: Split>(stream: T) -> (OwnedReadHalf<T>, OwnedWriteHalf<T>) {
+    let stream = Arc::new(stream);
+    (
+        OwnedReadHalf {
+            stream: stream.clone(),
+        },
+        OwnedWriteHalf {
+            stream,
+            close_on_drop: true,
+        },
+    )
+}
+
+/// The reading half of a split stream.
+pub struct OwnedReadHalf<T: Split> {
+    stream: Arc<T>,
+}
+
+impl<T: Split> OwnedReadHalf<T> {
+    /// Start a read, as the stream's own `read`.
+    pub fn read<B>(&self, buffer: B) -> impl Future<Output = OpResult<ReadOutcome, B>> + '_
+    where
+        B: IoBufMut + Send,
+    {
+        self.stream.read(buffer)
+    }
+
+    /// Fill the buffer's capacity, as the stream's own `read_exact`.
+    pub fn read_exact<B>(&self, buffer: B) -> impl Future<Output = TransferResult<B>> + '_
+    where
+        B: IoBufMut + Send,
+    {
+        self.stream.read_exact(buffer)
+    }
+
+    /// Read until the peer closes, as the stream's own `read_to_end`.
+    pub fn read_to_end(
+        &self,
+        buffer: Vec<u8>,
+    ) -> impl Future<Output = TransferResult<Vec<u8>>> + '_ {
+        self.stream.read_to_end(buffer)
+    }
+
+    /// Put the stream back together.
+    ///
+    /// Fails, handing both halves back, if they came from different streams.
+    /// The write half's shutdown does not run.
+    pub fn reunite(self, mut other: OwnedWriteHalf<T>) -> Result<T, ReuniteError<T>> {
+        if !Arc::ptr_eq(&self.stream, &other.stream) {
+            return Err(ReuniteError(self, other));
+        }
+        other.close_on_drop = false;
+        drop(other);
+        // Operations hold clones of the socket or handle, never of this
+        // `Arc`, so the two halves were its only owners.
+        Ok(Arc::into_inner(self.stream).expect("only the two halves share the stream"))
+    }
+}
+
+/// The writing half of a split stream.
+///
+/// Dropping it ends this side's output: a socket is shut down for sending, so
+/// the peer reads a graceful close while the read half keeps receiving. A
+/// pipe has no half-close, and its peer sees the close once both halves are
+/// dropped.
+pub struct OwnedWriteHalf<T: Split> {
+    stream: Arc<T>,
+    close_on_drop: bool,
+}
+
+impl<T: Split> OwnedWriteHalf<T> {
+    /// Start a write, as the stream's own `write`.
+    pub fn write<B>(&self, buffer: B) -> impl Future<Output = OpResult<usize, B>> + '_
+    where
+        B: IoBuf + Send,
+    {
+        self.stream.write(buffer)
+    }
+
+    /// Write the whole buffer, as the stream's own `write_all`.
+    pub fn write_all<B>(&self, buffer: B) -> impl Future<Output = TransferResult<B>> + '_
+    where
+        B: IoBuf + Send,
+    {
+        self.stream.write_all(buffer)
+    }
+
+    /// Put the stream back together; see [`OwnedReadHalf::reunite`].
+    pub fn reunite(self, other: OwnedReadHalf<T>) -> Result<T, ReuniteError<T>> {
+        other.reunite(self)
+    }
+}
+
+impl<T: Split> Drop for OwnedWriteHalf<T> {
+    fn drop(&mut self) {
+        if self.close_on_drop {
+            self.stream.close_write();
+        }
+    }
+}
+
+impl<T: Split + std::fmt::Debug> std::fmt::Debug for OwnedReadHalf<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OwnedReadHalf")
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+impl<T: Split + std::fmt::Debug> std::fmt::Debug for OwnedWriteHalf<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OwnedWriteHalf")
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+/// Halves of two different streams, handed back by a failed reunite.
+pub struct ReuniteError<T: Split>(pub OwnedReadHalf<T>, pub OwnedWriteHalf<T>);
+
+impl<T: Split> std::fmt::Debug for ReuniteError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ReuniteError").finish_non_exhaustive()
+    }
+}
+
+impl<T: Split> std::fmt::Display for ReuniteError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "tried to reunite halves of different streams")
+    }
+}
+
+impl<T: Split> std::error::Error for ReuniteError<T> {}
@@ -196,7 +196,10 @@ impl Registrar for ThreadPool {
     type Io = ThreadPoolIo;
 
     fn register(&self, handle: HANDLE) -> Result<Self::Io, RegistrationError> {
-        ThreadPoolIo::new(handle)
+        let io = ThreadPoolIo::new(handle)?;
+        #[cfg(any(test, feature = "test-util"))]
+        counter::inc();
+        Ok(io)
     }
 }
 
@@ -211,11 +214,41 @@ impl Registrar for Rc<Proactor> {
 
     fn register(&self, handle: HANDLE) -> Result<Self::Io, RegistrationError> {
         Proactor::attach(self, handle)?;
+        #[cfg(any(test, feature = "test-util"))]
+        counter::inc();
         // Shared ownership, so the proactor cannot outlive its files.
         Ok(Rc::clone(self))
     }
 }
 
+#[cfg(any(test, feature = "test-util"))]
+mod counter {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static REGISTERED: AtomicUsize = AtomicUsize::new(0);
+
+    pub(super) fn inc() {
+        REGISTERED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Total number of registrations made through a
+    /// [`Registrar`](super::Registrar) since the process started.
+    ///
+    /// Test support. This is a cumulative counter, not a live count: it is
+    /// never decremented, so it says nothing about how many handles are
+    /// registered now or whether any leaked. Use it only for before/after
+    /// deltas, for instance to check that reusing a socket did not register
+    /// it again; [`live_sockets`](crate::net::live_sockets) is the live count.
+    /// Process-global, so a test taking a delta must hold
+    /// [`socket_guard`](crate::net::socket_guard).
+    pub fn registrations() -> usize {
+        REGISTERED.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+pub use counter::registrations;
+
 #[cfg(test)]
 mod tests {
     use super::*;
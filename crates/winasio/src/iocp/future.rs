@@ -58,6 +58,22 @@ impl<T: OpCode> Submit<T> {
     pub fn is_ready(&self) -> bool {
         self.inline.is_some()
     }
+
+    /// Request cancellation without giving up the operation.
+    ///
+    /// Unlike dropping, this keeps the future: it still resolves once the
+    /// completion lands and still hands the state back. The result is usually
+    /// `ERROR_OPERATION_ABORTED`, but an operation that finished before the
+    /// request took effect reports its real result. Does nothing once the
+    /// operation has finished.
+    pub(crate) fn cancel(&self) {
+        if self.inline.is_some() {
+            return;
+        }
+        if let Some(key) = &self.key {
+            let _ = key.cancel();
+        }
+    }
 }
 
 impl<T: OpCode> Future for Submit<T> {
@@ -104,13 +104,15 @@ pub use future::Submit;
 pub use handle::Handle;
 pub use op::{win32_result, IntoInner, OpCode};
 pub use ops::{
-    ConnectPipe, ReadAt, ReadHandle, ReadHandleAt, SendHandle, WaitForHandle, WriteAt, WriteHandle,
-    WriteHandleAt,
+    ConnectPipe, DeviceControl, ReadAt, ReadHandle, ReadHandleAt, SendHandle, TransactPipe,
+    WaitForHandle, WriteAt, WriteHandle, WriteHandleAt,
 };
 pub use port::RegistrationError;
 pub use proactor::Proactor;
 pub use threadpool::ThreadPoolIo;
 
+#[cfg(any(test, feature = "test-util"))]
+pub use backend::registrations;
 #[cfg(any(test, feature = "test-util"))]
 pub use port::RefuseSkipMode;
 #[cfg(any(test, feature = "test-util"))]
@@ -0,0 +1,111 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Overlapped device and file-system control.
+//!
+//! One operation covers every `DeviceIoControl` code: the caller supplies the
+//! code, an input buffer and an output buffer, and the operation owns both
+//! until completion. Codes that report a partial result with `ERROR_MORE_DATA`
+//! get the delivered byte count back, exactly like a message-mode pipe read.
+
+use std::task::Poll;
+
+use windows::core::Result;
+use windows::Win32::System::IO::{CancelIoEx, OVERLAPPED};
+
+use crate::fs::outcome::ReadOutcome;
+use crate::iocp::buf::{IoBuf, IoBufMut};
+use crate::iocp::handle::Handle;
+use crate::iocp::op::{win32_result, IntoInner, OpCode};
+
+use super::reply::{classify_partial, request_region, Reply};
+use super::stream::inline_transferred_count;
+use super::sys::{set_offset, DeviceIoControl};
+
+/// Issue a control code against a shared handle.
+pub struct DeviceControl<I: IoBuf, O: IoBufMut> {
+    handle: Handle,
+    code: u32,
+    input: I,
+    output: Reply<O>,
+}
+
+impl<I: IoBuf, O: IoBufMut> DeviceControl<I, O> {
+    /// Send `code` with `input`, receiving into `output`'s spare capacity.
+    pub fn new(handle: Handle, code: u32, input: I, output: O) -> Self {
+        DeviceControl {
+            handle,
+            code,
+            input,
+            output: Reply::new(output, classify_partial),
+        }
+    }
+
+    /// Convert the byte-count result, reporting a partial answer as
+    /// [`ReadOutcome::MoreData`].
+    pub(crate) fn finish(self, result: Result<usize>) -> (Result<ReadOutcome>, O) {
+        self.output.finish(result)
+    }
+}
+
+impl<I: IoBuf, O: IoBufMut> IntoInner for DeviceControl<I, O> {
+    type Inner = (I, O);
+
+    fn into_inner(self) -> (I, O) {
+        (self.input, self.output.into_inner())
+    }
+}
+
+unsafe impl<I: IoBuf + Send, O: IoBufMut + Send> OpCode for DeviceControl<I, O> {
+    unsafe fn operate(&mut self, optr: *mut OVERLAPPED) -> Poll<Result<usize>> {
+        // Control codes take no offset, but the fields must be initialised.
+        unsafe { set_offset(optr, 0) };
+
+        let (in_ptr, in_len) = match request_region(&self.input) {
+            Ok(region) => region,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        let (out_ptr, out_len) = match self.output.region() {
+            Ok(region) => region,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+
+        // SAFETY: both regions are owned by this operation, which is retained
+        // until completion; the output region is spare capacity Windows may
+        // fill. Overlapped calls report the count through the OVERLAPPED, so
+        // no `bytes_returned` pointer is passed.
+        let ok = unsafe {
+            DeviceIoControl(
+                self.handle.raw(),
+                self.code,
+                in_ptr,
+                in_len,
+                out_ptr,
+                out_len,
+                std::ptr::null_mut(),
+                optr,
+            )
+        };
+        // No Windows call may occur between `DeviceIoControl` and
+        // `win32_result`.
+        let result = unsafe { win32_result(ok != 0, optr) };
+        if let Poll::Ready(ref ready) = result {
+            let transferred = inline_transferred_count(ready, optr);
+            self.output.record(ready, transferred);
+        }
+        result
+    }
+
+    unsafe fn cancel(&mut self, optr: *mut OVERLAPPED) -> Result<()> {
+        // SAFETY: `optr` is the same overlapped pointer passed to `operate`;
+        // the handle is kept alive by this operation's `Handle` clone.
+        unsafe { CancelIoEx(self.handle.raw(), Some(optr)) }
+    }
+
+    unsafe fn on_complete_with(&mut self, result: &Result<usize>, transferred: usize) {
+        self.output.record(result, transferred);
+    }
+}
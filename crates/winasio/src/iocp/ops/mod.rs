@@ -8,9 +8,14 @@
 
 pub mod event;
 pub mod file;
+mod ioctl;
+mod reply;
 mod stream;
 pub(crate) mod sys;
+mod transact;
 
 pub use event::WaitForHandle;
 pub use file::{ReadAt, SendHandle, WriteAt};
+pub use ioctl::DeviceControl;
 pub use stream::{ConnectPipe, ReadHandle, ReadHandleAt, WriteHandle, WriteHandleAt};
+pub use transact::TransactPipe;
@@ -0,0 +1,130 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Request and reply regions shared by device control and pipe transactions.
+//!
+//! [`DeviceControl`](super::DeviceControl) and
+//! [`TransactPipe`](super::TransactPipe) both hand Windows a request buffer and
+//! a reply buffer in one call, and both can be answered in part with
+//! `ERROR_MORE_DATA`. The regions they pass and the bookkeeping on completion
+//! live here, once; the operations differ only in which failures count as a
+//! read outcome rather than an error.
+
+use windows::core::Result;
+use windows::Win32::Foundation::ERROR_MORE_DATA;
+
+use crate::fs::outcome::ReadOutcome;
+use crate::iocp::buf::{IoBuf, IoBufMut};
+
+use super::sys::checked_u32_len;
+
+/// Decides which completions are a read outcome rather than an error.
+pub(super) type Classify = fn(&Result<usize>, usize) -> Option<ReadOutcome>;
+
+/// The pointer and length to pass for a request. An empty request is passed
+/// as null, which some drivers require.
+pub(super) fn request_region<I: IoBuf>(request: &I) -> Result<(*const u8, u32)> {
+    let len = checked_u32_len(request.bytes_init())?;
+    let ptr = if len == 0 {
+        std::ptr::null()
+    } else {
+        request.stable_ptr()
+    };
+    Ok((ptr, len))
+}
+
+/// A reply buffer and what its completion turned out to be.
+pub(super) struct Reply<O: IoBufMut> {
+    buffer: O,
+    classify: Classify,
+    outcome: Option<ReadOutcome>,
+}
+
+impl<O: IoBufMut> Reply<O> {
+    pub(super) fn new(buffer: O, classify: Classify) -> Self {
+        Reply {
+            buffer,
+            classify,
+            outcome: None,
+        }
+    }
+
+    /// The pointer and length to pass for the reply: the buffer's spare
+    /// capacity, or null when it has none.
+    pub(super) fn region(&mut self) -> Result<(*mut u8, u32)> {
+        let spare = self.buffer.as_uninit();
+        let len = checked_u32_len(spare.len())?;
+        let ptr = if len == 0 {
+            std::ptr::null_mut()
+        } else {
+            spare.as_mut_ptr().cast::<u8>()
+        };
+        Ok((ptr, len))
+    }
+
+    /// Record a completion, publishing the bytes Windows wrote.
+    pub(super) fn record(&mut self, result: &Result<usize>, transferred: usize) {
+        self.outcome = (self.classify)(result, transferred);
+        if result.is_ok() || matches!(self.outcome, Some(ReadOutcome::MoreData(_))) {
+            let n = transferred.min(self.buffer.bytes_total());
+            // SAFETY: Windows reported writing `transferred` bytes of reply.
+            // The value is clamped to this buffer's capacity before publication.
+            unsafe { self.buffer.set_init(n) };
+        }
+    }
+
+    /// Convert the byte-count result, reporting a partial reply as
+    /// [`ReadOutcome::MoreData`].
+    pub(super) fn finish(self, result: Result<usize>) -> (Result<ReadOutcome>, O) {
+        let outcome = match (result, self.outcome) {
+            (_, Some(outcome)) => Ok(outcome),
+            (Ok(n), None) => Ok(ReadOutcome::Bytes(n)),
+            (Err(e), None) => Err(e),
+        };
+        (outcome, self.buffer)
+    }
+
+    pub(super) fn into_inner(self) -> O {
+        self.buffer
+    }
+}
+
+/// Only a partial reply is an outcome; every other failure is an error.
+pub(super) fn classify_partial(result: &Result<usize>, transferred: usize) -> Option<ReadOutcome> {
+    match result {
+        Ok(n) => Some(ReadOutcome::Bytes(*n)),
+        Err(e) if e.code() == ERROR_MORE_DATA.to_hresult() => {
+            Some(ReadOutcome::MoreData(transferred))
+        }
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use windows::core::Error;
+    use windows::Win32::Foundation::ERROR_BROKEN_PIPE;
+
+    #[test]
+    fn only_a_partial_reply_is_an_outcome() {
+        let more = Err(Error::from_hresult(ERROR_MORE_DATA.to_hresult()));
+        assert_eq!(classify_partial(&more, 5), Some(ReadOutcome::MoreData(5)));
+        assert_eq!(classify_partial(&Ok(3), 3), Some(ReadOutcome::Bytes(3)));
+        let broken = Err(Error::from_hresult(ERROR_BROKEN_PIPE.to_hresult()));
+        assert_eq!(classify_partial(&broken, 0), None);
+    }
+
+    #[test]
+    fn an_empty_request_is_passed_as_null() {
+        let (ptr, len) = request_region(&Vec::<u8>::new()).unwrap();
+        assert!(ptr.is_null());
+        assert_eq!(len, 0);
+        let (ptr, len) = request_region(&vec![1u8, 2]).unwrap();
+        assert!(!ptr.is_null());
+        assert_eq!(len, 2);
+    }
+}
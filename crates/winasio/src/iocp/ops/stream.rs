@@ -25,7 +25,7 @@ use crate::iocp::op::{win32_result, IntoInner, OpCode};
 
 use super::sys::{checked_u32_len, set_offset, ReadFile, WriteFile};
 
-fn inline_transferred_count(result: &Result<usize>, optr: *mut OVERLAPPED) -> usize {
+pub(super) fn inline_transferred_count(result: &Result<usize>, optr: *mut OVERLAPPED) -> usize {
     match result {
         Ok(transferred) => *transferred,
         Err(err) if err.code() == ERROR_MORE_DATA.to_hresult() => {
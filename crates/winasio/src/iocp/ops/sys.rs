@@ -4,7 +4,7 @@
 // license information.
 // ------------------------------------------------------------
 
-//! Raw Win32 bindings for the read and write paths.
+//! Raw Win32 bindings for the read, write, device-control and transact paths.
 //!
 //! The `windows` crate's `ReadFile` takes `Option<&mut [u8]>`, which forces a
 //! caller filling spare capacity to construct a Rust slice over uninitialised
@@ -33,6 +33,31 @@ extern "system" {
         bytes_written: *mut u32,
         overlapped: *mut OVERLAPPED,
     ) -> i32;
+
+    // The `windows` crate's wrapper turns the status into a `Result` itself;
+    // declaring it raw leaves the thread's last error for `win32_result`, and
+    // takes the output region as a pointer for the same reason as `ReadFile`.
+    pub(crate) fn DeviceIoControl(
+        hdevice: HANDLE,
+        io_control_code: u32,
+        in_buffer: *const u8,
+        in_buffer_size: u32,
+        out_buffer: *mut u8,
+        out_buffer_size: u32,
+        bytes_returned: *mut u32,
+        overlapped: *mut OVERLAPPED,
+    ) -> i32;
+
+    // Raw for the same reasons as `DeviceIoControl`.
+    pub(crate) fn TransactNamedPipe(
+        named_pipe: HANDLE,
+        in_buffer: *const u8,
+        in_buffer_size: u32,
+        out_buffer: *mut u8,
+        out_buffer_size: u32,
+        bytes_read: *mut u32,
+        overlapped: *mut OVERLAPPED,
+    ) -> i32;
 }
 
 /// Reject a transfer length Windows cannot express.
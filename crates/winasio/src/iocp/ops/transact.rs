@@ -0,0 +1,113 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Request/response on a message-mode pipe.
+//!
+//! `TransactNamedPipe` writes one message and reads one reply as a single
+//! operation. A reply that does not fit reports `ERROR_MORE_DATA` with the
+//! delivered byte count, exactly like a message-mode read; the rest of the
+//! reply is then read with ordinary reads. A server that has gone reports
+//! [`ReadOutcome::ClosedPeer`], as a read would.
+
+use std::task::Poll;
+
+use windows::core::Result;
+use windows::Win32::System::IO::{CancelIoEx, OVERLAPPED};
+
+use crate::fs::outcome::{classify_read, ReadOutcome};
+use crate::iocp::buf::{IoBuf, IoBufMut};
+use crate::iocp::handle::Handle;
+use crate::iocp::op::{win32_result, IntoInner, OpCode};
+
+use super::reply::{request_region, Reply};
+use super::stream::inline_transferred_count;
+use super::sys::{set_offset, TransactNamedPipe};
+
+/// Write a request message and read the reply on a shared pipe handle.
+///
+/// The handle must be a message-type pipe in message read mode.
+pub struct TransactPipe<I: IoBuf, O: IoBufMut> {
+    handle: Handle,
+    request: I,
+    response: Reply<O>,
+}
+
+impl<I: IoBuf, O: IoBufMut> TransactPipe<I, O> {
+    /// Send `request`, receiving the reply into `response`'s spare capacity.
+    pub fn new(handle: Handle, request: I, response: O) -> Self {
+        TransactPipe {
+            handle,
+            request,
+            response: Reply::new(response, classify_read),
+        }
+    }
+
+    /// Convert the byte-count result, reporting a partial reply as
+    /// [`ReadOutcome::MoreData`] and a broken pipe as
+    /// [`ReadOutcome::ClosedPeer`].
+    pub(crate) fn finish(self, result: Result<usize>) -> (Result<ReadOutcome>, (I, O)) {
+        let (outcome, response) = self.response.finish(result);
+        (outcome, (self.request, response))
+    }
+}
+
+impl<I: IoBuf, O: IoBufMut> IntoInner for TransactPipe<I, O> {
+    type Inner = (I, O);
+
+    fn into_inner(self) -> (I, O) {
+        (self.request, self.response.into_inner())
+    }
+}
+
+unsafe impl<I: IoBuf + Send, O: IoBufMut + Send> OpCode for TransactPipe<I, O> {
+    unsafe fn operate(&mut self, optr: *mut OVERLAPPED) -> Poll<Result<usize>> {
+        // Pipes ignore offsets, but the fields must be initialised.
+        unsafe { set_offset(optr, 0) };
+
+        let (in_ptr, in_len) = match request_region(&self.request) {
+            Ok(region) => region,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        let (out_ptr, out_len) = match self.response.region() {
+            Ok(region) => region,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+
+        // SAFETY: both regions are owned by this operation, which is retained
+        // until completion; the reply region is spare capacity Windows may
+        // fill. Overlapped calls report the count through the OVERLAPPED, so
+        // no `bytes_read` pointer is passed.
+        let ok = unsafe {
+            TransactNamedPipe(
+                self.handle.raw(),
+                in_ptr,
+                in_len,
+                out_ptr,
+                out_len,
+                std::ptr::null_mut(),
+                optr,
+            )
+        };
+        // No Windows call may occur between `TransactNamedPipe` and
+        // `win32_result`.
+        let result = unsafe { win32_result(ok != 0, optr) };
+        if let Poll::Ready(ref ready) = result {
+            let transferred = inline_transferred_count(ready, optr);
+            self.response.record(ready, transferred);
+        }
+        result
+    }
+
+    unsafe fn cancel(&mut self, optr: *mut OVERLAPPED) -> Result<()> {
+        // SAFETY: `optr` is the same overlapped pointer passed to `operate`;
+        // the handle is kept alive by this operation's `Handle` clone.
+        unsafe { CancelIoEx(self.handle.raw(), Some(optr)) }
+    }
+
+    unsafe fn on_complete_with(&mut self, result: &Result<usize>, transferred: usize) {
+        self.response.record(result, transferred);
+    }
+}
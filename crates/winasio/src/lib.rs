@@ -4,12 +4,15 @@
 // license information.
 // ------------------------------------------------------------
 
+pub mod framing;
 pub mod fs;
 pub mod httpsys;
 pub mod io;
 pub mod iocp;
+pub mod mailslot;
 pub mod net;
 pub mod pipe;
+pub mod time;
 pub mod winhttp;
 
 pub mod sys;
@@ -0,0 +1,129 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Mailslot writing end.
+
+use std::future::Future;
+
+use windows::Win32::Foundation::HANDLE;
+use windows::Win32::Storage::FileSystem::{
+    CreateFileW, FILE_FLAG_OVERLAPPED, FILE_GENERIC_WRITE, FILE_SHARE_READ, OPEN_EXISTING,
+};
+
+use crate::fs::SetupError;
+use crate::iocp::{Handle, IntoInner, IoBuf, OpResult, Registrar, Submitter, WriteHandle};
+use crate::pipe::{drop_inner, Inner};
+
+use super::name::{mailslot_path, MailslotTarget};
+
+/// The writing end of a mailslot.
+///
+/// Each write delivers one message. Writes to a local or single remote
+/// mailslot succeed only while the mailslot exists. Writes to a domain are
+/// broadcast as datagrams: they succeed whether or not anyone is listening,
+/// and a message longer than 424 bytes is not delivered.
+pub struct MailslotClient<S: Submitter> {
+    inner: Option<Inner<S>>,
+}
+
+impl<S: Submitter> MailslotClient<S> {
+    /// Open the local mailslot `name` for writing.
+    pub fn open<R>(registrar: &R, name: &str) -> Result<Self, SetupError>
+    where
+        R: Registrar<Io = S>,
+    {
+        Self::open_target(registrar, &MailslotTarget::Local, name)
+    }
+
+    /// Open the mailslot `name` at `target` for writing.
+    pub fn open_target<R>(
+        registrar: &R,
+        target: &MailslotTarget,
+        name: &str,
+    ) -> Result<Self, SetupError>
+    where
+        R: Registrar<Io = S>,
+    {
+        let path = mailslot_path(target, name)?;
+
+        // SAFETY: the path was validated and composed without interior NULs,
+        // and every other argument is a plain value.
+        let raw = unsafe {
+            CreateFileW(
+                &path,
+                FILE_GENERIC_WRITE.0,
+                FILE_SHARE_READ,
+                None,
+                OPEN_EXISTING,
+                FILE_FLAG_OVERLAPPED,
+                None,
+            )
+        }
+        .map_err(SetupError::from_windows)?;
+
+        // SAFETY: `CreateFileW` returned a newly owned handle, and ownership of
+        // closing it transfers into `Handle`.
+        let handle = unsafe { Handle::from_raw(raw) };
+        match registrar.register(handle.raw()) {
+            Ok(submitter) => Ok(MailslotClient {
+                inner: Some(Inner::new(handle, submitter)),
+            }),
+            Err(e) => {
+                drop(handle);
+                Err(SetupError::from(e))
+            }
+        }
+    }
+
+    /// The underlying kernel handle, borrowed for interoperability.
+    ///
+    /// Ownership is not transferred, and operations built from it must not
+    /// outlive the `MailslotClient`.
+    pub fn handle(&self) -> HANDLE {
+        self.state().handle.raw()
+    }
+
+    /// Send `buffer`'s initialised bytes as one message.
+    ///
+    /// If the returned future is dropped before resolving, cancellation is
+    /// requested and the buffer is not returned.
+    pub fn write<B>(&self, buffer: B) -> impl Future<Output = OpResult<usize, B>>
+    where
+        B: IoBuf + Send,
+    {
+        let state = self.state();
+        let submitted = state
+            .submitter
+            .submit(WriteHandle::new(state.handle.clone(), buffer));
+        async move {
+            let OpResult(result, op) = submitted.await;
+            OpResult(result, op.into_inner())
+        }
+    }
+
+    fn state(&self) -> &Inner<S> {
+        self.inner.as_ref().expect("mailslot state is present")
+    }
+}
+
+impl<S: Submitter> std::fmt::Debug for MailslotClient<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MailslotClient")
+            .field(
+                "handle",
+                &self.inner.as_ref().map(|inner| inner.handle.raw().0),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: Submitter> Drop for MailslotClient<S> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            drop_inner(inner);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Asynchronous mailslots.
+//!
+//! A mailslot is a one-way, message-oriented inbox. [`MailslotOptions`]
+//! creates the reading end, a [`MailslotServer`], and any number of
+//! [`MailslotClient`]s open it by name and write messages into it. Both ends
+//! are registered with one of [`crate::iocp`]'s completion backends, so reads
+//! and writes are overlapped and own their buffers until completion, just as
+//! on a [`NamedPipe`](crate::pipe::NamedPipe).
+//!
+//! # Messages
+//!
+//! Every read returns exactly one message. A message that does not fit the
+//! buffer is not truncated: the read fails with `ERROR_INSUFFICIENT_BUFFER`
+//! and the message stays queued. [`MailslotServer::next_message_size`] reports
+//! how large the next one is, and [`MailslotServer::read_message`] waits for a
+//! message and returns all of it whatever its size.
+//!
+//! # Targets
+//!
+//! Names are composed into `\\.\mailslot\...` paths by the builders; a name
+//! may contain `\` for pseudo-directories. A client can also address a
+//! mailslot on another computer, or broadcast to every mailslot of a name in
+//! a domain, with [`MailslotTarget`]. Broadcasts are unacknowledged
+//! datagrams: writing succeeds whether or not anyone receives them, and
+//! messages over 424 bytes are dropped.
+//!
+//! # Timeouts
+//!
+//! Reads wait for a message indefinitely. Dropping a read future requests
+//! cancellation, so a deadline belongs to the caller, for example racing the
+//! read against [`crate::time::sleep`].
+//!
+//! ```no_run
+//! # use winasio::iocp::ThreadPool;
+//! # use winasio::mailslot::{MailslotClient, MailslotOptions};
+//! # async fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! let server = MailslotOptions::new(r"agents\status").create(&ThreadPool)?;
+//! let client = MailslotClient::open(&ThreadPool, r"agents\status")?;
+//!
+//! let winasio::iocp::OpResult(written, _) = client.write(b"up".to_vec()).await;
+//! written?;
+//! assert_eq!(server.read_message().await?, b"up");
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod name;
+mod server;
+
+pub use crate::fs::SetupError;
+pub use client::MailslotClient;
+pub use name::{MailslotTarget, MAX_NAME_LEN};
+pub use server::{MailslotOptions, MailslotServer};
@@ -0,0 +1,139 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Mailslot name validation and path composition.
+
+use windows::core::HSTRING;
+
+use crate::fs::SetupError;
+
+const LOCAL_MAILSLOT_PREFIX: &str = r"\\.\mailslot\";
+
+/// Maximum accepted mailslot name length, pseudo-directories included.
+pub const MAX_NAME_LEN: usize = 256 - LOCAL_MAILSLOT_PREFIX.len();
+
+/// Where a [`MailslotClient`](super::MailslotClient) delivers its messages.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum MailslotTarget {
+    /// The mailslot of that name on this computer.
+    #[default]
+    Local,
+    /// The mailslot of that name on the named computer.
+    Computer(String),
+    /// Every mailslot of that name in the named domain.
+    Domain(String),
+    /// Every mailslot of that name in this computer's primary domain.
+    PrimaryDomain,
+}
+
+/// `\\.\mailslot\name`, for creating a mailslot.
+pub(crate) fn local_mailslot_path(name: &str) -> Result<HSTRING, SetupError> {
+    mailslot_path(&MailslotTarget::Local, name)
+}
+
+/// The path a client opens to reach `name` at `target`.
+pub(crate) fn mailslot_path(target: &MailslotTarget, name: &str) -> Result<HSTRING, SetupError> {
+    validate_name(name)?;
+    let host = match target {
+        MailslotTarget::Local => ".",
+        MailslotTarget::Computer(host) | MailslotTarget::Domain(host) => {
+            validate_host(host)?;
+            host
+        }
+        MailslotTarget::PrimaryDomain => "*",
+    };
+    Ok(HSTRING::from(format!(r"\\{host}\mailslot\{name}")))
+}
+
+/// A name may contain `\` to form pseudo-directories, but every component
+/// must be non-empty.
+fn validate_name(name: &str) -> Result<(), SetupError> {
+    if name.is_empty()
+        || name.len() > MAX_NAME_LEN
+        || name.contains('/')
+        || name.as_bytes().contains(&0)
+        || name.split('\\').any(str::is_empty)
+    {
+        Err(SetupError::InvalidName)
+    } else {
+        Ok(())
+    }
+}
+
+/// Computer and domain names are single components; `.` and `*` are spelled
+/// with [`MailslotTarget::Local`] and [`MailslotTarget::PrimaryDomain`].
+fn validate_host(host: &str) -> Result<(), SetupError> {
+    if host.is_empty()
+        || host == "."
+        || host == "*"
+        || host.contains(['\\', '/'])
+        || host.as_bytes().contains(&0)
+    {
+        Err(SetupError::InvalidName)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composes_paths_for_every_target() {
+        let cases = [
+            (MailslotTarget::Local, r"\\.\mailslot\status"),
+            (
+                MailslotTarget::Computer("agent01".into()),
+                r"\\agent01\mailslot\status",
+            ),
+            (
+                MailslotTarget::Domain("CORP".into()),
+                r"\\CORP\mailslot\status",
+            ),
+            (MailslotTarget::PrimaryDomain, r"\\*\mailslot\status"),
+        ];
+        for (target, expected) in cases {
+            assert_eq!(
+                mailslot_path(&target, "status").unwrap(),
+                HSTRING::from(expected)
+            );
+        }
+        assert_eq!(
+            local_mailslot_path(r"agents\status").unwrap(),
+            HSTRING::from(r"\\.\mailslot\agents\status")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_names_and_hosts() {
+        for name in [
+            "",
+            "has/slash",
+            "nul\0inside",
+            r"\leading",
+            r"trailing\",
+            r"double\\sep",
+        ] {
+            assert!(matches!(
+                local_mailslot_path(name),
+                Err(SetupError::InvalidName)
+            ));
+        }
+        let too_long = "x".repeat(MAX_NAME_LEN + 1);
+        assert!(matches!(
+            local_mailslot_path(&too_long),
+            Err(SetupError::InvalidName)
+        ));
+
+        for host in ["", ".", "*", r"a\b", "a/b", "nul\0"] {
+            assert!(matches!(
+                mailslot_path(&MailslotTarget::Computer(host.into()), "status"),
+                Err(SetupError::InvalidName)
+            ));
+        }
+    }
+}
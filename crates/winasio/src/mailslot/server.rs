@@ -0,0 +1,231 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Mailslot server builder and reading end.
+
+use std::future::Future;
+
+use windows::core::Result;
+use windows::Win32::Foundation::{ERROR_INSUFFICIENT_BUFFER, HANDLE};
+use windows::Win32::System::Mailslots::{CreateMailslotW, GetMailslotInfo};
+
+use crate::fs::security::SecurityDescriptor;
+use crate::fs::SetupError;
+use crate::iocp::{Handle, IntoInner, IoBufMut, OpResult, ReadHandle, Registrar, Submitter};
+use crate::pipe::{drop_inner, Inner};
+
+use super::name::local_mailslot_path;
+
+/// `MAILSLOT_WAIT_FOREVER`: reads pend until a message arrives.
+const MAILSLOT_WAIT_FOREVER: u32 = u32::MAX;
+
+/// `MAILSLOT_NO_MESSAGE`: `GetMailslotInfo`'s next size when none is queued.
+const MAILSLOT_NO_MESSAGE: u32 = u32::MAX;
+
+/// Initial buffer for [`MailslotServer::read_message`] when nothing is queued
+/// yet.
+const MESSAGE_CHUNK: usize = 4096;
+
+/// Builder for creating a mailslot.
+#[derive(Debug, Clone)]
+pub struct MailslotOptions {
+    name: String,
+    max_message_size: u32,
+    security_descriptor: Option<String>,
+}
+
+impl MailslotOptions {
+    /// Create options for the mailslot `name`.
+    ///
+    /// The name may contain `\` to place the mailslot in a pseudo-directory,
+    /// as in `agents\status`.
+    pub fn new(name: impl Into<String>) -> Self {
+        MailslotOptions {
+            name: name.into(),
+            max_message_size: 0,
+            security_descriptor: None,
+        }
+    }
+
+    /// Replace the mailslot name.
+    pub fn name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Set the largest message writers may send. Zero, the default, accepts
+    /// any size.
+    pub fn max_message_size(&mut self, size: u32) -> &mut Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Apply a security descriptor, written as SDDL, to the mailslot.
+    ///
+    /// The DACL decides who may open the mailslot for writing. A string
+    /// Windows rejects is reported as [`SetupError::InvalidSecurityDescriptor`]
+    /// when [`MailslotOptions::create`] runs.
+    pub fn security_descriptor_sddl(&mut self, sddl: impl Into<String>) -> &mut Self {
+        self.security_descriptor = Some(sddl.into());
+        self
+    }
+
+    /// Create and register the mailslot.
+    ///
+    /// Only one server can hold a name; creating it again while it exists
+    /// fails with `ERROR_ALREADY_EXISTS`.
+    pub fn create<R: Registrar>(
+        &self,
+        registrar: &R,
+    ) -> std::result::Result<MailslotServer<R::Io>, SetupError> {
+        let name = local_mailslot_path(&self.name)?;
+        let descriptor = self
+            .security_descriptor
+            .as_deref()
+            .map(SecurityDescriptor::from_sddl)
+            .transpose()?;
+        let attributes = descriptor.as_ref().map(|d| d.attributes(false));
+
+        // SAFETY: the name was validated and composed as a local, NUL-free
+        // mailslot path. The security attributes and the descriptor they point
+        // to outlive the call.
+        let raw = unsafe {
+            CreateMailslotW(
+                &name,
+                self.max_message_size,
+                MAILSLOT_WAIT_FOREVER,
+                attributes.as_ref().map(|a| a as *const _),
+            )
+        }
+        .map_err(SetupError::from_windows)?;
+        drop(descriptor);
+
+        // SAFETY: `CreateMailslotW` returned a newly owned handle, and
+        // ownership of closing it transfers into `Handle`.
+        let handle = unsafe { Handle::from_raw(raw) };
+        match registrar.register(handle.raw()) {
+            Ok(submitter) => Ok(MailslotServer {
+                inner: Some(Inner::new(handle, submitter)),
+            }),
+            Err(e) => {
+                drop(handle);
+                Err(SetupError::from(e))
+            }
+        }
+    }
+}
+
+/// The reading end of a mailslot.
+///
+/// Every read returns one whole message. Messages stay queued until read;
+/// the mailslot and everything still queued in it go away when this is
+/// dropped.
+pub struct MailslotServer<S: Submitter> {
+    inner: Option<Inner<S>>,
+}
+
+impl<S: Submitter> MailslotServer<S> {
+    /// The underlying kernel handle, borrowed for interoperability.
+    ///
+    /// Ownership is not transferred, and operations built from it must not
+    /// outlive the `MailslotServer`.
+    pub fn handle(&self) -> HANDLE {
+        self.state().handle.raw()
+    }
+
+    /// Start reading the next message into `buffer`, resolving to its length.
+    ///
+    /// The read waits until a message arrives. A message larger than the
+    /// buffer's capacity fails with `ERROR_INSUFFICIENT_BUFFER` and stays
+    /// queued; [`MailslotServer::next_message_size`] reports the size to
+    /// allocate, and [`MailslotServer::read_message`] does both.
+    ///
+    /// If the returned future is dropped before resolving, cancellation is
+    /// requested and the buffer is not returned.
+    pub fn read<B>(&self, buffer: B) -> impl Future<Output = OpResult<usize, B>>
+    where
+        B: IoBufMut + Send,
+    {
+        let state = self.state();
+        let submitted = state
+            .submitter
+            .submit(ReadHandle::new(state.handle.clone(), buffer));
+        async move {
+            let OpResult(result, op) = submitted.await;
+            OpResult(result, op.into_inner())
+        }
+    }
+
+    /// Wait for the next message and return all of it.
+    ///
+    /// The buffer is sized from [`MailslotServer::next_message_size`], and
+    /// grown and retried if a larger message gets there first.
+    ///
+    /// If the returned future is dropped before resolving, cancellation is
+    /// requested, and a message the cancelled read had already taken is lost.
+    pub fn read_message(&self) -> impl Future<Output = Result<Vec<u8>>> + '_ {
+        read_message(self)
+    }
+
+    /// The size of the next queued message, or `None` if nothing is queued.
+    pub fn next_message_size(&self) -> Result<Option<usize>> {
+        let mut next = 0u32;
+        // SAFETY: the handle is live while `self` is, and the out pointer is
+        // valid for the call.
+        unsafe { GetMailslotInfo(self.handle(), None, Some(&mut next), None, None) }?;
+        Ok((next != MAILSLOT_NO_MESSAGE).then_some(next as usize))
+    }
+
+    /// The number of messages queued.
+    pub fn message_count(&self) -> Result<u32> {
+        let mut count = 0u32;
+        // SAFETY: the handle is live while `self` is, and the out pointer is
+        // valid for the call.
+        unsafe { GetMailslotInfo(self.handle(), None, None, Some(&mut count), None) }?;
+        Ok(count)
+    }
+
+    fn state(&self) -> &Inner<S> {
+        self.inner.as_ref().expect("mailslot state is present")
+    }
+}
+
+async fn read_message<S: Submitter>(server: &MailslotServer<S>) -> Result<Vec<u8>> {
+    let mut buffer = Vec::with_capacity(MESSAGE_CHUNK);
+    loop {
+        if let Some(len) = server.next_message_size()? {
+            buffer.reserve_exact(len);
+        }
+        let OpResult(result, returned) = server.read(buffer).await;
+        buffer = returned;
+        match result {
+            Ok(_) => return Ok(buffer),
+            // The message arrived after the size check and does not fit. It is
+            // still queued, so size the buffer for it and read again.
+            Err(e) if e.code() == ERROR_INSUFFICIENT_BUFFER.to_hresult() => buffer.clear(),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+impl<S: Submitter> std::fmt::Debug for MailslotServer<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MailslotServer")
+            .field(
+                "handle",
+                &self.inner.as_ref().map(|inner| inner.handle.raw().0),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: Submitter> Drop for MailslotServer<S> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            drop_inner(inner);
+        }
+    }
+}
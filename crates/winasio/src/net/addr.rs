@@ -73,11 +73,7 @@ impl SockAddrBytes {
                     sin_family: AF_INET,
                     // Winsock wants the port in network order.
                     sin_port: v4.port().to_be(),
-                    sin_addr: IN_ADDR {
-                        S_un: IN_ADDR_0 {
-                            S_un_b: octets_to_in_addr(v4.ip().octets()),
-                        },
-                    },
+                    sin_addr: in_addr(*v4.ip()),
                     sin_zero: [0; 8],
                 };
                 // SAFETY: `SOCKADDR_STORAGE` is at least as large as, and at
@@ -95,11 +91,7 @@ impl SockAddrBytes {
                     sin6_family: AF_INET6,
                     sin6_port: v6.port().to_be(),
                     sin6_flowinfo: v6.flowinfo().to_be(),
-                    sin6_addr: IN6_ADDR {
-                        u: IN6_ADDR_0 {
-                            Byte: v6.ip().octets(),
-                        },
-                    },
+                    sin6_addr: in6_addr(v6.ip()),
                     Anonymous: SOCKADDR_IN6_0 {
                         sin6_scope_id: v6.scope_id(),
                     },
@@ -314,6 +306,24 @@ fn decode_unix(storage: &SOCKADDR_STORAGE, reported: i32) -> UnixSocketAddr {
     UnixSocketAddr::from_sun_path(&slot, available)
 }
 
+/// An IPv4 address as Winsock's `IN_ADDR`, for option structures.
+pub(crate) fn in_addr(addr: Ipv4Addr) -> IN_ADDR {
+    IN_ADDR {
+        S_un: IN_ADDR_0 {
+            S_un_b: octets_to_in_addr(addr.octets()),
+        },
+    }
+}
+
+/// An IPv6 address as Winsock's `IN6_ADDR`, for option structures.
+pub(crate) fn in6_addr(addr: &Ipv6Addr) -> IN6_ADDR {
+    IN6_ADDR {
+        u: IN6_ADDR_0 {
+            Byte: addr.octets(),
+        },
+    }
+}
+
 fn octets_to_in_addr(o: [u8; 4]) -> windows::Win32::Networking::WinSock::IN_ADDR_0_0 {
     windows::Win32::Networking::WinSock::IN_ADDR_0_0 {
         s_b1: o[0],
@@ -0,0 +1,342 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Decoding the control buffer `WSARecvMsg` fills in.
+//!
+//! The buffer is a run of `WSACMSGHDR` records, each a header — a
+//! pointer-sized length, then a level and a type as 32-bit integers — followed
+//! by its data, with both header and data aligned to the pointer size. The
+//! `WSA_CMSG_*` macros that walk it in C have no binding, so the walk is done
+//! here, on bytes, without casting the buffer to any structure.
+//!
+//! That makes the decoder pure: it reads a slice and a word size and nothing
+//! else, so the unit tests below cover both the 64-bit and the 32-bit layout,
+//! and malformed records, on any host. Windows is little-endian on every
+//! architecture it runs on, so every field is read as little-endian rather
+//! than in the host's order.
+//!
+//! A record this crate did not ask for is skipped. A record whose length is
+//! shorter than its header, or runs past the end of the buffer, ends the walk:
+//! nothing after it can be located.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use windows::Win32::Networking::WinSock::{
+    IPPROTO_IP, IPPROTO_IPV6, IPV6_ECN, IPV6_HOPLIMIT, IPV6_PKTINFO, IP_ECN, IP_PKTINFO, IP_TTL,
+};
+
+/// The explicit congestion notification codepoint of a received packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ecn {
+    /// `00`: the sender does not support ECN.
+    NotEct,
+    /// `01`: ECN-capable transport, codepoint 1.
+    Ect1,
+    /// `10`: ECN-capable transport, codepoint 0.
+    Ect0,
+    /// `11`: congestion experienced.
+    Ce,
+}
+
+impl Ecn {
+    /// The codepoint in the low two bits of `bits`.
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => Ecn::NotEct,
+            0b01 => Ecn::Ect1,
+            0b10 => Ecn::Ect0,
+            _ => Ecn::Ce,
+        }
+    }
+}
+
+/// What [`super::UdpSocket::recv_msg`] learned about one datagram.
+///
+/// The packet information, hop limit and ECN fields are present only when the
+/// socket was asked to report them and the stack supplied them; see
+/// [`super::UdpSocket::set_recv_packet_info`] and its neighbours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvMeta {
+    len: usize,
+    source: SocketAddr,
+    control: ControlMessages,
+}
+
+impl RecvMeta {
+    pub(crate) fn new(len: usize, source: SocketAddr, control: ControlMessages) -> Self {
+        RecvMeta {
+            len,
+            source,
+            control,
+        }
+    }
+
+    /// The datagram's length.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the datagram was empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The address the datagram came from.
+    pub fn source(&self) -> SocketAddr {
+        self.source
+    }
+
+    /// The local address the datagram was sent to.
+    ///
+    /// For a socket bound to the wildcard this is the one way to learn which
+    /// local address a query arrived on, and so which to answer from. For a
+    /// multicast datagram it is the group.
+    pub fn destination(&self) -> Option<IpAddr> {
+        self.control.packet_info.map(|info| info.destination)
+    }
+
+    /// The index of the interface the datagram arrived on.
+    pub fn interface_index(&self) -> Option<u32> {
+        self.control.packet_info.map(|info| info.interface_index)
+    }
+
+    /// The IPv4 TTL or IPv6 hop limit the datagram arrived with.
+    pub fn hop_limit(&self) -> Option<u8> {
+        self.control.hop_limit
+    }
+
+    /// The ECN codepoint the datagram arrived with.
+    pub fn ecn(&self) -> Option<Ecn> {
+        self.control.ecn
+    }
+}
+
+/// `IP_PKTINFO` / `IPV6_PKTINFO`, decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PacketInfo {
+    pub(crate) destination: IpAddr,
+    pub(crate) interface_index: u32,
+}
+
+/// Everything the decoder recognised in one control buffer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ControlMessages {
+    pub(crate) packet_info: Option<PacketInfo>,
+    pub(crate) hop_limit: Option<u8>,
+    pub(crate) ecn: Option<Ecn>,
+}
+
+/// The pointer size of the platform this crate is built for.
+pub(crate) const NATIVE_WORD: usize = std::mem::size_of::<usize>();
+
+/// Decode a control buffer laid out for a platform of the given pointer size.
+pub(crate) fn decode(buffer: &[u8], word: usize) -> ControlMessages {
+    let header = header_len(word);
+    let data_offset = align(header, word);
+    let mut found = ControlMessages::default();
+    let mut at = 0;
+
+    while buffer.len().saturating_sub(at) >= header {
+        let record = &buffer[at..];
+        let Some(len) = read_word(record, word) else {
+            break;
+        };
+        // A zero length is the unused, zero-filled tail of the buffer.
+        if len < data_offset || len > record.len() {
+            break;
+        }
+        let level = read_i32(&record[word..]);
+        let kind = read_i32(&record[word + 4..]);
+        apply(&mut found, level, kind, &record[data_offset..len]);
+
+        match at.checked_add(align(len, word)) {
+            Some(next) => at = next,
+            None => break,
+        }
+    }
+    found
+}
+
+fn apply(found: &mut ControlMessages, level: i32, kind: i32, data: &[u8]) {
+    match (level, kind) {
+        (l, IP_PKTINFO) if l == IPPROTO_IP.0 => {
+            // `IN_PKTINFO`: a 4-byte address, then a 32-bit interface index.
+            if let (Some(addr), Some(index)) = (data.get(..4), data.get(4..8)) {
+                let addr: [u8; 4] = addr.try_into().expect("four bytes");
+                found.packet_info = Some(PacketInfo {
+                    destination: IpAddr::V4(Ipv4Addr::from(addr)),
+                    interface_index: read_u32(index),
+                });
+            }
+        }
+        (l, IPV6_PKTINFO) if l == IPPROTO_IPV6.0 => {
+            // `IN6_PKTINFO`: a 16-byte address, then a 32-bit interface index.
+            if let (Some(addr), Some(index)) = (data.get(..16), data.get(16..20)) {
+                let addr: [u8; 16] = addr.try_into().expect("sixteen bytes");
+                found.packet_info = Some(PacketInfo {
+                    destination: IpAddr::V6(Ipv6Addr::from(addr)),
+                    interface_index: read_u32(index),
+                });
+            }
+        }
+        (l, IP_TTL) if l == IPPROTO_IP.0 => found.hop_limit = int_value(data),
+        (l, IPV6_HOPLIMIT) if l == IPPROTO_IPV6.0 => found.hop_limit = int_value(data),
+        (l, IP_ECN) if l == IPPROTO_IP.0 => found.ecn = int_value(data).map(Ecn::from_bits),
+        (l, IPV6_ECN) if l == IPPROTO_IPV6.0 => found.ecn = int_value(data).map(Ecn::from_bits),
+        _ => {}
+    }
+}
+
+/// A header's size before padding: the length word, then level and type.
+fn header_len(word: usize) -> usize {
+    word + 8
+}
+
+fn align(len: usize, word: usize) -> usize {
+    len.div_ceil(word) * word
+}
+
+/// A record whose value is an `INT`, narrowed to the byte it carries.
+fn int_value(data: &[u8]) -> Option<u8> {
+    data.get(..4).map(|value| read_u32(value) as u8)
+}
+
+fn read_word(bytes: &[u8], word: usize) -> Option<usize> {
+    match word {
+        4 => Some(read_u32(bytes) as usize),
+        8 => usize::try_from(u64::from_le_bytes(bytes[..8].try_into().ok()?)).ok(),
+        _ => None,
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().expect("four bytes"))
+}
+
+fn read_i32(bytes: &[u8]) -> i32 {
+    i32::from_le_bytes(bytes[..4].try_into().expect("four bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lay out one record the way `WSA_CMSG_SPACE` does.
+    fn record(word: usize, level: i32, kind: i32, data: &[u8]) -> Vec<u8> {
+        let data_offset = align(header_len(word), word);
+        let len = data_offset + data.len();
+        let mut out = vec![0u8; align(len, word)];
+        out[..word].copy_from_slice(&(len as u64).to_le_bytes()[..word]);
+        out[word..word + 4].copy_from_slice(&level.to_le_bytes());
+        out[word + 4..word + 8].copy_from_slice(&kind.to_le_bytes());
+        out[data_offset..len].copy_from_slice(data);
+        out
+    }
+
+    fn v4_pktinfo(word: usize, addr: [u8; 4], index: u32) -> Vec<u8> {
+        let mut data = addr.to_vec();
+        data.extend_from_slice(&index.to_le_bytes());
+        record(word, IPPROTO_IP.0, IP_PKTINFO, &data)
+    }
+
+    #[test]
+    fn an_empty_or_zeroed_buffer_decodes_to_nothing() {
+        assert_eq!(decode(&[], 8), ControlMessages::default());
+        assert_eq!(decode(&[0; 64], 8), ControlMessages::default());
+    }
+
+    #[test]
+    fn every_recognised_record_is_decoded_on_both_word_sizes() {
+        for word in [4, 8] {
+            let mut buffer = v4_pktinfo(word, [192, 168, 1, 7], 12);
+            buffer.extend(record(word, IPPROTO_IP.0, IP_TTL, &64i32.to_le_bytes()));
+            buffer.extend(record(word, IPPROTO_IP.0, IP_ECN, &3i32.to_le_bytes()));
+
+            let found = decode(&buffer, word);
+            assert_eq!(
+                found.packet_info,
+                Some(PacketInfo {
+                    destination: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 7)),
+                    interface_index: 12,
+                }),
+                "word size {word}"
+            );
+            assert_eq!(found.hop_limit, Some(64));
+            assert_eq!(found.ecn, Some(Ecn::Ce));
+        }
+    }
+
+    #[test]
+    fn ipv6_records_are_decoded() {
+        let addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+        let mut data = addr.octets().to_vec();
+        data.extend_from_slice(&3u32.to_le_bytes());
+        let mut buffer = record(8, IPPROTO_IPV6.0, IPV6_PKTINFO, &data);
+        buffer.extend(record(
+            8,
+            IPPROTO_IPV6.0,
+            IPV6_HOPLIMIT,
+            &255i32.to_le_bytes(),
+        ));
+        buffer.extend(record(8, IPPROTO_IPV6.0, IPV6_ECN, &2i32.to_le_bytes()));
+
+        let found = decode(&buffer, 8);
+        assert_eq!(
+            found.packet_info,
+            Some(PacketInfo {
+                destination: IpAddr::V6(addr),
+                interface_index: 3,
+            })
+        );
+        assert_eq!(found.hop_limit, Some(255));
+        assert_eq!(found.ecn, Some(Ecn::Ect0));
+    }
+
+    #[test]
+    fn unknown_records_are_skipped() {
+        let mut buffer = record(8, 0xffff, 99, &[1, 2, 3]);
+        buffer.extend(v4_pktinfo(8, [10, 0, 0, 1], 4));
+        assert_eq!(
+            decode(&buffer, 8)
+                .packet_info
+                .map(|info| info.interface_index),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn a_record_with_a_bad_length_ends_the_walk() {
+        // Longer than the buffer.
+        let mut overlong = v4_pktinfo(8, [10, 0, 0, 1], 4);
+        overlong[0] = 200;
+        assert_eq!(decode(&overlong, 8), ControlMessages::default());
+
+        // Shorter than its own header.
+        let mut short = v4_pktinfo(8, [10, 0, 0, 1], 4);
+        short[0] = 4;
+        short.extend(record(8, IPPROTO_IP.0, IP_TTL, &1i32.to_le_bytes()));
+        assert_eq!(decode(&short, 8), ControlMessages::default());
+
+        // A record cut off mid-header keeps what came before it.
+        let mut cut = v4_pktinfo(8, [10, 0, 0, 1], 4);
+        cut.extend_from_slice(&[24, 0, 0]);
+        assert!(decode(&cut, 8).packet_info.is_some());
+    }
+
+    #[test]
+    fn a_truncated_value_is_ignored_rather_than_misread() {
+        let buffer = record(8, IPPROTO_IP.0, IP_PKTINFO, &[127, 0, 0, 1]);
+        assert_eq!(decode(&buffer, 8).packet_info, None);
+    }
+
+    #[test]
+    fn ecn_codepoints_come_from_the_low_two_bits() {
+        assert_eq!(Ecn::from_bits(0b00), Ecn::NotEct);
+        assert_eq!(Ecn::from_bits(0b01), Ecn::Ect1);
+        assert_eq!(Ecn::from_bits(0b10), Ecn::Ect0);
+        assert_eq!(Ecn::from_bits(0b1111_1111), Ecn::Ce);
+    }
+}
@@ -0,0 +1,171 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Closing a stream so the peer can tell "done" from "gone".
+//!
+//! The recipe is short — shut down writes, read until the peer closes, then
+//! close — and the drain loop is where it goes wrong: a loop that stops on the
+//! first zero-byte read, or treats a reset as the end, or never gives up on a
+//! peer that keeps sending. [`close_gracefully`] is that loop, written once
+//! for both stream types, with the two ways out that a caller needs bounded:
+//! a deadline, and a cap on how much is read and thrown away. Either one
+//! ends in an abortive close, and [`CloseOutcome`] says which.
+//!
+//! The abortive close is `SO_LINGER {1, 0}` followed by the stream's drop,
+//! which sends RST rather than FIN. That is deliberate: a peer still sending
+//! past the deadline has not read the FIN either, and a plain close would
+//! leave the connection half-open on its side.
+
+use std::future::{poll_fn, Future};
+use std::net::Shutdown;
+use std::pin::{pin, Pin};
+use std::task::Poll;
+use std::time::Duration;
+
+use crate::fs::ReadOutcome;
+use crate::io::WholePayloadIo;
+use crate::iocp::OpResult;
+use crate::time::sleep;
+
+use super::error::SocketError;
+use super::socket::Socket;
+
+/// The most [`close_gracefully`](super::TcpStream::close_gracefully) reads
+/// and discards before giving up on the peer. Pass another limit to
+/// [`close_gracefully_with`](super::TcpStream::close_gracefully_with).
+pub const DEFAULT_DRAIN_LIMIT: u64 = 256 * 1024;
+
+/// The size of each drain read.
+const DRAIN_CHUNK: usize = 16 * 1024;
+
+/// How a [`close_gracefully`](super::TcpStream::close_gracefully) ended.
+///
+/// Each variant carries how many bytes the peer sent after the shutdown,
+/// which were read and discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CloseOutcome {
+    /// The peer closed its side in time. The connection ended in order on
+    /// both sides.
+    Graceful {
+        /// Bytes read and discarded before the peer closed.
+        discarded: u64,
+    },
+    /// The timeout passed before the peer closed, and the connection was
+    /// reset.
+    TimedOut {
+        /// Bytes read and discarded before the deadline.
+        discarded: u64,
+    },
+    /// The peer sent more than the drain limit, and the connection was reset.
+    DrainLimit {
+        /// Bytes read and discarded, a little over the limit.
+        discarded: u64,
+    },
+}
+
+impl CloseOutcome {
+    /// Whether the connection ended in order rather than being reset.
+    pub fn is_graceful(&self) -> bool {
+        matches!(self, CloseOutcome::Graceful { .. })
+    }
+
+    /// Bytes the peer sent after the shutdown, read and discarded.
+    pub fn discarded(&self) -> u64 {
+        match *self {
+            CloseOutcome::Graceful { discarded }
+            | CloseOutcome::TimedOut { discarded }
+            | CloseOutcome::DrainLimit { discarded } => discarded,
+        }
+    }
+}
+
+/// Shut down writes on `io`, drain it until the peer closes, and arrange an
+/// abortive close if that does not happen within `timeout` or `drain_limit`
+/// bytes.
+///
+/// `socket` is `io`'s own. The caller drops `io` afterwards, which is the
+/// close itself, and clears `io`'s read timeout beforehand: `timeout` is the
+/// only deadline on the drain.
+pub(crate) async fn close_gracefully<T: WholePayloadIo>(
+    io: &T,
+    socket: &Socket,
+    timeout: Duration,
+    drain_limit: u64,
+) -> Result<CloseOutcome, SocketError> {
+    socket
+        .shutdown_dir(Shutdown::Write)
+        .map_err(SocketError::from_win32)?;
+
+    let mut timer = sleep(timeout);
+    let mut buffer = Vec::with_capacity(DRAIN_CHUNK);
+    let mut discarded = 0u64;
+    loop {
+        let mut read = pin!(io.read_once(0, buffer));
+        let step = poll_fn(|cx| {
+            if let Poll::Ready(done) = read.as_mut().poll(cx) {
+                return Poll::Ready(Some(done));
+            }
+            if Pin::new(&mut timer).poll(cx).is_ready() {
+                return Poll::Ready(None);
+            }
+            Poll::Pending
+        })
+        .await;
+        // Out of time. Dropping the read cancels it; it has nothing worth
+        // keeping.
+        let Some(OpResult(result, returned)) = step else {
+            reset_on_close(socket);
+            return Ok(CloseOutcome::TimedOut { discarded });
+        };
+        buffer = returned;
+
+        match result {
+            Ok(ReadOutcome::ClosedPeer | ReadOutcome::Eof) => {
+                return Ok(CloseOutcome::Graceful { discarded });
+            }
+            Ok(ReadOutcome::Bytes(n) | ReadOutcome::MoreData(n)) => {
+                discarded += n as u64;
+                if discarded > drain_limit {
+                    reset_on_close(socket);
+                    return Ok(CloseOutcome::DrainLimit { discarded });
+                }
+            }
+            // Reset, timed out at the connection level, or otherwise broken:
+            // there is nothing left to close gracefully, and the caller should
+            // know the peer did not finish in order. The drain reads carry no
+            // read timeout of their own, so a timeout here is never one to
+            // retry.
+            Err(e) => return Err(SocketError::from_win32(e)),
+        }
+    }
+}
+
+/// Make the coming close send RST: `SO_LINGER` on, with a zero timeout.
+///
+/// A failure is ignored. The close still happens; it is merely the ordinary
+/// one, and the outcome already reports that the peer did not finish.
+fn reset_on_close(socket: &Socket) {
+    let _ = socket.set_linger(Some(Duration::ZERO));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_outcome_reports_what_it_discarded() {
+        let outcomes = [
+            CloseOutcome::Graceful { discarded: 1 },
+            CloseOutcome::TimedOut { discarded: 2 },
+            CloseOutcome::DrainLimit { discarded: 3 },
+        ];
+        let discarded: Vec<_> = outcomes.iter().map(CloseOutcome::discarded).collect();
+        assert_eq!(discarded, [1, 2, 3]);
+        let graceful: Vec<_> = outcomes.iter().map(CloseOutcome::is_graceful).collect();
+        assert_eq!(graceful, [true, false, false]);
+    }
+}
@@ -0,0 +1,263 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Deadlines on connect, accept, read and write.
+//!
+//! Dropping an operation future is already a way to give up on it, but not a
+//! good one for a timeout: the buffer is lost, and an accept that completed
+//! in the same instant loses the connection with it. [`within`] instead
+//! cancels the operation *in place* when the deadline passes and goes on
+//! awaiting it, so the state comes back exactly as it would have without a
+//! deadline. If the completion turns out to be the cancellation, it is
+//! reported as `WSAETIMEDOUT` — [`SocketError::TimedOut`] once classified —
+//! rather than as `ERROR_OPERATION_ABORTED`, which a caller would read as
+//! "someone cancelled me". If the operation beat the cancellation, its real
+//! result stands.
+//!
+//! [`swept_if_silent`] is the same idea for an accept that waits for the
+//! client's first bytes, where what runs out is not the caller's patience but
+//! a connected client's: the completed connection's `SO_CONNECT_TIME` is
+//! checked once a second, and the accept is cancelled in place once it has
+//! been connected and silent for longer than the limit.
+//!
+//! Each deadline is a [`crate::time`] timer, so a loop driving a
+//! [`Proactor`](crate::iocp::Proactor) must poll with a timeout for it to be
+//! noticed; see that module.
+
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::{Mutex, PoisonError};
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+use windows::core::{Error, HRESULT};
+use windows::Win32::Foundation::{ERROR_INVALID_PARAMETER, ERROR_OPERATION_ABORTED};
+use windows::Win32::Networking::WinSock::{SOL_SOCKET, SO_CONNECT_TIME, WSAETIMEDOUT};
+
+use crate::iocp::{OpCode, OpResult, Submit};
+use crate::time::sleep;
+
+use super::error::SocketError;
+use super::socket::Socket;
+
+/// Await `submitted`, cancelling it in place if `limit` passes first.
+///
+/// `None` is no deadline at all, and costs nothing over a plain `.await`. So
+/// is a limit too long for an `Instant` to reach, such as [`Duration::MAX`],
+/// which is how callers usually spell "no timeout".
+pub(crate) async fn within<T: OpCode>(
+    mut submitted: Submit<T>,
+    limit: Option<Duration>,
+) -> OpResult<usize, T> {
+    let Some(limit) = limit.filter(|&limit| reachable(limit)) else {
+        return submitted.await;
+    };
+    let mut timer = Some(sleep(limit));
+    let mut timed_out = false;
+    let OpResult(result, op) = poll_fn(|cx| {
+        if let Poll::Ready(done) = Pin::new(&mut submitted).poll(cx) {
+            return Poll::Ready(done);
+        }
+        if let Some(t) = timer.as_mut() {
+            if Pin::new(t).poll(cx).is_ready() {
+                timer = None;
+                timed_out = true;
+                // The completion still arrives, and wakes the waker the poll
+                // above installed.
+                submitted.cancel();
+            }
+        }
+        Poll::Pending
+    })
+    .await;
+    OpResult(timed_out_if(result, timed_out), op)
+}
+
+/// Whether a deadline `limit` from now is an instant at all.
+fn reachable(limit: Duration) -> bool {
+    Instant::now().checked_add(limit).is_some()
+}
+
+/// How often [`swept_if_silent`] reads `SO_CONNECT_TIME`: the option's own
+/// resolution, so checking more often learns nothing.
+const SWEEP_PERIOD: Duration = Duration::from_secs(1);
+
+/// Await an accept that waits for the client's first send, cancelling it in
+/// place once `accepted` has been connected for `limit` without sending.
+///
+/// Returns whether the sweep cancelled it. The result is left as it came, so
+/// `ERROR_OPERATION_ABORTED` together with `true` is a silent client turned
+/// away, and anything else is the accept's real result — including a
+/// connection whose data beat the cancellation.
+pub(crate) async fn swept_if_silent<T: OpCode>(
+    mut submitted: Submit<T>,
+    accepted: &Socket,
+    limit: Duration,
+) -> (OpResult<usize, T>, bool) {
+    let mut timer = sleep(SWEEP_PERIOD);
+    let mut swept = false;
+    let completed = poll_fn(|cx| {
+        if let Poll::Ready(done) = Pin::new(&mut submitted).poll(cx) {
+            return Poll::Ready(done);
+        }
+        // Re-arming polls the new timer in the same pass, so its waker is
+        // installed before this returns.
+        while !swept && Pin::new(&mut timer).poll(cx).is_ready() {
+            // `SO_CONNECT_TIME` works on a socket whose accept is still
+            // pending; it is the documented way to find these clients. A
+            // failed read is treated as "not connected" and retried.
+            let connected = accepted.get_u32_option(SOL_SOCKET, SO_CONNECT_TIME);
+            if connected.is_ok_and(|seconds| silent_too_long(seconds, limit)) {
+                swept = true;
+                // The cancelled accept owns the silent client's socket and
+                // closes it when dropped.
+                submitted.cancel();
+            } else {
+                timer = sleep(SWEEP_PERIOD);
+            }
+        }
+        Poll::Pending
+    })
+    .await;
+    (completed, swept)
+}
+
+/// Whether a pending accept's `SO_CONNECT_TIME` reading is past `limit`.
+///
+/// The option counts whole seconds and reads `0xFFFFFFFF` until a client has
+/// connected. `limit` is rounded up to whole seconds, and to at least one, so
+/// that a client is never turned away before it could have sent anything.
+fn silent_too_long(seconds: u32, limit: Duration) -> bool {
+    let limit = (limit.as_secs() + u64::from(limit.subsec_nanos() > 0)).max(1);
+    seconds != u32::MAX && u64::from(seconds) >= limit
+}
+
+/// Report a cancellation this module caused as a timeout.
+fn timed_out_if(
+    result: windows::core::Result<usize>,
+    timed_out: bool,
+) -> windows::core::Result<usize> {
+    match result {
+        Err(e) if timed_out && e.code() == ERROR_OPERATION_ABORTED.to_hresult() => {
+            Err(timed_out_error())
+        }
+        other => other,
+    }
+}
+
+/// `WSAETIMEDOUT`, the code [`SocketError::from_win32`] reads as
+/// [`SocketError::TimedOut`].
+fn timed_out_error() -> Error {
+    Error::from_hresult(HRESULT::from_win32(WSAETIMEDOUT.0 as u32))
+}
+
+/// A stream's read and write timeouts.
+///
+/// Behind locks rather than plain fields because, as with
+/// `std::net::TcpStream`, they are set through `&self` on a stream other
+/// tasks may be using.
+#[derive(Debug, Default)]
+pub(crate) struct Timeouts {
+    read: Mutex<Option<Duration>>,
+    write: Mutex<Option<Duration>>,
+}
+
+impl Timeouts {
+    pub(crate) fn read(&self) -> Option<Duration> {
+        *self.read.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn write(&self) -> Option<Duration> {
+        *self.write.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn set_read(&self, timeout: Option<Duration>) -> Result<(), SocketError> {
+        *self.read.lock().unwrap_or_else(PoisonError::into_inner) = checked(timeout)?;
+        Ok(())
+    }
+
+    pub(crate) fn set_write(&self, timeout: Option<Duration>) -> Result<(), SocketError> {
+        *self.write.lock().unwrap_or_else(PoisonError::into_inner) = checked(timeout)?;
+        Ok(())
+    }
+}
+
+/// Refuse a zero timeout, as `std` does: it would fail every operation, and
+/// is far more likely a unit mistake than a request for that.
+fn checked(timeout: Option<Duration>) -> Result<Option<Duration>, SocketError> {
+    match timeout {
+        Some(d) if d.is_zero() => Err(SocketError::Win32(Error::from_hresult(
+            ERROR_INVALID_PARAMETER.to_hresult(),
+        ))),
+        other => Ok(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aborted() -> windows::core::Result<usize> {
+        Err(Error::from_hresult(ERROR_OPERATION_ABORTED.to_hresult()))
+    }
+
+    #[test]
+    fn only_our_own_cancellation_becomes_a_timeout() {
+        let err = timed_out_if(aborted(), true).unwrap_err();
+        assert!(matches!(
+            SocketError::from_win32(err),
+            SocketError::TimedOut
+        ));
+        // Cancelled by someone else: still a cancellation.
+        let err = timed_out_if(aborted(), false).unwrap_err();
+        assert!(matches!(
+            SocketError::from_win32(err),
+            SocketError::Cancelled
+        ));
+    }
+
+    #[test]
+    fn a_result_that_beat_the_deadline_stands() {
+        assert_eq!(timed_out_if(Ok(7), true).unwrap(), 7);
+    }
+
+    #[test]
+    fn only_a_connected_client_past_the_limit_is_swept() {
+        let limit = Duration::from_secs(2);
+        assert!(!silent_too_long(u32::MAX, limit), "nobody has connected");
+        assert!(!silent_too_long(1, limit));
+        assert!(silent_too_long(2, limit));
+        assert!(silent_too_long(60, limit));
+    }
+
+    #[test]
+    fn the_sweep_limit_rounds_up_to_whole_seconds() {
+        assert!(!silent_too_long(1, Duration::from_millis(1500)));
+        assert!(silent_too_long(2, Duration::from_millis(1500)));
+        // A client connected for under a second reads as zero; a limit below
+        // one second must not sweep it.
+        assert!(!silent_too_long(0, Duration::ZERO));
+        assert!(!silent_too_long(0, Duration::from_millis(10)));
+        assert!(silent_too_long(1, Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn an_unreachable_limit_is_no_deadline() {
+        assert!(reachable(Duration::from_secs(1)));
+        assert!(!reachable(Duration::MAX));
+    }
+
+    #[test]
+    fn a_zero_timeout_is_refused() {
+        let timeouts = Timeouts::default();
+        assert!(timeouts.set_read(Some(Duration::ZERO)).is_err());
+        assert_eq!(timeouts.read(), None);
+        timeouts.set_write(Some(Duration::from_secs(1))).unwrap();
+        assert_eq!(timeouts.write(), Some(Duration::from_secs(1)));
+        timeouts.set_write(None).unwrap();
+        assert_eq!(timeouts.write(), None);
+    }
+}
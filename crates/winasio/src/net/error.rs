@@ -26,6 +26,12 @@
 //! A refused connection is `WSAECONNREFUSED` (10061) one way and
 //! `ERROR_CONNECTION_REFUSED` (1225) the other. Classifying only one of them
 //! would make the outcome depend on timing, so both are mapped.
+//!
+//! Connecting by host name is the one exception to the single type, and gets
+//! [`ConnectError`]: it can fail once per resolved address, and a caller needs
+//! all of those failures, not the last.
+
+use std::net::SocketAddr;
 
 use windows::core::Error;
 use windows::Win32::Foundation::{
@@ -36,6 +42,7 @@ use windows::Win32::Foundation::{
 use windows::Win32::Networking::WinSock::{
     WSAEACCES, WSAEADDRINUSE, WSAEADDRNOTAVAIL, WSAECONNABORTED, WSAECONNREFUSED, WSAECONNRESET,
     WSAEDISCON, WSAEHOSTUNREACH, WSAENETRESET, WSAENETUNREACH, WSAESHUTDOWN, WSAETIMEDOUT,
+    WSAHOST_NOT_FOUND, WSANO_DATA, WSA_E_CANCELLED,
 };
 
 use crate::iocp::RegistrationError;
@@ -62,6 +69,12 @@ pub enum SocketError {
     AddressNotAvailable,
     /// Access was denied.
     AccessDenied,
+    /// The name does not exist, or has no address records.
+    ///
+    /// Only [`lookup_host`](super::lookup_host) reports this. A resolver that
+    /// could not be reached is a different failure and stays
+    /// [`SocketError::Win32`]: retrying it may help, retrying this will not.
+    HostNotFound,
     /// The operation was cancelled — normally because the awaiting future was
     /// dropped, or the owning socket was closed.
     Cancelled,
@@ -146,6 +159,13 @@ impl SocketError {
             // Cancellation. Only ever arrives on the completion path — the
             // packet a cancelled operation still delivers.
             c if c == ERROR_OPERATION_ABORTED.0 as i32 => SocketError::Cancelled,
+            // The resolver's spelling of the same thing.
+            c if c == WSA_E_CANCELLED.0 => SocketError::Cancelled,
+
+            // Name resolution. `WSANO_DATA` is a name that exists with no
+            // address of the requested family, which to a caller wanting to
+            // connect is the same answer.
+            c if c == WSAHOST_NOT_FOUND.0 || c == WSANO_DATA.0 => SocketError::HostNotFound,
 
             _ => SocketError::Win32(err),
         }
@@ -175,6 +195,7 @@ impl std::fmt::Display for SocketError {
             SocketError::AddressInUse => write!(f, "address is already in use"),
             SocketError::AddressNotAvailable => write!(f, "address is not available"),
             SocketError::AccessDenied => write!(f, "access denied"),
+            SocketError::HostNotFound => write!(f, "host not found"),
             SocketError::Cancelled => write!(f, "operation was cancelled"),
             SocketError::AlreadyRegistered => write!(
                 f,
@@ -191,6 +212,66 @@ impl std::fmt::Display for SocketError {
 
 impl std::error::Error for SocketError {}
 
+/// Why [`TcpStream::connect_host`](super::TcpStream::connect_host) failed.
+///
+/// Separate from [`SocketError`] because connecting by name can fail more than
+/// once: a host with an unreachable IPv6 address and a refusing IPv4 one has
+/// two failures, and reporting only the last would hide the one worth
+/// fixing.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConnectError {
+    /// The name could not be resolved.
+    Resolve(SocketError),
+    /// The name resolved, but to no address a stream can connect to.
+    NoAddresses,
+    /// Every resolved address was tried and none connected. One entry
+    /// per address, in the order they were tried; never empty.
+    Attempts(Vec<(SocketAddr, SocketError)>),
+}
+
+impl ConnectError {
+    /// The per-address failures, in the order the addresses were tried.
+    ///
+    /// Empty unless this is [`ConnectError::Attempts`].
+    pub fn attempts(&self) -> &[(SocketAddr, SocketError)] {
+        match self {
+            ConnectError::Attempts(attempts) => attempts,
+            _ => &[],
+        }
+    }
+}
+
+impl std::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectError::Resolve(e) => write!(f, "could not resolve host: {e}"),
+            ConnectError::NoAddresses => write!(f, "host resolved to no usable address"),
+            ConnectError::Attempts(attempts) => {
+                write!(f, "could not connect to any resolved address")?;
+                for (addr, e) in attempts {
+                    write!(f, "; {addr}: {e}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConnectError::Resolve(e) => Some(e),
+            ConnectError::NoAddresses => None,
+            // The first address is the resolver's preferred one, so its
+            // failure is the one to chain.
+            ConnectError::Attempts(attempts) => attempts
+                .first()
+                .map(|(_, e)| e as &(dyn std::error::Error + 'static)),
+        }
+    }
+}
+
 /// Recover the numeric code from a Win32-facility `HRESULT`.
 ///
 /// Winsock codes (10004–11031) fit in 16 bits, so they survive the same
@@ -293,6 +374,38 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn resolver_failures_are_classified() {
+        assert!(matches!(
+            classify(WSAHOST_NOT_FOUND.0),
+            SocketError::HostNotFound
+        ));
+        assert!(matches!(classify(WSANO_DATA.0), SocketError::HostNotFound));
+        assert!(matches!(
+            classify(WSA_E_CANCELLED.0),
+            SocketError::Cancelled
+        ));
+    }
+
+    #[test]
+    fn a_connect_error_reports_every_attempt() {
+        let err = ConnectError::Attempts(vec![
+            ("[::1]:80".parse().unwrap(), SocketError::Unreachable),
+            (
+                "127.0.0.1:80".parse().unwrap(),
+                SocketError::ConnectionRefused,
+            ),
+        ]);
+        assert_eq!(err.attempts().len(), 2);
+        assert_eq!(
+            err.to_string(),
+            "could not connect to any resolved address; \
+             [::1]:80: network or host is unreachable; \
+             127.0.0.1:80: connection refused"
+        );
+        assert!(ConnectError::NoAddresses.attempts().is_empty());
+    }
+
     #[test]
     fn an_unclassified_code_stays_opaque() {
         // A control: the table must not swallow codes it does not know.
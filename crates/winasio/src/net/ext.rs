@@ -26,14 +26,16 @@
 //! The distinction is not academic here. A non-IFS layered provider is the one
 //! realistic socket that can refuse the mandatory inline-success skip mode
 //! (see `iocp::port`) — exactly the case where "generic entry point" and "this
-//! provider's implementation" stop coinciding. Resolving all three the same way
-//! also leaves one mechanism to reason about instead of two.
+//! provider's implementation" stop coinciding. Resolving all of them the same
+//! way also leaves one mechanism to reason about instead of two. `TransmitFile`
+//! is in the same position as `AcceptEx` and is resolved the same way.
+//! `WSARecvMsg` and `DisconnectEx`, like `ConnectEx`, have no export at all.
 //!
 //! # Why one cache is enough
 //!
 //! Winsock documents these as per-provider, which would suggest caching per
-//! socket or per family. Measured on this platform, all three pointers came
-//! back identical for `AF_INET` and `AF_INET6` sockets and across repeated
+//! socket or per family. Measured on this platform, the pointers came back
+//! identical for `AF_INET` and `AF_INET6` sockets and across repeated
 //! sockets of the same family, so one process-wide cache — discovered through a
 //! single throwaway `AF_INET` socket — serves both families. This is what mio,
 //! tokio and compio all do.
@@ -44,10 +46,13 @@
 use std::sync::OnceLock;
 
 use windows::core::{Result, GUID};
+use windows::Win32::Foundation::HANDLE;
 use windows::Win32::Networking::WinSock::{
-    WSAIoctl, AF_INET, LPFN_ACCEPTEX, LPFN_CONNECTEX, LPFN_GETACCEPTEXSOCKADDRS,
-    SIO_GET_EXTENSION_FUNCTION_POINTER, SOCKADDR, SOCKET, WSAID_ACCEPTEX, WSAID_CONNECTEX,
-    WSAID_GETACCEPTEXSOCKADDRS,
+    WSAIoctl, AF_INET, LPFN_ACCEPTEX, LPFN_CONNECTEX, LPFN_DISCONNECTEX, LPFN_GETACCEPTEXSOCKADDRS,
+    LPFN_TRANSMITFILE, LPFN_WSARECVMSG, LPWSAOVERLAPPED_COMPLETION_ROUTINE,
+    SIO_GET_EXTENSION_FUNCTION_POINTER, SOCKADDR, SOCKET, TRANSMIT_FILE_BUFFERS, WSAID_ACCEPTEX,
+    WSAID_CONNECTEX, WSAID_DISCONNECTEX, WSAID_GETACCEPTEXSOCKADDRS, WSAID_TRANSMITFILE,
+    WSAID_WSARECVMSG, WSAMSG,
 };
 
 use super::socket::Socket;
@@ -75,6 +80,14 @@ pub(crate) type ConnectExFn = unsafe extern "system" fn(
     overlapped: *mut windows::Win32::System::IO::OVERLAPPED,
 ) -> windows::core::BOOL;
 
+/// `DisconnectEx`, with the `Option` discharged.
+pub(crate) type DisconnectExFn = unsafe extern "system" fn(
+    socket: SOCKET,
+    overlapped: *mut windows::Win32::System::IO::OVERLAPPED,
+    flags: u32,
+    reserved: u32,
+) -> windows::core::BOOL;
+
 /// `GetAcceptExSockaddrs`, with the `Option` discharged.
 pub(crate) type GetAcceptExSockaddrsFn = unsafe extern "system" fn(
     output_buffer: *const core::ffi::c_void,
@@ -87,6 +100,29 @@ pub(crate) type GetAcceptExSockaddrsFn = unsafe extern "system" fn(
     remote_sockaddr_length: *mut i32,
 );
 
+/// `TransmitFile`, with the `Option` discharged.
+///
+/// The `windows` crate names the last parameter `dwreserved`; it is the
+/// `dwFlags` word that carries `TF_DISCONNECT` and friends.
+pub(crate) type TransmitFileFn = unsafe extern "system" fn(
+    socket: SOCKET,
+    file: HANDLE,
+    bytes_to_write: u32,
+    bytes_per_send: u32,
+    overlapped: *mut windows::Win32::System::IO::OVERLAPPED,
+    buffers: *const TRANSMIT_FILE_BUFFERS,
+    flags: u32,
+) -> windows::core::BOOL;
+
+/// `WSARecvMsg`, with the `Option` discharged.
+pub(crate) type WsaRecvMsgFn = unsafe extern "system" fn(
+    socket: SOCKET,
+    msg: *mut WSAMSG,
+    bytes_received: *mut u32,
+    overlapped: *mut windows::Win32::System::IO::OVERLAPPED,
+    completion_routine: LPWSAOVERLAPPED_COMPLETION_ROUTINE,
+) -> i32;
+
 /// The extension functions this crate uses.
 ///
 /// The `Option` in Winsock's `LPFN_*` aliases is discharged once, here, rather
@@ -95,7 +131,10 @@ pub(crate) type GetAcceptExSockaddrsFn = unsafe extern "system" fn(
 pub(crate) struct Extensions {
     pub(crate) accept_ex: AcceptExFn,
     pub(crate) connect_ex: ConnectExFn,
+    pub(crate) disconnect_ex: DisconnectExFn,
     pub(crate) get_accept_ex_sockaddrs: GetAcceptExSockaddrsFn,
+    pub(crate) transmit_file: TransmitFileFn,
+    pub(crate) wsa_recv_msg: WsaRecvMsgFn,
 }
 
 static EXTENSIONS: OnceLock<Extensions> = OnceLock::new();
@@ -124,15 +163,25 @@ fn resolve() -> Result<Extensions> {
     // SAFETY: as above.
     let connect_ex: LPFN_CONNECTEX = unsafe { lookup(&probe, &WSAID_CONNECTEX) }?;
     // SAFETY: as above.
+    let disconnect_ex: LPFN_DISCONNECTEX = unsafe { lookup(&probe, &WSAID_DISCONNECTEX) }?;
+    // SAFETY: as above.
     let get_accept_ex_sockaddrs: LPFN_GETACCEPTEXSOCKADDRS =
         unsafe { lookup(&probe, &WSAID_GETACCEPTEXSOCKADDRS) }?;
+    // SAFETY: as above.
+    let transmit_file: LPFN_TRANSMITFILE = unsafe { lookup(&probe, &WSAID_TRANSMITFILE) }?;
+    // SAFETY: as above. Looked up on the TCP probe like the rest: the provider
+    // hands the pointer back for any socket, and it is only called on UDP ones.
+    let wsa_recv_msg: LPFN_WSARECVMSG = unsafe { lookup(&probe, &WSAID_WSARECVMSG) }?;
 
     // A provider that reports success but hands back a null pointer would
     // otherwise blow up at the call site, far from the cause.
     Ok(Extensions {
         accept_ex: accept_ex.ok_or_else(proc_not_found)?,
         connect_ex: connect_ex.ok_or_else(proc_not_found)?,
+        disconnect_ex: disconnect_ex.ok_or_else(proc_not_found)?,
         get_accept_ex_sockaddrs: get_accept_ex_sockaddrs.ok_or_else(proc_not_found)?,
+        transmit_file: transmit_file.ok_or_else(proc_not_found)?,
+        wsa_recv_msg: wsa_recv_msg.ok_or_else(proc_not_found)?,
     })
 }
 
@@ -191,7 +240,10 @@ mod tests {
         // load-bearing rather than dead.
         assert_ne!(ext.accept_ex as usize, 0);
         assert_ne!(ext.connect_ex as usize, 0);
+        assert_ne!(ext.disconnect_ex as usize, 0);
         assert_ne!(ext.get_accept_ex_sockaddrs as usize, 0);
+        assert_ne!(ext.transmit_file as usize, 0);
+        assert_ne!(ext.wsa_recv_msg as usize, 0);
     }
 
     #[test]
@@ -236,6 +288,22 @@ mod tests {
             assert_eq!(a, c, "{name} must be the same function for v4 and AF_UNIX");
         }
 
+        // `TransmitFile` and `DisconnectEx` are only used on TCP sockets, so
+        // only the two IP families have to agree.
+        for (guid, name) in [
+            (&WSAID_TRANSMITFILE, "TransmitFile"),
+            (&WSAID_DISCONNECTEX, "DisconnectEx"),
+        ] {
+            // SAFETY: both are live sockets and the GUID names a function
+            // pointer.
+            let on = |s: &Socket| unsafe { lookup::<usize>(s, guid) }.expect("lookup");
+            assert_eq!(
+                on(&v4),
+                on(&v6),
+                "{name} must be the same function for v4 and v6"
+            );
+        }
+
         // Keep the typed lookup exercised as well, so the `LPFN_*` aliases the
         // rest of the module depends on are not left unproven by the
         // `usize`-shaped comparison above.
@@ -0,0 +1,404 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Happy Eyeballs (RFC 8305): racing staggered connects across families.
+//!
+//! A dual-stack host whose IPv6 path is broken drops the SYN rather than
+//! refusing it, so [`TcpStream::connect_host`] — which tries one address at a
+//! time — waits out a whole connect timeout before trying IPv4. This module
+//! starts the next address after a short delay instead, keeps the earlier
+//! attempts running, and takes whichever connects first.
+//!
+//! The policy — the address order and when each attempt may start — is
+//! `Schedule`, a state machine over `std` types only, driven by the
+//! caller's clock. Its tests therefore need no sockets and no timers, though
+//! as part of this crate they still build and run on Windows only.
+//! [`HappyEyeballs::connect`] is the thin driver that feeds it connect results
+//! and [`crate::time`] deadlines.
+//!
+//! Resolution is a single `GetAddrInfoExW` query for both families, so the
+//! RFC's "Resolution Delay" between the AAAA and A answers does not arise.
+
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+use crate::iocp::Registrar;
+use crate::time::{sleep_until, Sleep};
+
+use super::error::{ConnectError, SocketError};
+use super::resolve::lookup_host;
+use super::stream::TcpStream;
+
+/// RFC 8305's recommended "Connection Attempt Delay".
+const DEFAULT_DELAY: Duration = Duration::from_millis(250);
+
+/// Connects to a host's addresses with staggered, concurrent attempts.
+///
+/// Addresses are interleaved by family, starting with the family of the
+/// resolver's first answer. The first attempt starts at once; each later one
+/// starts when the [delay](HappyEyeballs::delay) has passed since the
+/// previous start, or as soon as an attempt fails, whichever is first. The
+/// first stream to connect is returned and the others are dropped, which
+/// cancels their `ConnectEx` exactly as dropping any other operation future
+/// does — on a caller-driven backend, keep driving the proactor until those
+/// records are reclaimed.
+///
+/// The delay is a [`crate::time`] timer, so a loop driving a
+/// [`Proactor`](crate::iocp::Proactor) must poll with a timeout for it to be
+/// noticed; see that module.
+#[derive(Debug, Clone, Copy)]
+pub struct HappyEyeballs {
+    delay: Duration,
+}
+
+impl Default for HappyEyeballs {
+    fn default() -> Self {
+        HappyEyeballs {
+            delay: DEFAULT_DELAY,
+        }
+    }
+}
+
+impl HappyEyeballs {
+    /// The RFC's defaults: a 250 ms delay between attempts.
+    pub fn new() -> Self {
+        HappyEyeballs::default()
+    }
+
+    /// Set how long an attempt is given before the next one starts alongside
+    /// it.
+    ///
+    /// RFC 8305 recommends 250 ms and no less than 100 ms. Zero starts every
+    /// address at once; a delay too long to reach, such as [`Duration::MAX`],
+    /// starts the next address only when an attempt fails.
+    pub fn delay(&mut self, delay: Duration) -> &mut Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Resolve `host` with [`lookup_host`](super::lookup_host) and race its
+    /// addresses.
+    pub async fn connect_host<R>(
+        &self,
+        registrar: &R,
+        host: &str,
+        port: u16,
+    ) -> Result<TcpStream<R::Io>, ConnectError>
+    where
+        R: Registrar,
+    {
+        let addrs = lookup_host(host, port)
+            .await
+            .map_err(ConnectError::Resolve)?;
+        self.connect(registrar, addrs).await
+    }
+
+    /// Race connects to `addrs`, which should be in the resolver's order.
+    ///
+    /// Fails with [`ConnectError::NoAddresses`] if `addrs` is empty, and with
+    /// [`ConnectError::Attempts`] — every address, in the order its attempt
+    /// started — if none connects.
+    pub async fn connect<R, I>(
+        &self,
+        registrar: &R,
+        addrs: I,
+    ) -> Result<TcpStream<R::Io>, ConnectError>
+    where
+        R: Registrar,
+        I: IntoIterator<Item = SocketAddr>,
+    {
+        let mut schedule = Schedule::new(addrs, self.delay);
+        let mut running = Vec::new();
+        let mut timer: Option<Sleep> = None;
+
+        poll_fn(|cx| loop {
+            match schedule.next(Instant::now()) {
+                Step::Start(index, addr) => {
+                    running.push((index, Box::pin(TcpStream::connect(registrar, addr))));
+                    continue;
+                }
+                Step::WaitUntil(deadline) => {
+                    if timer.as_ref().map(Sleep::deadline) != Some(deadline) {
+                        timer = Some(sleep_until(deadline));
+                    }
+                }
+                Step::Idle => timer = None,
+                Step::Exhausted => return Poll::Ready(Err(schedule.take_error())),
+            }
+
+            let mut failed = false;
+            let mut i = 0;
+            while i < running.len() {
+                match running[i].1.as_mut().poll(cx) {
+                    // The losers are dropped with `running` when this
+                    // function returns, which cancels them.
+                    Poll::Ready(Ok(stream)) => return Poll::Ready(Ok(stream)),
+                    Poll::Ready(Err(e)) => {
+                        let (index, _) = running.swap_remove(i);
+                        schedule.failed(index, e);
+                        failed = true;
+                    }
+                    Poll::Pending => i += 1,
+                }
+            }
+            // A failure may have released the next attempt, or ended the race.
+            if failed {
+                continue;
+            }
+
+            if let Some(sleep) = timer.as_mut() {
+                if Pin::new(sleep).poll(cx).is_ready() {
+                    timer = None;
+                    continue;
+                }
+            }
+            return Poll::Pending;
+        })
+        .await
+    }
+}
+
+/// What the driver should do next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    /// Start attempt number `.0`, to `.1`.
+    Start(usize, SocketAddr),
+    /// Nothing may start before this instant.
+    WaitUntil(Instant),
+    /// Nothing may start until an attempt in flight finishes.
+    Idle,
+    /// Every attempt has failed.
+    Exhausted,
+}
+
+/// When [`Schedule`] may start its next attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Gate {
+    /// Now.
+    Open,
+    /// Once this instant has passed, or an attempt has failed.
+    At(Instant),
+    /// Once an attempt has failed: the delay ends later than an `Instant`
+    /// can express.
+    OnFailure,
+}
+
+/// The Happy Eyeballs policy, with no I/O and no clock of its own.
+///
+/// Generic over the error type only so that its tests need not construct a
+/// [`SocketError`].
+#[derive(Debug)]
+struct Schedule<E> {
+    pending: VecDeque<SocketAddr>,
+    started: usize,
+    in_flight: usize,
+    delay: Duration,
+    /// When the next attempt may start.
+    gate: Gate,
+    failures: Vec<(usize, SocketAddr, E)>,
+    addrs: Vec<SocketAddr>,
+}
+
+impl<E> Schedule<E> {
+    fn new<I: IntoIterator<Item = SocketAddr>>(addrs: I, delay: Duration) -> Self {
+        let pending = interleave(addrs);
+        Schedule {
+            addrs: pending.iter().copied().collect(),
+            pending,
+            started: 0,
+            in_flight: 0,
+            delay,
+            gate: Gate::Open,
+            failures: Vec::new(),
+        }
+    }
+
+    fn next(&mut self, now: Instant) -> Step {
+        if self.pending.is_empty() {
+            return if self.in_flight == 0 {
+                Step::Exhausted
+            } else {
+                Step::Idle
+            };
+        }
+        match self.gate {
+            Gate::At(at) if now < at => Step::WaitUntil(at),
+            Gate::OnFailure => Step::Idle,
+            _ => {
+                let addr = self.pending.pop_front().expect("checked non-empty");
+                let index = self.started;
+                self.started += 1;
+                self.in_flight += 1;
+                self.gate = match now.checked_add(self.delay) {
+                    Some(at) => Gate::At(at),
+                    None => Gate::OnFailure,
+                };
+                Step::Start(index, addr)
+            }
+        }
+    }
+
+    /// Record that attempt `index` failed. The next attempt, if any, may
+    /// start at once rather than waiting out the delay.
+    fn failed(&mut self, index: usize, err: E) {
+        self.in_flight -= 1;
+        self.gate = Gate::Open;
+        self.failures.push((index, self.addrs[index], err));
+    }
+
+    /// The failures, in the order their attempts started.
+    fn take_failures(&mut self) -> Vec<(SocketAddr, E)> {
+        let mut failures = std::mem::take(&mut self.failures);
+        failures.sort_by_key(|(index, _, _)| *index);
+        failures
+            .into_iter()
+            .map(|(_, addr, err)| (addr, err))
+            .collect()
+    }
+}
+
+impl Schedule<SocketError> {
+    fn take_error(&mut self) -> ConnectError {
+        if self.addrs.is_empty() {
+            ConnectError::NoAddresses
+        } else {
+            ConnectError::Attempts(self.take_failures())
+        }
+    }
+}
+
+/// Order addresses as RFC 8305 section 4 asks: alternate families, starting
+/// with the family of the first address, and otherwise keep the resolver's
+/// order.
+fn interleave<I: IntoIterator<Item = SocketAddr>>(addrs: I) -> VecDeque<SocketAddr> {
+    let addrs: Vec<SocketAddr> = addrs.into_iter().collect();
+    let Some(first) = addrs.first() else {
+        return VecDeque::new();
+    };
+    let first_v6 = first.is_ipv6();
+    let mut ordered = VecDeque::with_capacity(addrs.len());
+    let (mut preferred, mut other): (VecDeque<_>, VecDeque<_>) =
+        addrs.into_iter().partition(|a| a.is_ipv6() == first_v6);
+    loop {
+        match (preferred.pop_front(), other.pop_front()) {
+            (None, None) => return ordered,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addrs(list: &[&str]) -> Vec<SocketAddr> {
+        list.iter().map(|a| a.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn families_alternate_starting_with_the_first_answer() {
+        let ordered = interleave(addrs(&[
+            "[2001:db8::1]:80",
+            "[2001:db8::2]:80",
+            "[2001:db8::3]:80",
+            "192.0.2.1:80",
+            "192.0.2.2:80",
+        ]));
+        assert_eq!(
+            Vec::from(ordered),
+            addrs(&[
+                "[2001:db8::1]:80",
+                "192.0.2.1:80",
+                "[2001:db8::2]:80",
+                "192.0.2.2:80",
+                "[2001:db8::3]:80",
+            ])
+        );
+
+        let ordered = interleave(addrs(&["192.0.2.1:80", "[2001:db8::1]:80", "192.0.2.2:80"]));
+        assert_eq!(
+            Vec::from(ordered),
+            addrs(&["192.0.2.1:80", "[2001:db8::1]:80", "192.0.2.2:80"])
+        );
+    }
+
+    #[test]
+    fn a_single_family_keeps_its_order() {
+        let list = addrs(&["192.0.2.3:80", "192.0.2.1:80", "192.0.2.2:80"]);
+        assert_eq!(Vec::from(interleave(list.clone())), list);
+        assert!(interleave(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn attempts_are_staggered_by_the_delay() {
+        let t0 = Instant::now();
+        let delay = Duration::from_millis(250);
+        let list = addrs(&["[2001:db8::1]:80", "192.0.2.1:80", "192.0.2.2:80"]);
+        let mut schedule = Schedule::<()>::new(list.clone(), delay);
+
+        assert_eq!(schedule.next(t0), Step::Start(0, list[0]));
+        assert_eq!(schedule.next(t0), Step::WaitUntil(t0 + delay));
+        let t1 = t0 + Duration::from_millis(100);
+        assert_eq!(schedule.next(t1), Step::WaitUntil(t0 + delay));
+        let t2 = t0 + delay;
+        assert_eq!(schedule.next(t2), Step::Start(1, list[1]));
+        assert_eq!(schedule.next(t2 + delay), Step::Start(2, list[2]));
+        assert_eq!(schedule.next(t2 + delay), Step::Idle);
+    }
+
+    #[test]
+    fn a_failure_starts_the_next_attempt_at_once() {
+        let t0 = Instant::now();
+        let list = addrs(&["[2001:db8::1]:80", "192.0.2.1:80"]);
+        let mut schedule = Schedule::new(list.clone(), Duration::from_secs(1));
+
+        assert_eq!(schedule.next(t0), Step::Start(0, list[0]));
+        let t1 = t0 + Duration::from_millis(10);
+        schedule.failed(0, "refused");
+        assert_eq!(schedule.next(t1), Step::Start(1, list[1]));
+    }
+
+    #[test]
+    fn a_delay_past_any_instant_waits_for_a_failure() {
+        let t0 = Instant::now();
+        let list = addrs(&["[2001:db8::1]:80", "192.0.2.1:80"]);
+        let mut schedule = Schedule::new(list.clone(), Duration::MAX);
+
+        assert_eq!(schedule.next(t0), Step::Start(0, list[0]));
+        assert_eq!(schedule.next(t0 + Duration::from_secs(3600)), Step::Idle);
+        schedule.failed(0, "unreachable");
+        assert_eq!(schedule.next(t0), Step::Start(1, list[1]));
+    }
+
+    #[test]
+    fn failures_are_reported_in_start_order() {
+        let t0 = Instant::now();
+        let list = addrs(&["[2001:db8::1]:80", "192.0.2.1:80"]);
+        let mut schedule = Schedule::new(list.clone(), Duration::ZERO);
+
+        assert_eq!(schedule.next(t0), Step::Start(0, list[0]));
+        assert_eq!(schedule.next(t0), Step::Start(1, list[1]));
+        // The second attempt fails first.
+        schedule.failed(1, "refused");
+        assert_eq!(schedule.next(t0), Step::Idle);
+        schedule.failed(0, "unreachable");
+        assert_eq!(schedule.next(t0), Step::Exhausted);
+        assert_eq!(
+            schedule.take_failures(),
+            [(list[0], "unreachable"), (list[1], "refused")]
+        );
+    }
+
+    #[test]
+    fn no_addresses_is_exhausted_immediately() {
+        let mut schedule = Schedule::<()>::new(Vec::new(), DEFAULT_DELAY);
+        assert_eq!(schedule.next(Instant::now()), Step::Exhausted);
+    }
+}
@@ -0,0 +1,194 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Several accepts outstanding on one listener.
+//!
+//! [`TcpListener::incoming`](super::TcpListener::incoming) and
+//! [`UnixListener::incoming`](super::UnixListener::incoming) differ only in
+//! how an accept is posted and how its completion becomes a stream. Each hands
+//! its own pair of functions to one [`Accepting`], which does the rest. They
+//! are plain function pointers rather than a trait so that no crate-private
+//! bound shows up on a public type.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::iocp::{OpResult, Submit};
+
+use super::error::SocketError;
+use super::ops::accept::AcceptSocket;
+
+/// Post one `AcceptEx` on the listener.
+pub(crate) type SubmitAccept<L, S> = fn(&L) -> Result<PostedAccept<S>, SocketError>;
+
+/// Turn a completed `AcceptEx`, and the submitter of a recycled socket if it
+/// used one, into the listener's connection type.
+pub(crate) type FinishAccept<L, S, T> =
+    fn(&L, OpResult<usize, AcceptSocket>, Option<S>) -> Result<T, SocketError>;
+
+/// An `AcceptEx` in flight, with the submitter of the socket it accepts onto
+/// when that socket was recycled.
+pub(crate) struct PostedAccept<S> {
+    pub(crate) submitted: Submit<AcceptSocket>,
+    pub(crate) reused: Option<S>,
+}
+
+/// Connections from a listener with several accepts outstanding.
+///
+/// Created by [`TcpListener::incoming`](super::TcpListener::incoming), as
+/// [`Incoming`](super::Incoming), and by
+/// [`UnixListener::incoming`](super::UnixListener::incoming), as
+/// [`UnixIncoming`](super::UnixIncoming). Connections are yielded in the order
+/// their accepts were posted among those that have completed, so one slow
+/// completion cannot starve the others. A completed accept's replacement is
+/// posted before its connection is handed back, so the listener is at full
+/// depth again while the caller handles it.
+///
+/// Dropping this cancels the outstanding accepts. Unlike a single dropped
+/// `accept`, that can turn away clients: an accept the kernel completed but
+/// that was not yet yielded holds a connection the peer already considers
+/// established, and it is closed with the operation.
+pub struct Accepting<'a, L, S, T> {
+    listener: &'a L,
+    submit: SubmitAccept<L, S>,
+    finish: FinishAccept<L, S, T>,
+    in_flight: Vec<PostedAccept<S>>,
+    depth: usize,
+}
+
+impl<'a, L, S, T> Accepting<'a, L, S, T> {
+    /// Accepts on `listener`, at most `depth` of them posted; zero is treated
+    /// as one. Nothing is posted until the first poll.
+    pub(crate) fn new(
+        listener: &'a L,
+        depth: usize,
+        submit: SubmitAccept<L, S>,
+        finish: FinishAccept<L, S, T>,
+    ) -> Self {
+        Accepting {
+            listener,
+            submit,
+            finish,
+            in_flight: Vec::new(),
+            depth: depth.max(1),
+        }
+    }
+
+    /// Wait for the next connection.
+    ///
+    /// An error is one accept failing, or a new accept failing to post, not
+    /// the set: the next call posts a replacement and carries on.
+    pub async fn next(&mut self) -> Result<T, SocketError> {
+        std::future::poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    /// Poll for the next connection, topping the posted accepts back up to
+    /// the depth.
+    ///
+    /// A connection already accepted is yielded even while posting new
+    /// accepts fails; that error is reported only when nothing has completed.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, SocketError>> {
+        let topped_up = self.top_up();
+        for i in 0..self.in_flight.len() {
+            if let Poll::Ready(completed) = Pin::new(&mut self.in_flight[i].submitted).poll(cx) {
+                let posted = self.in_flight.remove(i);
+                // Replace it now rather than on the next call. If that fails,
+                // the next call tries again and reports the error.
+                let _ = self.top_up();
+                return Poll::Ready((self.finish)(self.listener, completed, posted.reused));
+            }
+        }
+        match topped_up {
+            Err(e) => Poll::Ready(Err(e)),
+            Ok(()) => Poll::Pending,
+        }
+    }
+
+    fn top_up(&mut self) -> Result<(), SocketError> {
+        while self.in_flight.len() < self.depth {
+            let posted = (self.submit)(self.listener)?;
+            self.in_flight.push(posted);
+        }
+        Ok(())
+    }
+}
+
+impl<L, S, T> std::fmt::Debug for Accepting<'_, L, S, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Accepting")
+            .field("in_flight", &self.in_flight.len())
+            .field("depth", &self.depth)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::net::SocketAddr;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use windows::core::{Error, HRESULT};
+    use windows::Win32::Networking::WinSock::WSAENOBUFS;
+
+    use super::*;
+    use crate::iocp::Proactor;
+    use crate::net::{TcpListener, TcpStream};
+
+    /// A listener that can post only so many accepts, as one out of buffers.
+    struct Starved {
+        listener: TcpListener<Rc<Proactor>>,
+        posts_left: Cell<usize>,
+    }
+
+    fn submit(starved: &Starved) -> Result<PostedAccept<Rc<Proactor>>, SocketError> {
+        match starved.posts_left.get() {
+            0 => Err(SocketError::from_win32(Error::from_hresult(
+                HRESULT::from_win32(WSAENOBUFS.0 as u32),
+            ))),
+            n => {
+                starved.posts_left.set(n - 1);
+                starved.listener.submit_accept()
+            }
+        }
+    }
+
+    fn finish(
+        starved: &Starved,
+        completed: OpResult<usize, AcceptSocket>,
+        reused: Option<Rc<Proactor>>,
+    ) -> Result<(TcpStream<Rc<Proactor>>, SocketAddr), SocketError> {
+        starved.listener.finish_accept(completed, reused)
+    }
+
+    #[test]
+    fn a_completed_accept_is_yielded_while_posting_fails() {
+        let _guard = crate::net::socket::socket_guard();
+        let proactor = Rc::new(Proactor::new().unwrap());
+        let starved = Starved {
+            listener: TcpListener::bind(&proactor, "127.0.0.1:0".parse().unwrap()).unwrap(),
+            posts_left: Cell::new(1),
+        };
+        let mut accepting = Accepting::new(&starved, 2, submit, finish);
+        let client = std::net::TcpStream::connect(starved.listener.local_addr()).unwrap();
+
+        let mut yielded = None;
+        for _ in 0..100 {
+            match proactor.block_on(accepting.next()) {
+                Ok((_, peer)) => {
+                    yielded = Some(peer);
+                    break;
+                }
+                // Nothing completed yet: the posting error is reported.
+                Err(_) => {
+                    let _ = proactor.poll(Some(Duration::from_millis(10)));
+                }
+            }
+        }
+        assert_eq!(yielded, Some(client.local_addr().unwrap()));
+    }
+}
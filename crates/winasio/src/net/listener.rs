@@ -7,12 +7,20 @@
 //! A listening TCP socket.
 
 use std::net::SocketAddr;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
 
-use crate::iocp::{OpResult, Registrar, Submitter};
+use windows::Win32::Foundation::ERROR_OPERATION_ABORTED;
+
+use crate::iocp::{IoBufMut, OpResult, Registrar, Submitter};
 
 use super::addr::{family_of, unsupported_family};
+use super::deadline::{swept_if_silent, within};
 use super::error::SocketError;
-use super::ops::accept::AcceptSocket;
+use super::incoming::{Accepting, PostedAccept};
+use super::ops::accept::{AcceptSocket, AcceptedParts};
+use super::options::SocketOptions;
+use super::reuse::{Origin, ReusableSocket};
 use super::socket::Socket;
 use super::stream::TcpStream;
 
@@ -25,6 +33,11 @@ use super::stream::TcpStream;
 pub struct TcpListenerOptions {
     backlog: u32,
     only_v6: bool,
+    reuse_address: Option<bool>,
+    exclusive_address_use: Option<bool>,
+    accepted: SocketOptions,
+    silent_accept_limit: Duration,
+    recycle_limit: usize,
 }
 
 impl TcpListenerOptions {
@@ -37,6 +50,11 @@ impl TcpListenerOptions {
             // Matches the Windows default, so `new` changes nothing about how a
             // v6 listener behaves.
             only_v6: true,
+            reuse_address: None,
+            exclusive_address_use: None,
+            accepted: SocketOptions::new(),
+            silent_accept_limit: Duration::from_secs(10),
+            recycle_limit: 64,
         }
     }
 
@@ -61,6 +79,55 @@ impl TcpListenerOptions {
         self.only_v6 = only_v6;
         self
     }
+
+    /// Set `SO_REUSEADDR` on the listening socket before it binds.
+    ///
+    /// See [`Socket::set_reuse_address`] for why this is rarely what a
+    /// Windows server wants.
+    pub fn reuse_address(&mut self, reuse: bool) -> &mut Self {
+        self.reuse_address = Some(reuse);
+        self
+    }
+
+    /// Set `SO_EXCLUSIVEADDRUSE` on the listening socket before it binds, so
+    /// no other socket can bind the same address while it is open.
+    pub fn exclusive_address_use(&mut self, exclusive: bool) -> &mut Self {
+        self.exclusive_address_use = Some(exclusive);
+        self
+    }
+
+    /// Options applied to every accepted connection before
+    /// [`TcpListener::accept`] returns it.
+    ///
+    /// Set on the accepted socket itself rather than left to inheritance from
+    /// the listener, so what a connection gets does not depend on which
+    /// options Windows happens to copy across in `SO_UPDATE_ACCEPT_CONTEXT`.
+    pub fn accepted(&mut self, options: SocketOptions) -> &mut Self {
+        self.accepted = options;
+        self
+    }
+
+    /// How long a client may stay connected without sending before
+    /// [`TcpListener::accept_with_data`] turns it away. Ten seconds by
+    /// default.
+    ///
+    /// Measured with `SO_CONNECT_TIME`, which counts whole seconds, so the
+    /// limit is rounded up to whole seconds and to at least one.
+    pub fn silent_accept_limit(&mut self, limit: Duration) -> &mut Self {
+        self.silent_accept_limit = limit;
+        self
+    }
+
+    /// How many sockets [`TcpListener::recycle`] keeps for later accepts.
+    /// Sixty-four by default.
+    ///
+    /// Each pooled socket is an open, registered descriptor doing nothing, so
+    /// the pool only needs to cover the churn between accepts. Sockets
+    /// recycled beyond the limit are closed. Zero turns recycling off.
+    pub fn recycle_limit(&mut self, limit: usize) -> &mut Self {
+        self.recycle_limit = limit;
+        self
+    }
 }
 
 impl Default for TcpListenerOptions {
@@ -95,6 +162,15 @@ pub struct TcpListener<R: Registrar + Clone> {
     /// This is the reason the type is registrar-generic at all.
     registrar: R,
     socket: Socket,
+    /// Applied to each accepted socket.
+    accepted: SocketOptions,
+    /// How long [`TcpListener::accept_with_data`] waits on a silent client.
+    silent_accept_limit: Duration,
+    /// Disconnected sockets handed back by [`TcpListener::recycle`], used by
+    /// the next accepts before any new socket is created.
+    recycled: Mutex<Vec<ReusableSocket<R::Io>>>,
+    /// How many sockets `recycled` holds at most.
+    recycle_limit: usize,
     /// The bound address, cached at construction.
     ///
     /// Not just a convenience: the family it names decides what family each
@@ -129,6 +205,13 @@ impl<R: Registrar + Clone> TcpListener<R> {
                 .map_err(SocketError::from_win32)?;
         }
 
+        if let Some(reuse) = options.reuse_address {
+            socket.set_reuse_address(reuse)?;
+        }
+        if let Some(exclusive) = options.exclusive_address_use {
+            socket.set_exclusive_address_use(exclusive)?;
+        }
+
         socket.bind_to(addr).map_err(SocketError::from_win32)?;
         socket
             .listen_on(backlog_argument(options.backlog))
@@ -144,6 +227,10 @@ impl<R: Registrar + Clone> TcpListener<R> {
             socket,
             io,
             registrar: registrar.clone(),
+            accepted: options.accepted,
+            silent_accept_limit: options.silent_accept_limit,
+            recycled: Mutex::new(Vec::new()),
+            recycle_limit: options.recycle_limit,
             local,
         })
     }
@@ -165,18 +252,194 @@ impl<R: Registrar + Clone> TcpListener<R> {
     /// If the returned future is dropped before resolving, cancellation is
     /// requested on the listener and the half-built socket is closed.
     pub async fn accept(&self) -> Result<(TcpStream<R::Io>, SocketAddr), SocketError> {
+        self.accept_within(None).await
+    }
+
+    /// [`TcpListener::accept`], giving up after `timeout`.
+    ///
+    /// When `timeout` passes first, the `AcceptEx` is cancelled and this fails
+    /// with [`SocketError::TimedOut`]. Unlike dropping an `accept` future, the
+    /// cancellation is awaited: a connection accepted in the same instant is
+    /// returned rather than closed, so a timeout never loses a client.
+    pub async fn accept_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<(TcpStream<R::Io>, SocketAddr), SocketError> {
+        self.accept_within(Some(timeout)).await
+    }
+
+    /// Accept one connection together with the first bytes the client sends.
+    ///
+    /// `AcceptEx` completes only once the client has sent something, and
+    /// returns the stream, the peer and the count of bytes written to the
+    /// front of `buffer` in one completion — saving a read for protocols
+    /// where the client speaks first. At most 64 KiB of `buffer` is used. A
+    /// count of zero means the client closed its sending side without
+    /// sending.
+    ///
+    /// A client that connects and then sends nothing would hold this accept
+    /// indefinitely, so the pending connection is swept: once it has been
+    /// connected for the listener's
+    /// [`silent_accept_limit`](TcpListenerOptions::silent_accept_limit), it is
+    /// closed and a fresh accept is posted in its place. The silent client is
+    /// never returned, and this only resolves for a client that spoke, or on
+    /// an error.
+    ///
+    /// `buffer` comes back on every path, as it does from a read.
+    pub async fn accept_with_data<B: IoBufMut>(
+        &self,
+        mut buffer: B,
+    ) -> (
+        Result<(TcpStream<R::Io>, SocketAddr, usize), SocketError>,
+        B,
+    ) {
+        loop {
+            let (accepted, reused) = match self.accept_socket() {
+                Ok(socket) => socket,
+                Err(e) => return (Err(e), buffer),
+            };
+            // Read by the sweep while the operation owns the original.
+            let watched = accepted.clone();
+            let capacity = buffer.bytes_total();
+            let submitted = self.io.submit(AcceptSocket::with_data(
+                self.socket.clone(),
+                accepted,
+                capacity,
+            ));
+            let (OpResult(result, op), swept) =
+                swept_if_silent(submitted, &watched, self.silent_accept_limit).await;
+
+            if swept
+                && matches!(&result, Err(e) if e.code() == ERROR_OPERATION_ABORTED.to_hresult())
+            {
+                // The silent client's socket closes with `op` and `watched`,
+                // and a recycled one's registration with `reused`.
+                continue;
+            }
+
+            let (parts, received) = match op.finish_into(result, buffer.as_uninit()) {
+                Ok(finished) => finished,
+                Err(e) => return (Err(SocketError::from_win32(e)), buffer),
+            };
+            // SAFETY: `finish_into` initialised exactly `received` bytes at the
+            // front of the region `as_uninit` returned.
+            unsafe { buffer.set_init(received) };
+            let adopted = self.adopt_accepted(parts, reused);
+            return (
+                adopted.map(|(stream, peer)| (stream, peer, received)),
+                buffer,
+            );
+        }
+    }
+
+    /// Hand a disconnected socket back for a later accept to reuse.
+    ///
+    /// Accepts take recycled sockets before creating new ones, so a server
+    /// that ends each connection with
+    /// [`TcpStream::disconnect_for_reuse`] and recycles the result stops
+    /// creating and registering sockets once the pool covers its churn. A
+    /// recycled socket is never re-registered; it keeps the registration it
+    /// came with.
+    ///
+    /// The pool holds at most
+    /// [`recycle_limit`](TcpListenerOptions::recycle_limit) sockets. One
+    /// recycled into a full pool is closed, as a dropped [`ReusableSocket`]
+    /// is, and this still returns `Ok`: the caller is done with it either way.
+    ///
+    /// A socket of a different family than the listener is handed back as
+    /// the error, and so is one that made an outgoing connection: it is still
+    /// bound to its local port, and `AcceptEx` fails on a bound socket. Give
+    /// that one to [`TcpStream::connect_reusing`] instead.
+    pub fn recycle(&self, socket: ReusableSocket<R::Io>) -> Result<(), ReusableSocket<R::Io>> {
+        if socket.family() != family_of(&self.local) || socket.origin() != Origin::Accepted {
+            return Err(socket);
+        }
+        let mut recycled = self.recycled.lock().unwrap_or_else(PoisonError::into_inner);
+        if recycled.len() < self.recycle_limit {
+            recycled.push(socket);
+            return Ok(());
+        }
+        // Closed outside the lock: the submitter's teardown drains the
+        // socket's operations, and accepts should not wait on that.
+        drop(recycled);
+        drop(socket);
+        Ok(())
+    }
+
+    /// Keep `depth` accepts outstanding at once, yielding connections as
+    /// they arrive.
+    ///
+    /// A single [`accept`](TcpListener::accept) loop has no `AcceptEx`
+    /// posted while it handles each connection, so a burst waits in the
+    /// backlog for the loop to come round. [`Incoming`] keeps `depth`
+    /// pre-created sockets in posted accepts, and posts a replacement for
+    /// each completed one as soon as a poll finds it, before handing its
+    /// connection back. A `depth` of zero is treated as one.
+    pub fn incoming(&self, depth: usize) -> Incoming<'_, R> {
+        Accepting::new(self, depth, Self::submit_accept, Self::finish_accept)
+    }
+
+    async fn accept_within(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<(TcpStream<R::Io>, SocketAddr), SocketError> {
+        let PostedAccept { submitted, reused } = self.submit_accept()?;
+        self.finish_accept(within(submitted, timeout).await, reused)
+    }
+
+    /// Post one `AcceptEx`.
+    pub(super) fn submit_accept(&self) -> Result<PostedAccept<R::Io>, SocketError> {
+        let (accepted, reused) = self.accept_socket()?;
+        Ok(PostedAccept {
+            submitted: self
+                .io
+                .submit(AcceptSocket::new(self.socket.clone(), accepted)),
+            reused,
+        })
+    }
+
+    /// The socket an `AcceptEx` accepts onto, with its submitter if it is a
+    /// recycled one and so already registered.
+    fn accept_socket(&self) -> Result<(Socket, Option<R::Io>), SocketError> {
+        let recycled = self
+            .recycled
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+        if let Some(recycled) = recycled {
+            let (socket, submitter) = recycled.into_parts();
+            return Ok((socket, Some(submitter)));
+        }
         // `AcceptEx` does not create the socket; the caller must supply one of
         // the listener's family, unbound and unconnected.
-        let accepted =
+        let socket =
             Socket::new_overlapped(family_of(&self.local)).map_err(SocketError::from_win32)?;
+        Ok((socket, None))
+    }
 
-        let op = AcceptSocket::new(self.socket.clone(), accepted);
-        let OpResult(result, op) = self.io.submit(op).await;
+    /// Turn a completed `AcceptEx` into a registered stream.
+    pub(super) fn finish_accept(
+        &self,
+        completed: OpResult<usize, AcceptSocket>,
+        reused: Option<R::Io>,
+    ) -> Result<(TcpStream<R::Io>, SocketAddr), SocketError> {
+        let OpResult(result, op) = completed;
 
         // `finish` applies `SO_UPDATE_ACCEPT_CONTEXT` and copies both addresses
         // out of the provider's buffer before it is dropped.
         let parts = op.finish(result).map_err(SocketError::from_win32)?;
+        self.adopt_accepted(parts, reused)
+    }
 
+    /// Decode, configure and register what an `AcceptEx` produced.
+    ///
+    /// `reused` is the submitter a recycled socket already has; only a new
+    /// socket is registered.
+    fn adopt_accepted(
+        &self,
+        parts: AcceptedParts,
+        reused: Option<R::Io>,
+    ) -> Result<(TcpStream<R::Io>, SocketAddr), SocketError> {
         // The operation hands back encoded bytes rather than a `SocketAddr`,
         // so that it need know nothing about address families — see the
         // accept operation's docs. This is where the family knowledge lives:
@@ -187,22 +450,47 @@ impl<R: Registrar + Clone> TcpListener<R> {
             .to_socket_addr()
             .ok_or_else(|| SocketError::from_win32(unsupported_family()))?;
 
+        // After the context update, which is what makes the socket a TCP
+        // connection that per-connection options apply to.
+        self.accepted.apply(&parts.socket, self.local.is_ipv6())?;
+
         // LOAD-BEARING ORDERING — this is the whole of FR-027's mitigation, and
         // there is deliberately no guard object.
         //
         // Registering the accepted socket is the last fallible step. Everything
         // that could fail — the accept itself, the context update, the address
-        // decode — has already happened, so the window in which a registered
-        // socket exists with nothing owning it is empty by construction: the
-        // only statement between `register` and the `TcpStream` that owns the
-        // result is infallible. Registering earlier would open that window on
-        // every error path and would then need a guard to close it.
-        let io = self.registrar.register(parts.socket.as_handle())?;
-
-        Ok((TcpStream::from_parts(parts.socket, io), peer))
+        // decode, the accepted-socket options — has already happened, so the
+        // window in which a registered socket exists with nothing owning it is
+        // empty by construction: the only statement between `register` and the
+        // `TcpStream` that owns the result is infallible. Registering earlier
+        // would open that window on every error path and would then need a
+        // guard to close it.
+        //
+        // A recycled socket arrives registered already. On the error paths
+        // above its submitter is dropped with it, as a stream's would be.
+        let io = match reused {
+            Some(io) => io,
+            None => self.registrar.register(parts.socket.as_handle())?,
+        };
+
+        Ok((
+            TcpStream::from_parts(parts.socket, io, Origin::Accepted),
+            peer,
+        ))
     }
 }
 
+/// Connections from a [`TcpListener`] with several accepts outstanding.
+///
+/// Created by [`TcpListener::incoming`]. See [`Accepting`] for the ordering
+/// and for what dropping it does.
+pub type Incoming<'a, R> = Accepting<
+    'a,
+    TcpListener<R>,
+    <R as Registrar>::Io,
+    (TcpStream<<R as Registrar>::Io>, SocketAddr),
+>;
+
 impl<R: Registrar + Clone> Drop for TcpListener<R> {
     fn drop(&mut self) {
         // Ask the kernel to abandon any accept still in flight before the
@@ -253,8 +541,19 @@ mod tests {
     #[test]
     fn the_builder_records_what_it_was_given() {
         let mut options = TcpListenerOptions::new();
-        options.backlog(4).only_v6(false);
+        options
+            .backlog(4)
+            .only_v6(false)
+            .exclusive_address_use(true)
+            .accepted(*SocketOptions::new().nodelay(true))
+            .silent_accept_limit(Duration::from_secs(3))
+            .recycle_limit(2);
         assert_eq!(options.backlog, 4);
         assert!(!options.only_v6);
+        assert_eq!(options.exclusive_address_use, Some(true));
+        assert_eq!(options.reuse_address, None);
+        assert_eq!(options.accepted, *SocketOptions::new().nodelay(true));
+        assert_eq!(options.silent_accept_limit, Duration::from_secs(3));
+        assert_eq!(options.recycle_limit, 2);
     }
 }
@@ -4,19 +4,34 @@
 // license information.
 // ------------------------------------------------------------
 
-//! Asynchronous stream sockets: TCP, and `AF_UNIX`.
+//! Asynchronous sockets: TCP and `AF_UNIX` streams, and UDP.
 //!
-//! [`TcpListener`] and [`UnixListener`] accept connections with `AcceptEx`;
-//! [`TcpStream`] and [`UnixStream`] connect with `ConnectEx` and transfer with
-//! `WSARecv` / `WSASend`. All four are generic over the completion backend, so
-//! the same code runs on an owned [`Proactor`](crate::iocp::Proactor) or on
-//! the system thread pool.
+//! [`TcpListener`] and [`UnixListener`] accept connections with `AcceptEx`
+//! — one at a time, or several posted at once through [`Incoming`] and
+//! [`UnixIncoming`], which are both an [`Accepting`]. [`TcpStream`] and [`UnixStream`] connect with
+//! `ConnectEx` and transfer with `WSARecv` / `WSASend`. All four are generic
+//! over the completion backend, so the same code runs on an owned
+//! [`Proactor`](crate::iocp::Proactor) or on the system thread pool.
 //!
 //! The two families share every operation below the address: the same accept,
 //! connect, send and receive code, the same classification, the same
 //! ownership rules. What differs is what an address is, and that is confined
 //! to [`UnixSocketAddr`] and the two Unix types' constructors.
 //!
+//! [`UdpSocket`] sends and receives datagrams with `WSASendTo` /
+//! `WSARecvFrom`, under the same ownership and teardown rules. The stream
+//! invariants about end of stream and graceful close do not apply to it: a
+//! datagram socket has neither, and a zero-length receive is a zero-length
+//! datagram. It also joins multicast groups and, through `WSARecvMsg`,
+//! reports the local address and interface each datagram arrived on as a
+//! [`RecvMeta`].
+//!
+//! Names are resolved without blocking by [`lookup_host`], on overlapped
+//! `GetAddrInfoExW`, and [`TcpStream::connect_host`] connects by name on top
+//! of it, one address at a time. [`HappyEyeballs`] races the addresses
+//! instead, so a broken IPv6 path costs a fraction of a second rather than a
+//! connect timeout.
+//!
 //! # Invariants and obligations
 //!
 //! These hold for both families unless a bullet says otherwise.
@@ -40,6 +55,16 @@
 //!   the future if you need the buffer back. A dropped accept does not leak the
 //!   socket it was building: the operation owns it and closes it when the
 //!   driver finally releases the record.
+//!
+//!   The deadlines are the exception, and deliberately so:
+//!   [`TcpStream::connect_timeout`], [`TcpListener::accept_timeout`] and the
+//!   stream [read](TcpStream::set_read_timeout) and
+//!   [write](TcpStream::set_write_timeout) timeouts cancel the operation in
+//!   place and then await it, so the buffer or the accepted connection still
+//!   comes back, and the failure is [`SocketError::TimedOut`] rather than
+//!   [`SocketError::Cancelled`]. [`TcpListener::accept_with_data`] cancels in
+//!   place too, to turn away a client that connected but never sent, and
+//!   then accepts again rather than failing.
 //! * **Teardown is backend-specific.** Dropping a thread-pool-backed stream
 //!   cancels outstanding I/O and drains callbacks before releasing the socket.
 //!   A caller-driven stream requests cancellation and returns without driving
@@ -67,8 +92,18 @@
 //!   must be able to tell "I finished sending" from "I vanished", call
 //!   [`shutdown`](TcpStream::shutdown) with [`Shutdown::Write`] and read until
 //!   [`ReadOutcome::ClosedPeer`] before dropping. The crate will not do this
-//!   for you, because a graceful close can block and a `Drop` that blocks is
-//!   worse than one that is abrupt.
+//!   in `Drop`, because a graceful close can block and a `Drop` that blocks
+//!   is worse than one that is abrupt. It does it on request:
+//!   [`TcpStream::close_gracefully`] and [`UnixStream::close_gracefully`] run
+//!   that sequence with a deadline and a cap on what they drain —
+//!   [`DEFAULT_DRAIN_LIMIT`], or another through `close_gracefully_with` —
+//!   reset the connection if either runs out, and report which as a
+//!   [`CloseOutcome`].
+//!
+//!   [`TcpStream::disconnect_for_reuse`] is the exception that is not a
+//!   drop: `DisconnectEx` sends FIN and keeps the socket, registration and
+//!   all, as a [`ReusableSocket`] for [`TcpListener::recycle`] or
+//!   [`TcpStream::connect_reusing`].
 //! * **`closesocket`, not `CloseHandle`, and no [`crate::iocp::Handle`].** A
 //!   socket is closed exactly once, by the last [`Socket`] clone to drop, with
 //!   `closesocket`. `iocp::Handle` is not reused for sockets even though a
@@ -257,31 +292,48 @@
 //!
 //! # Out of scope
 //!
-//! UDP, `WSARecvFrom` / `WSASendTo`, `WSARecvMsg`, `TransmitFile` and vectored
-//! I/O. For `AF_UNIX` specifically: datagram sockets, which the platform does
+//! Vectored I/O, and sending with `WSASendMsg`. For
+//! `AF_UNIX` specifically: datagram sockets, which the platform does
 //! not offer — `SOCK_DGRAM` on `AF_UNIX` was measured to fail with
 //! `WSAEAFNOSUPPORT` — along with socket-pair helpers and any peer-credential
 //! analogue of `SO_PEERCRED`.
 
 mod addr;
+mod ancillary;
+mod close;
+mod deadline;
 mod error;
 mod ext;
+mod happy_eyeballs;
+mod incoming;
 mod init;
 mod listener;
 mod ops;
+mod options;
 mod outcome;
+mod resolve;
+mod reuse;
 mod socket;
 mod stream;
+mod udp;
 mod unix_addr;
 mod unix_listener;
 mod unix_stream;
 
-pub use error::SocketError;
-pub use listener::{TcpListener, TcpListenerOptions};
+pub use ancillary::{Ecn, RecvMeta};
+pub use close::{CloseOutcome, DEFAULT_DRAIN_LIMIT};
+pub use error::{ConnectError, SocketError};
+pub use happy_eyeballs::HappyEyeballs;
+pub use incoming::Accepting;
+pub use listener::{Incoming, TcpListener, TcpListenerOptions};
+pub use options::{Keepalive, SocketOptions};
+pub use resolve::{lookup_host, LookupHost};
+pub use reuse::ReusableSocket;
 pub use socket::Socket;
-pub use stream::TcpStream;
+pub use stream::{SendFileOptions, TcpStream};
+pub use udp::UdpSocket;
 pub use unix_addr::{UnixSocketAddr, UnixSocketAddrError, UNIX_PATH_MAX};
-pub use unix_listener::{UnixListener, UnixListenerOptions};
+pub use unix_listener::{UnixIncoming, UnixListener, UnixListenerOptions};
 pub use unix_stream::UnixStream;
 
 /// Re-exported so a caller need not reach into [`std::net`] for the one
@@ -306,6 +358,9 @@ pub type ThreadPoolTcpStream = TcpStream<crate::iocp::ThreadPoolIo>;
 /// while [`ThreadPoolTcpStream`] names `ThreadPoolIo`.
 pub type ThreadPoolTcpListener = TcpListener<crate::iocp::ThreadPool>;
 
+/// A [`UdpSocket`] on the system thread pool.
+pub type ThreadPoolUdpSocket = UdpSocket<crate::iocp::ThreadPoolIo>;
+
 /// A [`UnixStream`] on the system thread pool.
 pub type ThreadPoolUnixStream = UnixStream<crate::iocp::ThreadPoolIo>;
 
@@ -28,10 +28,22 @@
 //! not. The claim was inherited from the documented contract instead of being
 //! measured, which is the same mistake this crate has now made three times.)
 //!
-//! `dwReceiveDataLength` is zero, which is what makes this a pure
-//! accept: a non-zero value would make the operation wait for the client's
-//! first send, turning a completed TCP handshake into an unbounded wait that a
-//! silent client can hold open indefinitely.
+//! # Receiving with the accept
+//!
+//! `dwReceiveDataLength` is zero for [`AcceptSocket::new`], which is what
+//! makes it a pure accept. [`AcceptSocket::with_data`] asks for the client's
+//! first bytes as well, in a data area the provider places *before* the two
+//! address slots, so the output buffer is that area plus both slots in one
+//! allocation. The caller's own buffer cannot serve: it would need the slots
+//! appended, and the bytes are copied out to it instead, once, in
+//! [`AcceptSocket::finish_into`].
+//!
+//! A non-zero length changes what completes the operation. It is no longer
+//! the handshake but the client's first send, which turns a completed TCP
+//! connection into an unbounded wait that a silent client can hold open
+//! indefinitely. Nothing here defends against that; the listener does, by
+//! sweeping the pending socket's `SO_CONNECT_TIME` — see
+//! `TcpListener::accept_with_data`.
 //!
 //! # Why this operation knows nothing about address families
 //!
@@ -64,7 +76,7 @@ use windows::core::Result;
 use windows::Win32::Networking::WinSock::{SOCKADDR, SOCKADDR_STORAGE};
 use windows::Win32::System::IO::{CancelIoEx, OVERLAPPED};
 
-use crate::iocp::{win32_result, IntoInner, OpCode};
+use crate::iocp::{win32_result, IntoInner, OpCode, UninitSlice};
 
 use super::super::addr::SockAddrBytes;
 use super::super::ext::extensions;
@@ -76,6 +88,13 @@ use super::super::socket::Socket;
 /// serves `AF_UNIX` unchanged — the family that motivated checking.
 const ADDR_SLOT: usize = std::mem::size_of::<SOCKADDR_STORAGE>() + 16;
 
+/// The most a data-carrying accept asks for.
+///
+/// The first receive delivers what has arrived, not what was asked for, so a
+/// larger area would mostly go unused — and it is allocated per accept, on
+/// top of the caller's own buffer.
+pub(crate) const MAX_FIRST_CHUNK: usize = 64 * 1024;
+
 /// What a completed accept produced.
 pub(crate) struct AcceptedParts {
     pub(crate) socket: Socket,
@@ -90,12 +109,16 @@ pub(crate) struct AcceptedParts {
 pub(crate) struct AcceptSocket {
     listener: Socket,
     accepted: Socket,
-    /// The provider's address output. Written by the kernel for the whole of a
+    /// The provider's output: `data` bytes of the client's first send, then
+    /// the two address slots. Written by the kernel for the whole of a
     /// pending call, so it is owned by the operation.
-    buffer: Box<[u8; ADDR_SLOT * 2]>,
-    /// The `lpdwBytesReceived` slot. Zero for a pure accept, but the parameter
-    /// is not optional, and a stack slot would be a pointer the operation
-    /// cannot vouch for.
+    buffer: Box<[u8]>,
+    /// The `dwReceiveDataLength` argument. Zero for a pure accept.
+    data: usize,
+    /// The `lpdwBytesReceived` slot. Only meaningful for an inline
+    /// completion, and then equal to the completion's own count, but the
+    /// parameter is not optional, and a stack slot would be a pointer the
+    /// operation cannot vouch for.
     received: u32,
     /// The copied addresses, or the failure that stopped them being copied.
     ///
@@ -107,10 +130,19 @@ pub(crate) struct AcceptSocket {
 
 impl AcceptSocket {
     pub(crate) fn new(listener: Socket, accepted: Socket) -> Self {
+        Self::with_data(listener, accepted, 0)
+    }
+
+    /// An accept that completes with up to `data` bytes of the client's first
+    /// send, capped at [`MAX_FIRST_CHUNK`]. See the module docs for what that
+    /// costs.
+    pub(crate) fn with_data(listener: Socket, accepted: Socket, data: usize) -> Self {
+        let data = data.min(MAX_FIRST_CHUNK);
         AcceptSocket {
             listener,
             accepted,
-            buffer: Box::new([0u8; ADDR_SLOT * 2]),
+            buffer: vec![0u8; data + ADDR_SLOT * 2].into_boxed_slice(),
+            data,
             received: 0,
             outcome: None,
         }
@@ -118,7 +150,21 @@ impl AcceptSocket {
 
     /// The accepted socket, ready to use, with the peer's encoded address.
     pub(crate) fn finish(self, result: Result<usize>) -> Result<AcceptedParts> {
-        result?;
+        self.finish_into(result, UninitSlice::new(&mut []))
+            .map(|(parts, _)| parts)
+    }
+
+    /// [`finish`](AcceptSocket::finish), also copying the bytes received with
+    /// the connection into `data`.
+    ///
+    /// Returns how many were copied, which is every byte received when `data`
+    /// is at least as long as the length the operation was created with.
+    pub(crate) fn finish_into(
+        self,
+        result: Result<usize>,
+        data: &mut UninitSlice,
+    ) -> Result<(AcceptedParts, usize)> {
+        let received = result?;
         // The local address is copied too, and discarded: it is reachable
         // afterwards through `getsockname`, and requiring it to be there at
         // all is a check that the provider's buffer layout was what we
@@ -130,10 +176,17 @@ impl AcceptSocket {
             // would be worse than failing.
             None => return Err(windows::core::Error::from_thread()),
         };
-        Ok(AcceptedParts {
-            socket: self.accepted,
-            peer,
-        })
+        let copied = received.min(self.data).min(data.len());
+        // SAFETY: `copied` is within both the received prefix of `buffer` and
+        // `data`, and the two are distinct allocations.
+        unsafe { std::ptr::copy_nonoverlapping(self.buffer.as_ptr(), data.as_mut_ptr(), copied) };
+        Ok((
+            AcceptedParts {
+                socket: self.accepted,
+                peer,
+            },
+            copied,
+        ))
     }
 
     fn record_completion(&mut self, result: &Result<usize>) {
@@ -167,14 +220,15 @@ impl AcceptSocket {
         let mut peer_ptr: *mut SOCKADDR = std::ptr::null_mut();
         let mut peer_len: i32 = 0;
 
-        // SAFETY: the buffer is the one `AcceptEx` filled, with the same slot
-        // sizes it was given, and the four out-parameters are live locals. The
+        // SAFETY: the buffer is the one `AcceptEx` filled, with the same data
+        // length and slot sizes it was given, and the four out-parameters are
+        // live locals. The
         // pointers written back point into `self.buffer`, which is still owned
         // here.
         unsafe {
             get_addrs(
                 self.buffer.as_ptr().cast(),
-                0,
+                self.data as u32,
                 ADDR_SLOT as u32,
                 ADDR_SLOT as u32,
                 &mut local_ptr,
@@ -221,15 +275,17 @@ unsafe impl OpCode for AcceptSocket {
         };
 
         // SAFETY: the listener is listening, the accepted socket is a fresh
-        // unbound socket of the same family, the buffer is two slots of exactly
-        // the length declared, and `optr` is this operation's own `OVERLAPPED`.
+        // unbound socket of the same family, the buffer is the data area and
+        // two slots of exactly the lengths declared, and `optr` is this
+        // operation's own `OVERLAPPED`.
         let started = unsafe {
             accept_ex(
                 self.listener.raw(),
                 self.accepted.raw(),
                 self.buffer.as_mut_ptr().cast(),
-                // Zero: a pure accept. See the module docs.
-                0,
+                // Zero for a pure accept. See the module docs. At most
+                // `MAX_FIRST_CHUNK`, so the cast cannot truncate.
+                self.data as u32,
                 ADDR_SLOT as u32,
                 ADDR_SLOT as u32,
                 &mut self.received,
@@ -293,4 +349,25 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn a_data_area_is_capped_and_leaves_both_address_slots_behind_it() {
+        use windows::Win32::Networking::WinSock::INVALID_SOCKET;
+
+        let _guard = crate::net::socket_guard();
+        // SAFETY: `INVALID_SOCKET` is owned by nobody and is never closed.
+        let invalid = || unsafe { Socket::from_raw(INVALID_SOCKET) };
+
+        let pure = AcceptSocket::new(invalid(), invalid());
+        assert_eq!((pure.data, pure.buffer.len()), (0, ADDR_SLOT * 2));
+
+        let small = AcceptSocket::with_data(invalid(), invalid(), 512);
+        assert_eq!(small.buffer.len(), 512 + ADDR_SLOT * 2);
+
+        // A caller's buffer of any size must not become a `dwReceiveDataLength`
+        // that wraps in the `u32` cast.
+        let huge = AcceptSocket::with_data(invalid(), invalid(), usize::MAX / 2);
+        assert_eq!(huge.data, MAX_FIRST_CHUNK);
+        assert_eq!(huge.buffer.len(), MAX_FIRST_CHUNK + ADDR_SLOT * 2);
+    }
 }
@@ -0,0 +1,400 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! `WSARecvFrom` / `WSASendTo` / `WSARecvMsg` operations.
+//!
+//! The datagram counterparts of [`super::io`]'s `RecvSocket` / `SendSocket`,
+//! built the same way — descriptor, flags and address all owned by the
+//! operation, no byte-count pointer — and differing in what a result means.
+//!
+//! A datagram socket has no end of stream. A successful receive of zero bytes
+//! is a zero-length datagram, so nothing here goes near
+//! [`super::super::outcome`]'s `ClosedPeer` rule.
+//!
+//! A datagram longer than the buffer is truncated, the rest of it discarded,
+//! and the receive fails. The failure has two spellings: `WSAEMSGSIZE` when
+//! Winsock reports it inline, and `ERROR_MORE_DATA` when it arrives through
+//! the completion port as `STATUS_BUFFER_OVERFLOW`. [`RecvFromSocket::finish`]
+//! reports both as `WSAEMSGSIZE`, so callers match one code, and the bytes
+//! that did arrive are published into the buffer either way. The same holds
+//! for [`RecvMsgSocket`].
+
+use std::net::SocketAddr;
+use std::task::Poll;
+
+use windows::core::{Error, Result, HRESULT, PSTR};
+use windows::Win32::Foundation::ERROR_MORE_DATA;
+use windows::Win32::Networking::WinSock::{WSARecvFrom, WSASendTo, WSABUF, WSAEMSGSIZE, WSAMSG};
+use windows::Win32::System::IO::{CancelIoEx, OVERLAPPED};
+
+use crate::iocp::ops::sys::checked_u32_len;
+use crate::iocp::{win32_result, IntoInner, IoBuf, IoBufMut, OpCode};
+
+use super::super::addr::{unsupported_family, SockAddrBytes};
+use super::super::ancillary::{decode, RecvMeta, NATIVE_WORD};
+use super::super::ext::extensions;
+use super::super::socket::Socket;
+use super::io::OwnedWsaBuf;
+
+/// `WSAEMSGSIZE` as an error.
+fn message_size_error() -> Error {
+    Error::from_hresult(HRESULT::from_win32(WSAEMSGSIZE.0 as u32))
+}
+
+/// Whether `err` is either spelling of a truncated datagram.
+fn is_truncation(err: &Error) -> bool {
+    err.code() == ERROR_MORE_DATA.to_hresult() || err.code() == message_size_error().code()
+}
+
+/// Receive one datagram and the address it came from.
+pub(crate) struct RecvFromSocket<B: IoBufMut> {
+    socket: Socket,
+    buffer: B,
+    wsabuf: OwnedWsaBuf,
+    /// The in/out flags slot, owned for the same reason as `RecvSocket`'s.
+    flags: u32,
+    /// The sender's address and its length, both written by the kernel when
+    /// the receive completes — possibly long after `operate` returned.
+    from: SockAddrBytes,
+}
+
+impl<B: IoBufMut> RecvFromSocket<B> {
+    pub(crate) fn new(socket: Socket, buffer: B) -> Self {
+        RecvFromSocket {
+            socket,
+            buffer,
+            wsabuf: OwnedWsaBuf::zeroed(),
+            flags: 0,
+            from: SockAddrBytes::zeroed(),
+        }
+    }
+
+    fn record_completion(&mut self, result: &Result<usize>, transferred: usize) {
+        let n = match result {
+            Ok(n) => *n,
+            Err(e) if is_truncation(e) => transferred,
+            Err(_) => return,
+        };
+        let n = n.min(self.buffer.bytes_total());
+        // SAFETY: Windows reported initialising `n` bytes of the buffer this
+        // operation has owned for the whole call, and a truncated datagram
+        // still fills what it was given. Clamped to the capacity above.
+        unsafe { self.buffer.set_init(n) };
+    }
+
+    /// The datagram's length and sender.
+    pub(crate) fn finish(self, result: Result<usize>) -> (Result<(usize, SocketAddr)>, B) {
+        let result = match result {
+            Ok(n) => self
+                .from
+                .to_socket_addr()
+                .map(|from| (n, from))
+                .ok_or_else(unsupported_family),
+            Err(e) if is_truncation(&e) => Err(message_size_error()),
+            Err(e) => Err(e),
+        };
+        (result, self.buffer)
+    }
+}
+
+impl<B: IoBufMut> IntoInner for RecvFromSocket<B> {
+    type Inner = B;
+
+    fn into_inner(self) -> B {
+        self.buffer
+    }
+}
+
+// SAFETY: socket, descriptor, data pointer, flags and address slots are all
+// reached through `&mut self`, so they outlive the pinned operation. No
+// byte-count pointer is passed.
+unsafe impl<B: IoBufMut + Send> OpCode for RecvFromSocket<B> {
+    unsafe fn operate(&mut self, optr: *mut OVERLAPPED) -> Poll<Result<usize>> {
+        let slice = self.buffer.as_uninit();
+        let len = match checked_u32_len(slice.len()) {
+            Ok(len) => len,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        self.wsabuf = OwnedWsaBuf(WSABUF {
+            len,
+            buf: PSTR(slice.as_mut_ptr().cast::<u8>()),
+        });
+        self.flags = 0;
+        self.from = SockAddrBytes::zeroed();
+
+        // SAFETY: every pointer is derived from `&mut self`, whose allocation
+        // is retained until completion; a pending receive writes the address
+        // and its length after this frame is gone.
+        let started = unsafe {
+            WSARecvFrom(
+                self.socket.raw(),
+                std::slice::from_mut(&mut self.wsabuf.0),
+                None,
+                &mut self.flags,
+                Some(self.from.as_mut_ptr()),
+                Some(self.from.len_mut()),
+                Some(optr),
+                None,
+            )
+        };
+        // No Windows call may occur between `WSARecvFrom` and `win32_result`.
+        let result = unsafe { win32_result(started == 0, optr) };
+        if let Poll::Ready(ref ready) = result {
+            // SAFETY: `optr` is the `OVERLAPPED` just passed to `WSARecvFrom`;
+            // reading a field of it is not a Windows call.
+            let transferred = unsafe { (*optr).InternalHigh };
+            self.record_completion(ready, transferred);
+        }
+        result
+    }
+
+    unsafe fn cancel(&mut self, optr: *mut OVERLAPPED) -> Result<()> {
+        // SAFETY: `optr` is the same pointer passed to `operate`, and the
+        // socket is kept alive by this operation's own clone.
+        unsafe { CancelIoEx(self.socket.as_handle(), Some(optr)) }
+    }
+
+    unsafe fn on_complete_with(&mut self, result: &Result<usize>, transferred: usize) {
+        self.record_completion(result, transferred);
+    }
+}
+
+/// Room for every control record [`RecvMsgSocket`] can be asked for.
+///
+/// The largest set is `IPV6_PKTINFO`, a hop limit and an ECN value on a
+/// 64-bit platform: 40 + 24 + 24 bytes. Twice that leaves room for a record
+/// the stack adds unasked, which is skipped rather than allowed to push a
+/// wanted one out and set `MSG_CTRUNC`.
+const CONTROL_LEN: usize = 192;
+
+/// The control buffer, aligned as `WSACMSGHDR` requires.
+#[repr(C, align(8))]
+struct ControlBuffer([u8; CONTROL_LEN]);
+
+/// A `WSAMSG` that may cross threads.
+///
+/// Its pointers are self-references into the same operation — the address,
+/// the data descriptor and the control buffer — exactly like
+/// [`OwnedWsaBuf`]'s, and `Send` is sound for the same reason.
+struct OwnedWsaMsg(WSAMSG);
+
+// SAFETY: see the type's documentation; every pointer inside targets a field
+// of the operation that owns this value, and is rewritten in `operate`.
+unsafe impl Send for OwnedWsaMsg {}
+
+/// Receive one datagram with its sender and control records.
+pub(crate) struct RecvMsgSocket<B: IoBufMut> {
+    socket: Socket,
+    buffer: B,
+    wsabuf: OwnedWsaBuf,
+    /// Where the kernel writes the sender's address.
+    from: SockAddrBytes,
+    /// Where the kernel writes control records, zeroed before each start so
+    /// the decoder can read a zero length as the end.
+    control: ControlBuffer,
+    /// The message descriptor, pointing at the three fields above.
+    msg: OwnedWsaMsg,
+}
+
+impl<B: IoBufMut> RecvMsgSocket<B> {
+    pub(crate) fn new(socket: Socket, buffer: B) -> Self {
+        RecvMsgSocket {
+            socket,
+            buffer,
+            wsabuf: OwnedWsaBuf::zeroed(),
+            from: SockAddrBytes::zeroed(),
+            control: ControlBuffer([0; CONTROL_LEN]),
+            msg: OwnedWsaMsg(WSAMSG::default()),
+        }
+    }
+
+    fn record_completion(&mut self, result: &Result<usize>, transferred: usize) {
+        let n = match result {
+            Ok(n) => *n,
+            Err(e) if is_truncation(e) => transferred,
+            Err(_) => return,
+        };
+        let n = n.min(self.buffer.bytes_total());
+        // SAFETY: as for `RecvFromSocket::record_completion`.
+        unsafe { self.buffer.set_init(n) };
+    }
+
+    /// The datagram's length, sender and decoded control records.
+    ///
+    /// The control length is clamped to the buffer: on the completion path it
+    /// is whatever the kernel left in the descriptor, which is trusted no
+    /// further than the storage it describes. Past the records actually
+    /// written, the buffer is still zero.
+    pub(crate) fn finish(self, result: Result<usize>) -> (Result<RecvMeta>, B) {
+        let result = match result {
+            Ok(n) => {
+                let control_len = (self.msg.0.Control.len as usize).min(CONTROL_LEN);
+                let control = decode(&self.control.0[..control_len], NATIVE_WORD);
+                self.from
+                    .to_socket_addr()
+                    .map(|from| RecvMeta::new(n, from, control))
+                    .ok_or_else(unsupported_family)
+            }
+            Err(e) if is_truncation(&e) => Err(message_size_error()),
+            Err(e) => Err(e),
+        };
+        (result, self.buffer)
+    }
+}
+
+impl<B: IoBufMut> IntoInner for RecvMsgSocket<B> {
+    type Inner = B;
+
+    fn into_inner(self) -> B {
+        self.buffer
+    }
+}
+
+// SAFETY: the descriptor, the data pointer, the address and the control buffer
+// are all fields reached through `&mut self`, and `msg` points only at them, so
+// everything `WSARecvMsg` may write after returning outlives the pinned
+// operation. The byte-count out-parameter is null.
+unsafe impl<B: IoBufMut + Send> OpCode for RecvMsgSocket<B> {
+    unsafe fn operate(&mut self, optr: *mut OVERLAPPED) -> Poll<Result<usize>> {
+        let recv_msg = match extensions() {
+            Ok(ext) => ext.wsa_recv_msg,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        let slice = self.buffer.as_uninit();
+        let len = match checked_u32_len(slice.len()) {
+            Ok(len) => len,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        self.wsabuf = OwnedWsaBuf(WSABUF {
+            len,
+            buf: PSTR(slice.as_mut_ptr().cast::<u8>()),
+        });
+        self.from = SockAddrBytes::zeroed();
+        self.control.0.fill(0);
+        self.msg = OwnedWsaMsg(WSAMSG {
+            name: self.from.as_mut_ptr(),
+            namelen: self.from.len(),
+            lpBuffers: &mut self.wsabuf.0,
+            dwBufferCount: 1,
+            Control: WSABUF {
+                len: CONTROL_LEN as u32,
+                buf: PSTR(self.control.0.as_mut_ptr()),
+            },
+            dwFlags: 0,
+        });
+
+        // SAFETY: the pointer came from `WSAIoctl`'s extension lookup, the
+        // message and everything it points at are owned by this operation, and
+        // `optr` is this operation's own `OVERLAPPED`.
+        let started = unsafe {
+            recv_msg(
+                self.socket.raw(),
+                &mut self.msg.0,
+                std::ptr::null_mut(),
+                optr,
+                None,
+            )
+        };
+        // No Windows call may occur between `WSARecvMsg` and `win32_result`.
+        let result = unsafe { win32_result(started == 0, optr) };
+        if let Poll::Ready(ref ready) = result {
+            // SAFETY: as in `RecvFromSocket::operate`.
+            let transferred = unsafe { (*optr).InternalHigh };
+            self.record_completion(ready, transferred);
+        }
+        result
+    }
+
+    unsafe fn cancel(&mut self, optr: *mut OVERLAPPED) -> Result<()> {
+        // SAFETY: as for `RecvFromSocket::cancel`.
+        unsafe { CancelIoEx(self.socket.as_handle(), Some(optr)) }
+    }
+
+    unsafe fn on_complete_with(&mut self, result: &Result<usize>, transferred: usize) {
+        self.record_completion(result, transferred);
+    }
+}
+
+/// Send one datagram to an address.
+pub(crate) struct SendToSocket<B: IoBuf> {
+    socket: Socket,
+    buffer: B,
+    wsabuf: OwnedWsaBuf,
+    /// The destination. `WSASendTo` may read it while the send is pending.
+    to: SockAddrBytes,
+}
+
+impl<B: IoBuf> SendToSocket<B> {
+    pub(crate) fn new(socket: Socket, buffer: B, to: SockAddrBytes) -> Self {
+        SendToSocket {
+            socket,
+            buffer,
+            wsabuf: OwnedWsaBuf::zeroed(),
+            to,
+        }
+    }
+}
+
+impl<B: IoBuf> IntoInner for SendToSocket<B> {
+    type Inner = B;
+
+    fn into_inner(self) -> B {
+        self.buffer
+    }
+}
+
+// SAFETY: as for `RecvFromSocket` — socket, descriptor, payload and address
+// are all reached through `&mut self`, and no count pointer is passed.
+unsafe impl<B: IoBuf + Send> OpCode for SendToSocket<B> {
+    unsafe fn operate(&mut self, optr: *mut OVERLAPPED) -> Poll<Result<usize>> {
+        let len = match checked_u32_len(self.buffer.bytes_init()) {
+            Ok(len) => len,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        self.wsabuf = OwnedWsaBuf(WSABUF {
+            len,
+            buf: PSTR(self.buffer.stable_ptr() as *mut u8),
+        });
+
+        // SAFETY: the descriptor points at initialised bytes, and the address
+        // at storage of the length it reports, all owned by this operation.
+        let started = unsafe {
+            WSASendTo(
+                self.socket.raw(),
+                std::slice::from_ref(&self.wsabuf.0),
+                None,
+                0,
+                Some(self.to.as_ptr()),
+                self.to.len(),
+                Some(optr),
+                None,
+            )
+        };
+        // No Windows call may occur between `WSASendTo` and `win32_result`.
+        unsafe { win32_result(started == 0, optr) }
+    }
+
+    unsafe fn cancel(&mut self, optr: *mut OVERLAPPED) -> Result<()> {
+        // SAFETY: as for `RecvFromSocket::cancel`.
+        unsafe { CancelIoEx(self.socket.as_handle(), Some(optr)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_truncation_spellings_are_recognised() {
+        assert!(is_truncation(&message_size_error()));
+        assert!(is_truncation(&Error::from_hresult(
+            ERROR_MORE_DATA.to_hresult()
+        )));
+        assert!(!is_truncation(&Error::from_hresult(HRESULT::from_win32(
+            windows::Win32::Networking::WinSock::WSAECONNRESET.0 as u32
+        ))));
+    }
+}
@@ -0,0 +1,71 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! The `DisconnectEx` operation.
+//!
+//! `DisconnectEx` closes the connection but not the socket. With
+//! `TF_REUSE_SOCKET` the handle is then fit to be passed to `AcceptEx` or
+//! `ConnectEx` again, which skips creating, configuring and registering a new
+//! socket — and, for a registered one, the completion-port association that
+//! could not be made a second time anyway.
+//!
+//! The call sends FIN like `shutdown(SD_SEND)` and completes without waiting
+//! for the peer's close. When this side closes first, the connection sits in
+//! `TIME_WAIT`, and it is the *next* `AcceptEx` or `ConnectEx` on the handle
+//! that waits it out, not this operation.
+
+use std::task::Poll;
+
+use windows::core::Result;
+use windows::Win32::System::IO::{CancelIoEx, OVERLAPPED};
+
+use crate::iocp::{win32_result, IntoInner, OpCode};
+
+use super::super::ext::extensions;
+use super::super::socket::Socket;
+
+/// Disconnect a connected socket, keeping the handle.
+pub(crate) struct DisconnectSocket {
+    socket: Socket,
+    /// `TF_REUSE_SOCKET`, or zero to disconnect only.
+    flags: u32,
+}
+
+impl DisconnectSocket {
+    pub(crate) fn new(socket: Socket, flags: u32) -> Self {
+        DisconnectSocket { socket, flags }
+    }
+}
+
+impl IntoInner for DisconnectSocket {
+    type Inner = ();
+
+    fn into_inner(self) {}
+}
+
+// SAFETY: `operate` derives the socket from `self.socket`, reached through
+// `&mut self`, and passes no other pointer but `optr`.
+unsafe impl OpCode for DisconnectSocket {
+    unsafe fn operate(&mut self, optr: *mut OVERLAPPED) -> Poll<Result<usize>> {
+        let disconnect_ex = match extensions() {
+            Ok(ext) => ext.disconnect_ex,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+
+        // SAFETY: the pointer came from the extension lookup, the socket is
+        // kept alive by this operation's own clone, and `optr` is its own
+        // `OVERLAPPED`. The reserved word must be zero.
+        let started = unsafe { disconnect_ex(self.socket.raw(), optr, self.flags, 0) };
+        // No Windows call may occur between `DisconnectEx` and `win32_result`.
+        unsafe { win32_result(started.as_bool(), optr) }
+    }
+
+    unsafe fn cancel(&mut self, optr: *mut OVERLAPPED) -> Result<()> {
+        // SAFETY: `optr` is the same pointer passed to `operate`; the socket is
+        // kept alive by this operation's own `Socket` clone.
+        unsafe { CancelIoEx(self.socket.as_handle(), Some(optr)) }
+    }
+}
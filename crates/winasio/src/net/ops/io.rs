@@ -45,7 +45,7 @@ use super::super::socket::Socket;
 /// would make every operation holding one unspawnable on a multi-threaded
 /// executor.
 #[repr(transparent)]
-struct OwnedWsaBuf(WSABUF);
+pub(super) struct OwnedWsaBuf(pub(super) WSABUF);
 
 // SAFETY: the raw pointer inside is not an independent capability — it always
 // points into the buffer field of the same operation, which moves with it and
@@ -56,7 +56,7 @@ struct OwnedWsaBuf(WSABUF);
 unsafe impl Send for OwnedWsaBuf {}
 
 impl OwnedWsaBuf {
-    fn zeroed() -> Self {
+    pub(super) fn zeroed() -> Self {
         OwnedWsaBuf(WSABUF::default())
     }
 }
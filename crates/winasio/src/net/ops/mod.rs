@@ -8,4 +8,7 @@
 
 pub(crate) mod accept;
 pub(crate) mod connect;
+pub(crate) mod datagram;
+pub(crate) mod disconnect;
 pub(crate) mod io;
+pub(crate) mod transmit;
@@ -0,0 +1,157 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! The `TransmitFile` operation.
+//!
+//! `TransmitFile` sends a range of a file straight from the file system cache
+//! to a connected socket, optionally framed by a header and a trailer, without
+//! the bytes passing through user memory. The file range is addressed through
+//! the `OVERLAPPED` offset, exactly like a positional read, and the completion
+//! reports everything sent — header and trailer included.
+
+use std::task::Poll;
+
+use windows::core::Result;
+use windows::Win32::Foundation::HANDLE;
+use windows::Win32::Networking::WinSock::TRANSMIT_FILE_BUFFERS;
+use windows::Win32::System::IO::{CancelIoEx, OVERLAPPED};
+
+use crate::iocp::ops::sys::{checked_u32_len, set_offset};
+use crate::iocp::{win32_result, Handle, IntoInner, IoBuf, OpCode};
+
+use super::super::ext::extensions;
+use super::super::socket::Socket;
+
+/// The most one `TransmitFile` call may send from the file.
+///
+/// Documented as 2,147,483,646 bytes. A length of zero means "to the end of
+/// the file" rather than "nothing", so an empty range is expressed by passing
+/// no file at all.
+pub(crate) const MAX_TRANSMIT_LEN: u64 = i32::MAX as u64 - 1;
+
+/// The header/trailer descriptor, owned by the operation.
+///
+/// `TRANSMIT_FILE_BUFFERS` is read for the duration of a pending call, so it
+/// is a field rather than a stack value, and its raw pointers make it `!Send`.
+#[repr(transparent)]
+struct OwnedTransmitBuffers(TRANSMIT_FILE_BUFFERS);
+
+// SAFETY: the pointers inside only ever point into the `head` and `tail`
+// fields of the same operation, which move with it and are themselves `Send`.
+// Same reasoning as `OwnedWsaBuf`.
+unsafe impl Send for OwnedTransmitBuffers {}
+
+/// Send a file range, with optional header and trailer, over a socket.
+pub(crate) struct TransmitSocket<B: IoBuf> {
+    socket: Socket,
+    /// The file, or `None` when the range is empty.
+    file: Option<Handle>,
+    offset: u64,
+    len: u32,
+    head: Option<B>,
+    tail: Option<B>,
+    buffers: OwnedTransmitBuffers,
+    flags: u32,
+}
+
+impl<B: IoBuf> TransmitSocket<B> {
+    /// `len` must not exceed [`MAX_TRANSMIT_LEN`]; the caller checks it.
+    pub(crate) fn new(
+        socket: Socket,
+        file: Handle,
+        offset: u64,
+        len: u32,
+        head: Option<B>,
+        tail: Option<B>,
+        flags: u32,
+    ) -> Self {
+        TransmitSocket {
+            socket,
+            file: (len > 0).then_some(file),
+            offset,
+            len,
+            head,
+            tail,
+            buffers: OwnedTransmitBuffers(TRANSMIT_FILE_BUFFERS::default()),
+            flags,
+        }
+    }
+}
+
+impl<B: IoBuf> IntoInner for TransmitSocket<B> {
+    type Inner = (Option<B>, Option<B>);
+
+    fn into_inner(self) -> (Option<B>, Option<B>) {
+        (self.head, self.tail)
+    }
+}
+
+fn describe<B: IoBuf>(buffer: &Option<B>) -> Result<(*mut core::ffi::c_void, u32)> {
+    match buffer {
+        Some(b) if b.bytes_init() > 0 => Ok((
+            b.stable_ptr().cast_mut().cast(),
+            checked_u32_len(b.bytes_init())?,
+        )),
+        _ => Ok((std::ptr::null_mut(), 0)),
+    }
+}
+
+// SAFETY: `operate` derives the socket, the file handle, the descriptor and the
+// header and trailer pointers from fields reached through `&mut self`, so all
+// of them outlive a pending `TransmitFile`. Windows only reads the header and
+// trailer.
+unsafe impl<B: IoBuf + Send> OpCode for TransmitSocket<B> {
+    unsafe fn operate(&mut self, optr: *mut OVERLAPPED) -> Poll<Result<usize>> {
+        let transmit_file = match extensions() {
+            Ok(ext) => ext.transmit_file,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        let (head, head_len) = match describe(&self.head) {
+            Ok(d) => d,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        let (tail, tail_len) = match describe(&self.tail) {
+            Ok(d) => d,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        self.buffers.0 = TRANSMIT_FILE_BUFFERS {
+            Head: head,
+            HeadLength: head_len,
+            Tail: tail,
+            TailLength: tail_len,
+        };
+        let buffers = if head_len == 0 && tail_len == 0 {
+            std::ptr::null()
+        } else {
+            std::ptr::from_ref(&self.buffers.0)
+        };
+        let file = self.file.as_ref().map_or(HANDLE::default(), Handle::raw);
+
+        // SAFETY: the file offset lives in this operation's own `OVERLAPPED`.
+        unsafe { set_offset(optr, self.offset) };
+        // SAFETY: the pointer came from the extension lookup, every argument
+        // is owned by this operation, and `optr` is its own `OVERLAPPED`.
+        let started = unsafe {
+            transmit_file(
+                self.socket.raw(),
+                file,
+                self.len,
+                0,
+                optr,
+                buffers,
+                self.flags,
+            )
+        };
+        // No Windows call may occur between `TransmitFile` and `win32_result`.
+        unsafe { win32_result(started.as_bool(), optr) }
+    }
+
+    unsafe fn cancel(&mut self, optr: *mut OVERLAPPED) -> Result<()> {
+        // SAFETY: `optr` is the same pointer passed to `operate`; the socket is
+        // kept alive by this operation's own `Socket` clone.
+        unsafe { CancelIoEx(self.socket.as_handle(), Some(optr)) }
+    }
+}
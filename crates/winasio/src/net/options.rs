@@ -0,0 +1,441 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Typed socket options.
+//!
+//! Each option is a pair of methods on [`Socket`], so a caller holding any of
+//! this module's types can reach it through `socket()` without touching
+//! `setsockopt`. [`SocketOptions`] collects a set of them to apply in one go;
+//! [`super::TcpListenerOptions::accepted`] uses it to configure every
+//! accepted connection.
+//!
+//! Durations are carried in whole seconds, the unit Winsock takes for both
+//! linger and keepalive; anything finer is truncated, and anything larger than
+//! the option can hold saturates.
+
+use std::time::Duration;
+
+use windows::Win32::Networking::WinSock::{
+    IPPROTO_IP, IPPROTO_IPV6, IPPROTO_TCP, IPV6_UNICAST_HOPS, IP_TOS, IP_TTL, LINGER, SOL_SOCKET,
+    SO_EXCLUSIVEADDRUSE, SO_KEEPALIVE, SO_LINGER, SO_RCVBUF, SO_REUSEADDR, SO_SNDBUF, TCP_KEEPCNT,
+    TCP_KEEPIDLE, TCP_KEEPINTVL, TCP_NODELAY,
+};
+
+use super::error::SocketError;
+use super::socket::Socket;
+
+/// TCP keepalive timing, for [`Socket::set_tcp_keepalive`].
+///
+/// A field left unset keeps the socket's current value, which for a new
+/// socket is the system default — two hours idle, one second between probes,
+/// ten probes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Keepalive {
+    idle: Option<Duration>,
+    interval: Option<Duration>,
+    retries: Option<u32>,
+}
+
+impl Keepalive {
+    /// Keepalive with the system's timing.
+    pub fn new() -> Self {
+        Keepalive::default()
+    }
+
+    /// How long the connection must be idle before the first probe
+    /// (`TCP_KEEPIDLE`).
+    pub fn idle(&mut self, idle: Duration) -> &mut Self {
+        self.idle = Some(idle);
+        self
+    }
+
+    /// The time between unanswered probes (`TCP_KEEPINTVL`).
+    pub fn interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// How many unanswered probes end the connection (`TCP_KEEPCNT`).
+    pub fn retries(&mut self, retries: u32) -> &mut Self {
+        self.retries = Some(retries);
+        self
+    }
+}
+
+impl Socket {
+    /// Disable or enable Nagle's algorithm (`TCP_NODELAY`).
+    pub fn set_nodelay(&self, nodelay: bool) -> Result<(), SocketError> {
+        self.set_flag(IPPROTO_TCP.0, TCP_NODELAY, nodelay)
+    }
+
+    /// Whether Nagle's algorithm is disabled.
+    pub fn nodelay(&self) -> Result<bool, SocketError> {
+        self.flag(IPPROTO_TCP.0, TCP_NODELAY)
+    }
+
+    /// Turn TCP keepalive on or off with the system's timing
+    /// (`SO_KEEPALIVE`).
+    pub fn set_keepalive(&self, keepalive: bool) -> Result<(), SocketError> {
+        self.set_flag(SOL_SOCKET, SO_KEEPALIVE, keepalive)
+    }
+
+    /// Whether TCP keepalive is on.
+    pub fn keepalive(&self) -> Result<bool, SocketError> {
+        self.flag(SOL_SOCKET, SO_KEEPALIVE)
+    }
+
+    /// Turn TCP keepalive on with the given timing.
+    ///
+    /// The per-socket timing options need Windows 10 version 1709 or later;
+    /// older systems reject them with `WSAENOPROTOOPT`.
+    pub fn set_tcp_keepalive(&self, keepalive: &Keepalive) -> Result<(), SocketError> {
+        self.set_keepalive(true)?;
+        if let Some(idle) = keepalive.idle {
+            self.set_u32(IPPROTO_TCP.0, TCP_KEEPIDLE, whole_seconds(idle))?;
+        }
+        if let Some(interval) = keepalive.interval {
+            self.set_u32(IPPROTO_TCP.0, TCP_KEEPINTVL, whole_seconds(interval))?;
+        }
+        if let Some(retries) = keepalive.retries {
+            self.set_u32(IPPROTO_TCP.0, TCP_KEEPCNT, retries)?;
+        }
+        Ok(())
+    }
+
+    /// How long the connection must be idle before the first keepalive probe.
+    pub fn keepalive_idle(&self) -> Result<Duration, SocketError> {
+        self.get_u32(IPPROTO_TCP.0, TCP_KEEPIDLE)
+            .map(|secs| Duration::from_secs(secs.into()))
+    }
+
+    /// The time between unanswered keepalive probes.
+    pub fn keepalive_interval(&self) -> Result<Duration, SocketError> {
+        self.get_u32(IPPROTO_TCP.0, TCP_KEEPINTVL)
+            .map(|secs| Duration::from_secs(secs.into()))
+    }
+
+    /// How many unanswered keepalive probes end the connection.
+    pub fn keepalive_retries(&self) -> Result<u32, SocketError> {
+        self.get_u32(IPPROTO_TCP.0, TCP_KEEPCNT)
+    }
+
+    /// Set what closing the socket does with unsent data (`SO_LINGER`).
+    ///
+    /// `None`, the default, closes in the background: `closesocket` returns
+    /// at once and the system keeps sending. `Some(timeout)` waits up to
+    /// `timeout` for the data to go. `Some(Duration::ZERO)` is the abortive
+    /// close: unsent data is discarded and the peer receives a reset rather
+    /// than a FIN.
+    ///
+    /// A nonzero timeout makes the final `closesocket` blocking: whichever
+    /// drop releases the last reference to the socket waits, for up to
+    /// `timeout`, on the thread it runs on. Prefer a graceful shutdown — see
+    /// the [module docs](super) — over lingering in `Drop`.
+    pub fn set_linger(&self, linger: Option<Duration>) -> Result<(), SocketError> {
+        let value = match linger {
+            None => LINGER {
+                l_onoff: 0,
+                l_linger: 0,
+            },
+            Some(timeout) => LINGER {
+                l_onoff: 1,
+                l_linger: timeout.as_secs().min(u16::MAX.into()) as u16,
+            },
+        };
+        // SAFETY: `LINGER` is two `u16`s with no padding, and is the value
+        // `SO_LINGER` takes.
+        unsafe { self.set_option(SOL_SOCKET, SO_LINGER, &value) }.map_err(SocketError::from_win32)
+    }
+
+    /// The current `SO_LINGER` setting, as [`Socket::set_linger`] takes it.
+    pub fn linger(&self) -> Result<Option<Duration>, SocketError> {
+        // SAFETY: `LINGER` is plain data, valid for any bit pattern.
+        let value: LINGER =
+            unsafe { self.get_option(SOL_SOCKET, SO_LINGER) }.map_err(SocketError::from_win32)?;
+        Ok((value.l_onoff != 0).then(|| Duration::from_secs(value.l_linger.into())))
+    }
+
+    /// Set the size of the receive buffer (`SO_RCVBUF`).
+    pub fn set_recv_buffer_size(&self, size: usize) -> Result<(), SocketError> {
+        self.set_u32(SOL_SOCKET, SO_RCVBUF, buffer_size(size))
+    }
+
+    /// The size of the receive buffer.
+    pub fn recv_buffer_size(&self) -> Result<usize, SocketError> {
+        self.get_u32(SOL_SOCKET, SO_RCVBUF)
+            .map(|size| size as usize)
+    }
+
+    /// Set the size of the send buffer (`SO_SNDBUF`).
+    ///
+    /// Zero disables send buffering: a send completes only once the data is
+    /// acknowledged or on the wire, and overlapped sends are how throughput is
+    /// kept up.
+    pub fn set_send_buffer_size(&self, size: usize) -> Result<(), SocketError> {
+        self.set_u32(SOL_SOCKET, SO_SNDBUF, buffer_size(size))
+    }
+
+    /// The size of the send buffer.
+    pub fn send_buffer_size(&self) -> Result<usize, SocketError> {
+        self.get_u32(SOL_SOCKET, SO_SNDBUF)
+            .map(|size| size as usize)
+    }
+
+    /// Set the time-to-live of outgoing IPv4 packets (`IP_TTL`).
+    ///
+    /// Fails on an IPv6 socket; use [`Socket::set_unicast_hops_v6`] there.
+    pub fn set_ttl(&self, ttl: u32) -> Result<(), SocketError> {
+        self.set_u32(IPPROTO_IP.0, IP_TTL, ttl)
+    }
+
+    /// The time-to-live of outgoing IPv4 packets.
+    pub fn ttl(&self) -> Result<u32, SocketError> {
+        self.get_u32(IPPROTO_IP.0, IP_TTL)
+    }
+
+    /// Set the hop limit of outgoing IPv6 unicast packets
+    /// (`IPV6_UNICAST_HOPS`).
+    pub fn set_unicast_hops_v6(&self, hops: u32) -> Result<(), SocketError> {
+        self.set_u32(IPPROTO_IPV6.0, IPV6_UNICAST_HOPS, hops)
+    }
+
+    /// The hop limit of outgoing IPv6 unicast packets.
+    pub fn unicast_hops_v6(&self) -> Result<u32, SocketError> {
+        self.get_u32(IPPROTO_IPV6.0, IPV6_UNICAST_HOPS)
+    }
+
+    /// Set the type-of-service byte of outgoing IPv4 packets (`IP_TOS`).
+    ///
+    /// Windows records the value and reports it back, but by default does not
+    /// stamp it onto packets: DSCP marking is governed by QoS policy, not by
+    /// this option.
+    pub fn set_tos(&self, tos: u32) -> Result<(), SocketError> {
+        self.set_u32(IPPROTO_IP.0, IP_TOS, tos)
+    }
+
+    /// The type-of-service byte recorded for outgoing IPv4 packets.
+    pub fn tos(&self) -> Result<u32, SocketError> {
+        self.get_u32(IPPROTO_IP.0, IP_TOS)
+    }
+
+    /// Allow binding an address already in use (`SO_REUSEADDR`).
+    ///
+    /// Must be set before binding. On Windows this is weaker than it sounds
+    /// and riskier: it lets a second socket bind the *same* address and port
+    /// outright, not merely one in `TIME_WAIT`, and which of them receives
+    /// traffic is undefined. Prefer [`Socket::set_exclusive_address_use`] for
+    /// servers.
+    pub fn set_reuse_address(&self, reuse: bool) -> Result<(), SocketError> {
+        self.set_flag(SOL_SOCKET, SO_REUSEADDR, reuse)
+    }
+
+    /// Whether `SO_REUSEADDR` is set.
+    pub fn reuse_address(&self) -> Result<bool, SocketError> {
+        self.flag(SOL_SOCKET, SO_REUSEADDR)
+    }
+
+    /// Refuse to share the bound address with any other socket
+    /// (`SO_EXCLUSIVEADDRUSE`).
+    ///
+    /// Must be set before binding, and cannot be combined with
+    /// [`Socket::set_reuse_address`]: Winsock rejects the second of the two
+    /// with `WSAEINVAL`.
+    pub fn set_exclusive_address_use(&self, exclusive: bool) -> Result<(), SocketError> {
+        self.set_flag(SOL_SOCKET, SO_EXCLUSIVEADDRUSE, exclusive)
+    }
+
+    /// Whether `SO_EXCLUSIVEADDRUSE` is set.
+    pub fn exclusive_address_use(&self) -> Result<bool, SocketError> {
+        self.flag(SOL_SOCKET, SO_EXCLUSIVEADDRUSE)
+    }
+
+    fn set_flag(&self, level: i32, name: i32, value: bool) -> Result<(), SocketError> {
+        self.set_u32(level, name, u32::from(value))
+    }
+
+    fn flag(&self, level: i32, name: i32) -> Result<bool, SocketError> {
+        self.get_u32(level, name).map(|value| value != 0)
+    }
+
+    fn set_u32(&self, level: i32, name: i32, value: u32) -> Result<(), SocketError> {
+        self.set_u32_option(level, name, value)
+            .map_err(SocketError::from_win32)
+    }
+
+    fn get_u32(&self, level: i32, name: i32) -> Result<u32, SocketError> {
+        self.get_u32_option(level, name)
+            .map_err(SocketError::from_win32)
+    }
+}
+
+/// A set of options to apply to a socket together.
+///
+/// Only the options that were set are applied; everything else is left as the
+/// socket has it. Built with `&mut Self` setters like the crate's other
+/// option types.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SocketOptions {
+    nodelay: Option<bool>,
+    keepalive: Option<Keepalive>,
+    linger: Option<Option<Duration>>,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    ttl: Option<u32>,
+    tos: Option<u32>,
+}
+
+impl SocketOptions {
+    /// An empty set, which changes nothing.
+    pub fn new() -> Self {
+        SocketOptions::default()
+    }
+
+    /// See [`Socket::set_nodelay`].
+    pub fn nodelay(&mut self, nodelay: bool) -> &mut Self {
+        self.nodelay = Some(nodelay);
+        self
+    }
+
+    /// See [`Socket::set_tcp_keepalive`].
+    pub fn keepalive(&mut self, keepalive: Keepalive) -> &mut Self {
+        self.keepalive = Some(keepalive);
+        self
+    }
+
+    /// See [`Socket::set_linger`].
+    pub fn linger(&mut self, linger: Option<Duration>) -> &mut Self {
+        self.linger = Some(linger);
+        self
+    }
+
+    /// See [`Socket::set_recv_buffer_size`].
+    pub fn recv_buffer_size(&mut self, size: usize) -> &mut Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// See [`Socket::set_send_buffer_size`].
+    pub fn send_buffer_size(&mut self, size: usize) -> &mut Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// See [`Socket::set_ttl`]. Applied as `IPV6_UNICAST_HOPS` to an IPv6
+    /// socket.
+    pub fn ttl(&mut self, ttl: u32) -> &mut Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// See [`Socket::set_tos`]. Ignored for an IPv6 socket.
+    pub fn tos(&mut self, tos: u32) -> &mut Self {
+        self.tos = Some(tos);
+        self
+    }
+
+    /// Apply every option that was set to `socket`, stopping at the first
+    /// failure.
+    ///
+    /// `v6` says which IP level the TTL goes to, since an unbound socket
+    /// cannot report its own family.
+    pub(crate) fn apply(&self, socket: &Socket, v6: bool) -> Result<(), SocketError> {
+        if let Some(nodelay) = self.nodelay {
+            socket.set_nodelay(nodelay)?;
+        }
+        if let Some(keepalive) = &self.keepalive {
+            socket.set_tcp_keepalive(keepalive)?;
+        }
+        if let Some(linger) = self.linger {
+            socket.set_linger(linger)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        match (self.ttl, v6) {
+            (Some(ttl), false) => socket.set_ttl(ttl)?,
+            (Some(hops), true) => socket.set_unicast_hops_v6(hops)?,
+            (None, _) => {}
+        }
+        if let (Some(tos), false) = (self.tos, v6) {
+            socket.set_tos(tos)?;
+        }
+        Ok(())
+    }
+}
+
+/// A duration as the whole seconds a keepalive option takes.
+fn whole_seconds(duration: Duration) -> u32 {
+    duration.as_secs().min(u32::MAX.into()) as u32
+}
+
+/// A buffer size as the `int` the option takes, saturated.
+fn buffer_size(size: usize) -> u32 {
+    size.min(i32::MAX as usize) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_and_sizes_saturate_rather_than_wrap() {
+        assert_eq!(whole_seconds(Duration::from_millis(2_900)), 2);
+        assert_eq!(whole_seconds(Duration::from_secs(u64::MAX)), u32::MAX);
+        assert_eq!(buffer_size(64 * 1024), 64 * 1024);
+        assert_eq!(buffer_size(usize::MAX), i32::MAX as u32);
+    }
+
+    #[test]
+    fn options_round_trip_on_a_real_socket() {
+        let _guard = crate::net::socket::socket_guard();
+        let socket =
+            Socket::new_overlapped(windows::Win32::Networking::WinSock::AF_INET).expect("socket");
+
+        socket.set_nodelay(true).unwrap();
+        assert!(socket.nodelay().unwrap());
+
+        socket.set_linger(Some(Duration::ZERO)).unwrap();
+        assert_eq!(socket.linger().unwrap(), Some(Duration::ZERO));
+        socket.set_linger(None).unwrap();
+        assert_eq!(socket.linger().unwrap(), None);
+
+        socket
+            .set_tcp_keepalive(Keepalive::new().idle(Duration::from_secs(30)).retries(3))
+            .unwrap();
+        assert!(socket.keepalive().unwrap());
+        assert_eq!(socket.keepalive_idle().unwrap(), Duration::from_secs(30));
+        assert_eq!(socket.keepalive_retries().unwrap(), 3);
+
+        socket.set_ttl(42).unwrap();
+        assert_eq!(socket.ttl().unwrap(), 42);
+
+        socket.set_exclusive_address_use(true).unwrap();
+        assert!(socket.exclusive_address_use().unwrap());
+        assert!(
+            socket.set_reuse_address(true).is_err(),
+            "the two address-sharing options exclude each other"
+        );
+    }
+
+    #[test]
+    fn only_the_options_that_were_set_are_applied() {
+        let _guard = crate::net::socket::socket_guard();
+        let socket =
+            Socket::new_overlapped(windows::Win32::Networking::WinSock::AF_INET).expect("socket");
+        let before = socket.recv_buffer_size().unwrap();
+
+        let mut options = SocketOptions::new();
+        options.nodelay(true).linger(Some(Duration::from_secs(5)));
+        options.apply(&socket, false).unwrap();
+
+        assert!(socket.nodelay().unwrap());
+        assert_eq!(socket.linger().unwrap(), Some(Duration::from_secs(5)));
+        assert_eq!(socket.recv_buffer_size().unwrap(), before);
+    }
+}
@@ -0,0 +1,368 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Asynchronous name resolution with overlapped `GetAddrInfoExW`.
+//!
+//! `std`'s `ToSocketAddrs` blocks the calling thread for as long as the
+//! resolver takes, which on a cold cache or a dead DNS server is seconds.
+//! `GetAddrInfoExW` given an `OVERLAPPED` and a completion routine does not:
+//! it returns `WSA_IO_PENDING` and later calls the routine on a thread of its
+//! own, which then invokes the stored [`Waker`] — the same arrangement as
+//! [`crate::time`]'s timers, and with the same consequence for a caller
+//! driving a [`Proactor`](crate::iocp::Proactor): the routine is not a packet
+//! on the port, so the loop must either hand out wakers that interrupt its
+//! wait or poll with a timeout.
+//!
+//! # Ownership
+//!
+//! Everything the resolver may touch after the call returns — the
+//! `OVERLAPPED`, the result slot, the cancel handle, the name and the hints —
+//! lives in one reference-counted [`Query`]. The pending call holds a strong
+//! reference of its own, given up by the completion routine, so a dropped
+//! [`LookupHost`] cannot free memory the resolver is about to write. The
+//! result list is freed with `FreeAddrInfoExW` by whoever drops the last
+//! reference, which is why a lookup dropped *after* it completed but before
+//! it was polled again does not leak it either.
+
+use std::cell::UnsafeCell;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use windows::core::{Error, HRESULT, PCWSTR};
+use windows::Win32::Foundation::HANDLE;
+use windows::Win32::Networking::WinSock::{
+    FreeAddrInfoExW, GetAddrInfoExCancel, GetAddrInfoExW, ADDRINFOEXW, AF_UNSPEC, IPPROTO_TCP,
+    NS_ALL, SOCK_STREAM, WSA_IO_PENDING,
+};
+use windows::Win32::System::IO::OVERLAPPED;
+
+use super::addr::SockAddrBytes;
+use super::error::SocketError;
+use super::init::ensure_winsock;
+
+/// Resolve `name` to the socket addresses it names, each with `port`.
+///
+/// The addresses come back in the order the system resolver ranks them,
+/// which is the order a client should try them in; see
+/// [`TcpStream::connect_host`](super::TcpStream::connect_host). A literal
+/// address such as `"127.0.0.1"` or `"::1"` resolves to itself without a
+/// query.
+///
+/// A name that does not exist fails with [`SocketError::HostNotFound`].
+///
+/// The query starts on first poll. Dropping the future before it resolves
+/// cancels the query with `GetAddrInfoExCancel`; the resolver's own
+/// completion still runs afterwards, and releases what it was using then.
+pub fn lookup_host(name: &str, port: u16) -> LookupHost {
+    LookupHost {
+        name: name.encode_utf16().chain(Some(0)).collect(),
+        port,
+        query: None,
+    }
+}
+
+/// Future returned by [`lookup_host`].
+pub struct LookupHost {
+    /// The name as a NUL-terminated wide string, until the query takes it.
+    name: Vec<u16>,
+    port: u16,
+    query: Option<Arc<Query>>,
+}
+
+impl Future for LookupHost {
+    type Output = Result<Vec<SocketAddr>, SocketError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.query.is_none() {
+            match Query::start(std::mem::take(&mut this.name)) {
+                Ok(query) => this.query = Some(query),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+        let query = this.query.as_ref().expect("query was just started");
+        query.register(cx.waker());
+        // Checked after registering, so a completion in between is not
+        // missed: either the routine saw the waker, or this load sees the
+        // flag.
+        if !query.done.load(Ordering::Acquire) {
+            return Poll::Pending;
+        }
+        Poll::Ready(query.take(this.port))
+    }
+}
+
+impl Drop for LookupHost {
+    fn drop(&mut self) {
+        if let Some(query) = self.query.take() {
+            query.cancel();
+        }
+    }
+}
+
+impl std::fmt::Debug for LookupHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LookupHost")
+            .field("port", &self.port)
+            .field("started", &self.query.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// One overlapped `GetAddrInfoExW` call and everything it may write to.
+///
+/// `repr(C)` with the `OVERLAPPED` first, so the pointer the completion
+/// routine receives is also a pointer to the whole query.
+#[repr(C)]
+struct Query {
+    overlapped: UnsafeCell<OVERLAPPED>,
+    /// Written by the resolver; read only once `done` is set.
+    result: UnsafeCell<*mut ADDRINFOEXW>,
+    /// Written by `GetAddrInfoExW` before it returns; read only by `cancel`.
+    cancel: UnsafeCell<HANDLE>,
+    hints: ADDRINFOEXW,
+    name: Vec<u16>,
+    done: AtomicBool,
+    /// The routine's `dwError`, meaningful once `done` is set.
+    error: AtomicU32,
+    waker: Mutex<Option<Waker>>,
+}
+
+// SAFETY: the raw pointers are the resolver's result list and cancel handle,
+// neither tied to a thread. The cells are written by the resolver before it
+// sets `done` (with `Release`) and read afterwards (after `Acquire`), or
+// written by `GetAddrInfoExW` on the thread that then owns the `LookupHost`.
+unsafe impl Send for Query {}
+// SAFETY: as above.
+unsafe impl Sync for Query {}
+
+impl Query {
+    fn start(name: Vec<u16>) -> Result<Arc<Self>, SocketError> {
+        ensure_winsock().map_err(SocketError::from_win32)?;
+        let query = Arc::new(Query {
+            overlapped: UnsafeCell::new(OVERLAPPED::default()),
+            result: UnsafeCell::new(std::ptr::null_mut()),
+            cancel: UnsafeCell::new(HANDLE::default()),
+            // One entry per address rather than one per socket type and
+            // protocol: only stream sockets are connected from these.
+            hints: ADDRINFOEXW {
+                ai_family: AF_UNSPEC.0 as i32,
+                ai_socktype: SOCK_STREAM.0,
+                ai_protocol: IPPROTO_TCP.0,
+                ..Default::default()
+            },
+            name,
+            done: AtomicBool::new(false),
+            error: AtomicU32::new(0),
+            waker: Mutex::new(None),
+        });
+
+        // The pending call's own reference, given up by `lookup_complete`.
+        let pending = Arc::into_raw(Arc::clone(&query));
+        // SAFETY: every pointer is into `query`, which the reference above
+        // keeps alive until the completion routine has run. The routine is
+        // only ever handed the `OVERLAPPED`, which is at offset zero.
+        let code = unsafe {
+            GetAddrInfoExW(
+                PCWSTR(query.name.as_ptr()),
+                PCWSTR::null(),
+                NS_ALL,
+                None,
+                Some(std::ptr::addr_of!(query.hints)),
+                query.result.get(),
+                None,
+                Some(query.overlapped.get().cast_const()),
+                Some(lookup_complete),
+                Some(query.cancel.get()),
+            )
+        };
+        if code != WSA_IO_PENDING.0 {
+            // Finished inline, and in that case the routine is not called:
+            // take back its reference and publish the answer here.
+            //
+            // SAFETY: `pending` came from `Arc::into_raw` above and nothing
+            // else will reclaim it.
+            drop(unsafe { Arc::from_raw(pending) });
+            query.error.store(code as u32, Ordering::Relaxed);
+            query.done.store(true, Ordering::Release);
+        }
+        Ok(query)
+    }
+
+    fn register(&self, waker: &Waker) {
+        let mut slot = self.waker.lock().unwrap_or_else(PoisonError::into_inner);
+        match slot.as_ref() {
+            Some(current) if current.will_wake(waker) => {}
+            _ => *slot = Some(waker.clone()),
+        }
+    }
+
+    /// Take the answer. Only called once `done` has been observed.
+    fn take(&self, port: u16) -> Result<Vec<SocketAddr>, SocketError> {
+        let error = self.error.load(Ordering::Relaxed);
+        if error != 0 {
+            return Err(SocketError::from_win32(Error::from_hresult(
+                HRESULT::from_win32(error),
+            )));
+        }
+        // SAFETY: `done` was observed with `Acquire`, so the resolver has
+        // finished writing the slot, and the `LookupHost` holding `self` is
+        // the only reader.
+        let head = unsafe { std::mem::replace(&mut *self.result.get(), std::ptr::null_mut()) };
+        // SAFETY: `head` is the list the resolver returned, owned here until
+        // the free below.
+        let addrs = unsafe { collect(head, port) };
+        if !head.is_null() {
+            // SAFETY: as above; the slot was cleared so `Drop` will not free
+            // it again.
+            unsafe { FreeAddrInfoExW(Some(head.cast_const())) };
+        }
+        Ok(addrs)
+    }
+
+    /// Abandon the query, if it is still running.
+    fn cancel(&self) {
+        // Nobody will poll again, so forget the waker first: a completion
+        // after this does not wake a task that has moved on. One that took
+        // the waker already may still wake it once.
+        self.waker
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if self.done.load(Ordering::Acquire) {
+            return;
+        }
+        // SAFETY: the handle was written by `GetAddrInfoExW` before `start`
+        // returned. If the query completes between the check above and this
+        // call, the handle is already spent and the cancel fails with
+        // `WSA_INVALID_HANDLE`, which is the answer wanted.
+        unsafe { GetAddrInfoExCancel(self.cancel.get()) };
+    }
+}
+
+impl Drop for Query {
+    fn drop(&mut self) {
+        let head = *self.result.get_mut();
+        if !head.is_null() {
+            // SAFETY: the last reference is gone, so the resolver has
+            // finished with the list and nobody took it.
+            unsafe { FreeAddrInfoExW(Some(head.cast_const())) };
+        }
+    }
+}
+
+/// The resolver's completion routine.
+///
+/// # Safety
+///
+/// `overlapped` is the first field of a [`Query`] whose pending reference
+/// was leaked by [`Query::start`].
+unsafe extern "system" fn lookup_complete(error: u32, _bytes: u32, overlapped: *const OVERLAPPED) {
+    // SAFETY: see above; this reclaims the pending reference exactly once.
+    let query = unsafe { Arc::from_raw(overlapped.cast::<Query>()) };
+    query.error.store(error, Ordering::Relaxed);
+    query.done.store(true, Ordering::Release);
+    // Taken under the lock but woken after it is released: a waker that
+    // polls inline re-enters `register`, which takes the lock again. A
+    // `cancel` racing with this may miss the waker and let it be woken once
+    // more, which a stale waker tolerates.
+    let waker = query
+        .waker
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// Decode a result list, skipping families this crate does not speak.
+///
+/// # Safety
+///
+/// `head` is null or the head of a list returned by `GetAddrInfoExW`.
+unsafe fn collect(mut head: *const ADDRINFOEXW, port: u16) -> Vec<SocketAddr> {
+    let mut addrs = Vec::new();
+    while !head.is_null() {
+        // SAFETY: a non-null entry in a live result list.
+        let entry = unsafe { &*head };
+        let len = i32::try_from(entry.ai_addrlen).unwrap_or(i32::MAX);
+        // SAFETY: `ai_addr` holds `ai_addrlen` bytes of `sockaddr`.
+        let decoded = unsafe { SockAddrBytes::copy_from_raw(entry.ai_addr, len) }
+            .and_then(SockAddrBytes::to_socket_addr);
+        if let Some(mut addr) = decoded {
+            addr.set_port(port);
+            addrs.push(addr);
+        }
+        head = entry.ai_next;
+    }
+    addrs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iocp::Proactor;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn a_literal_resolves_to_itself_with_the_port() {
+        // `Proactor::block_on` polls with a timeout, which is what notices a
+        // completion routine that is not a packet on its port.
+        let proactor = Proactor::new().expect("proactor");
+        let addrs = proactor
+            .block_on(lookup_host("127.0.0.1", 8080))
+            .expect("resolve");
+        assert_eq!(
+            addrs,
+            [SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8080)]
+        );
+    }
+
+    #[test]
+    fn a_waker_that_polls_inline_does_not_deadlock() {
+        // Polls the lookup from inside the completion routine's wake, which
+        // re-enters `register` while the routine is still running.
+        struct Inline {
+            lookup: Mutex<Option<Pin<Box<LookupHost>>>>,
+            done: Mutex<std::sync::mpsc::Sender<()>>,
+        }
+        impl std::task::Wake for Inline {
+            fn wake(self: Arc<Self>) {
+                let waker = Waker::from(self.clone());
+                let mut cx = Context::from_waker(&waker);
+                let mut slot = self.lookup.lock().unwrap();
+                if let Some(lookup) = slot.as_mut() {
+                    if let Poll::Ready(result) = lookup.as_mut().poll(&mut cx) {
+                        result.expect("resolve");
+                        *slot = None;
+                        let _ = self.done.lock().unwrap().send(());
+                    }
+                }
+            }
+        }
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let inline = Arc::new(Inline {
+            lookup: Mutex::new(Some(Box::pin(lookup_host("localhost", 80)))),
+            done: Mutex::new(tx),
+        });
+        Waker::from(inline.clone()).wake();
+        rx.recv_timeout(std::time::Duration::from_secs(10))
+            .expect("the lookup finished");
+    }
+
+    #[test]
+    fn an_unpolled_lookup_starts_nothing() {
+        let lookup = lookup_host("localhost", 1);
+        assert!(lookup.query.is_none());
+        drop(lookup);
+    }
+}
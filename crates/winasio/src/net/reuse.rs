@@ -0,0 +1,102 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! A disconnected TCP socket kept for another connection.
+//!
+//! [`TcpStream::disconnect_for_reuse`](super::TcpStream::disconnect_for_reuse)
+//! ends a connection with `DisconnectEx(TF_REUSE_SOCKET)` and hands back the
+//! socket rather than closing it. The socket goes back into service through
+//! [`TcpListener::recycle`](super::TcpListener::recycle), which feeds it to a
+//! later `AcceptEx`, or [`TcpStream::connect_reusing`](super::TcpStream::connect_reusing).
+//!
+//! The submitter travels with the socket, and that is the point rather than a
+//! convenience. A handle can be associated with a completion port, or bound to
+//! a thread-pool I/O object, exactly once, so a reused socket cannot be
+//! registered again — it has to keep the registration it already has. This is
+//! also why a reused socket only goes back to a listener or a connect on the
+//! same backend type it came from: the submitter is part of its type.
+//!
+//! Where the socket came from matters too. `DisconnectEx` leaves an accepted
+//! socket unbound, which is what `AcceptEx` needs, but a socket that made an
+//! outgoing connection stays bound to its local port. Such a socket can only
+//! connect again, so [`TcpListener::recycle`](super::TcpListener::recycle)
+//! refuses it.
+
+use windows::Win32::Networking::WinSock::ADDRESS_FAMILY;
+
+use crate::iocp::Submitter;
+
+use super::socket::Socket;
+
+/// How a stream's connection was made, which decides where its socket can be
+/// reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Origin {
+    /// By `AcceptEx`. The socket comes back from `DisconnectEx` unbound.
+    Accepted,
+    /// By `ConnectEx`. The socket stays bound to its local port.
+    Connected,
+}
+
+/// A disconnected socket, still registered, ready for another connection.
+///
+/// Dropping it closes the socket. It still counts in
+/// [`live_sockets`](super::live_sockets) until then, exactly as a connected
+/// stream does; reuse moves the socket between owners without closing it.
+pub struct ReusableSocket<S: Submitter> {
+    /// Declared first so it is dropped first, as in `TcpListener`: the
+    /// submitter's teardown may cancel through the socket.
+    submitter: S,
+    socket: Socket,
+    /// The family it was created with. A disconnected socket has no address
+    /// to ask, and both `AcceptEx` and `ConnectEx` need one of a matching
+    /// family.
+    family: ADDRESS_FAMILY,
+    origin: Origin,
+}
+
+impl<S: Submitter> ReusableSocket<S> {
+    pub(crate) fn new(
+        socket: Socket,
+        submitter: S,
+        family: ADDRESS_FAMILY,
+        origin: Origin,
+    ) -> Self {
+        ReusableSocket {
+            submitter,
+            socket,
+            family,
+            origin,
+        }
+    }
+
+    pub(crate) fn family(&self) -> ADDRESS_FAMILY {
+        self.family
+    }
+
+    pub(crate) fn origin(&self) -> Origin {
+        self.origin
+    }
+
+    pub(crate) fn into_parts(self) -> (Socket, S) {
+        (self.socket, self.submitter)
+    }
+
+    /// The socket.
+    pub fn socket(&self) -> &Socket {
+        &self.socket
+    }
+}
+
+impl<S: Submitter> std::fmt::Debug for ReusableSocket<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReusableSocket")
+            .field("socket", &self.socket)
+            .field("family", &self.family.0)
+            .field("origin", &self.origin)
+            .finish_non_exhaustive()
+    }
+}
@@ -36,9 +36,10 @@ use std::sync::Arc;
 use windows::core::{Error, Result};
 use windows::Win32::Foundation::HANDLE;
 use windows::Win32::Networking::WinSock::{
-    bind, closesocket, getpeername, getsockname, listen, setsockopt, shutdown, WSASocketW,
-    ADDRESS_FAMILY, INVALID_SOCKET, IPPROTO_IPV6, IPPROTO_TCP, IPV6_V6ONLY, SD_BOTH, SD_RECEIVE,
-    SD_SEND, SOCKET, SOCK_STREAM, SOL_SOCKET, SO_UPDATE_ACCEPT_CONTEXT, SO_UPDATE_CONNECT_CONTEXT,
+    bind, closesocket, connect, getpeername, getsockname, getsockopt, listen, setsockopt, shutdown,
+    WSASocketW, ADDRESS_FAMILY, INVALID_SOCKET, IPPROTO_IPV6, IPPROTO_TCP, IPPROTO_UDP,
+    IPV6_V6ONLY, SD_BOTH, SD_RECEIVE, SD_SEND, SOCKET, SOCK_DGRAM, SOCK_STREAM, SOL_SOCKET,
+    SO_UPDATE_ACCEPT_CONTEXT, SO_UPDATE_CONNECT_CONTEXT, WINSOCK_SOCKET_TYPE,
     WSA_FLAG_NO_HANDLE_INHERIT, WSA_FLAG_OVERLAPPED,
 };
 
@@ -139,6 +140,15 @@ impl Socket {
     /// two named constructors below spell out which value each family wants,
     /// so no caller has to remember.
     pub(crate) fn new_overlapped_with(family: ADDRESS_FAMILY, protocol: i32) -> Result<Socket> {
+        Socket::create(family, SOCK_STREAM, protocol)
+    }
+
+    /// Create an overlapped UDP socket of the given family.
+    pub(crate) fn new_overlapped_udp(family: ADDRESS_FAMILY) -> Result<Socket> {
+        Socket::create(family, SOCK_DGRAM, IPPROTO_UDP.0)
+    }
+
+    fn create(family: ADDRESS_FAMILY, kind: WINSOCK_SOCKET_TYPE, protocol: i32) -> Result<Socket> {
         ensure_winsock()?;
         // `WSA_FLAG_NO_HANDLE_INHERIT` alongside the overlapped flag: without
         // it a child process spawned while a connection is open inherits the
@@ -151,7 +161,7 @@ impl Socket {
         let raw = unsafe {
             WSASocketW(
                 family.0 as i32,
-                SOCK_STREAM.0,
+                kind.0,
                 protocol,
                 None,
                 0,
@@ -198,6 +208,11 @@ impl Socket {
         Arc::strong_count(&self.0)
     }
 
+    /// Whether this is the only reference, so no operation still holds one.
+    pub(crate) fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.0) == 1
+    }
+
     /// Ask the kernel to abandon every operation this thread's process started
     /// on this socket.
     ///
@@ -226,6 +241,17 @@ impl Socket {
         last_error_if(rc)
     }
 
+    /// Fix the peer of a datagram socket with a plain, synchronous `connect`.
+    ///
+    /// For UDP this only records the default destination and filters what is
+    /// received; nothing goes on the wire, so there is nothing to overlap.
+    pub(crate) fn connect_to(&self, addr: SocketAddr) -> Result<()> {
+        let encoded = SockAddrBytes::from_socket_addr(addr);
+        // SAFETY: `encoded` outlives the call and describes `len()` valid bytes.
+        let rc = unsafe { connect(self.raw(), encoded.as_ptr(), encoded.len()) };
+        last_error_if(rc)
+    }
+
     pub(crate) fn listen_on(&self, backlog: i32) -> Result<()> {
         // SAFETY: a live socket bound by the caller.
         let rc = unsafe { listen(self.raw(), backlog) };
@@ -284,6 +310,73 @@ impl Socket {
         bytes.to_unix_addr().ok_or_else(unsupported_family)
     }
 
+    /// Set a socket option from a plain value.
+    ///
+    /// # Safety
+    ///
+    /// `T` must be the type the option expects, and must have no padding:
+    /// its bytes are handed to Winsock as they are, and reading padding would
+    /// read uninitialised memory.
+    pub(crate) unsafe fn set_option<T: Copy>(
+        &self,
+        level: i32,
+        name: i32,
+        value: &T,
+    ) -> Result<()> {
+        // SAFETY: the caller guarantees `T` has no padding, so every byte of
+        // `value` is initialised, and the slice does not outlive the borrow.
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                std::ptr::from_ref(value).cast::<u8>(),
+                std::mem::size_of::<T>(),
+            )
+        };
+        // SAFETY: a live socket, and a value of the size the option expects.
+        let rc = unsafe { setsockopt(self.raw(), level, name, Some(bytes)) };
+        last_error_if(rc)
+    }
+
+    /// Read a socket option into a plain value.
+    ///
+    /// # Safety
+    ///
+    /// `T` must be the type the option reports, and every bit pattern must be
+    /// a valid `T`: the value starts zeroed and Winsock may write fewer bytes
+    /// than its size.
+    pub(crate) unsafe fn get_option<T: Copy>(&self, level: i32, name: i32) -> Result<T> {
+        let mut value = std::mem::MaybeUninit::<T>::zeroed();
+        let mut len = std::mem::size_of::<T>() as i32;
+        // SAFETY: a live socket, and an output slot of the length passed.
+        let rc = unsafe {
+            getsockopt(
+                self.raw(),
+                level,
+                name,
+                windows::core::PSTR(value.as_mut_ptr().cast()),
+                &mut len,
+            )
+        };
+        last_error_if(rc)?;
+        // SAFETY: the slot was zeroed, Winsock wrote at most its size, and the
+        // caller guarantees any bit pattern is a valid `T`.
+        Ok(unsafe { value.assume_init() })
+    }
+
+    /// Read an option that reports a 4-byte boolean or integer.
+    ///
+    /// Some boolean options report a single byte; the zeroed slot and
+    /// little-endian layout make that read back as the same value.
+    pub(crate) fn get_u32_option(&self, level: i32, name: i32) -> Result<u32> {
+        // SAFETY: every bit pattern is a valid `u32`.
+        unsafe { self.get_option(level, name) }
+    }
+
+    /// Set an option that takes a 4-byte boolean or integer.
+    pub(crate) fn set_u32_option(&self, level: i32, name: i32, value: u32) -> Result<()> {
+        // SAFETY: `u32` has no padding, and these options take a `DWORD`.
+        unsafe { self.set_option(level, name, &value) }
+    }
+
     /// Set `IPV6_V6ONLY`, controlling whether an IPv6 listener also accepts
     /// IPv4 clients.
     pub(crate) fn set_only_v6(&self, only_v6: bool) -> Result<()> {
@@ -8,14 +8,27 @@
 
 use std::future::Future;
 use std::net::{Shutdown, SocketAddr};
+use std::ops::Range;
+use std::time::Duration;
 
-use crate::fs::ReadOutcome;
+use windows::core::{Error, HRESULT};
+use windows::Win32::Foundation::ERROR_INVALID_PARAMETER;
+use windows::Win32::Networking::WinSock::{TF_DISCONNECT, TF_REUSE_SOCKET, WSAEINVAL};
+
+use crate::fs::{File, ReadOutcome};
+use crate::io::{OwnedReadHalf, OwnedWriteHalf};
 use crate::iocp::{IntoInner, IoBuf, IoBufMut, OpResult, Registrar, Submitter};
 
-use super::addr::{family_of, wildcard_for, SockAddrBytes};
-use super::error::SocketError;
+use super::addr::{family_of, unsupported_family, wildcard_for, SockAddrBytes};
+use super::close::{close_gracefully, CloseOutcome, DEFAULT_DRAIN_LIMIT};
+use super::deadline::{within, Timeouts};
+use super::error::{ConnectError, SocketError};
 use super::ops::connect::ConnectSocket;
+use super::ops::disconnect::DisconnectSocket;
 use super::ops::io::{RecvSocket, SendSocket};
+use super::ops::transmit::{TransmitSocket, MAX_TRANSMIT_LEN};
+use super::resolve::lookup_host;
+use super::reuse::{Origin, ReusableSocket};
 use super::socket::Socket;
 
 struct Inner<S> {
@@ -23,6 +36,56 @@ struct Inner<S> {
     submitter: S,
 }
 
+/// What [`TcpStream::send_file_with`] does to the connection afterwards.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SendFileOptions {
+    disconnect: bool,
+    reuse_socket: bool,
+}
+
+impl SendFileOptions {
+    /// Leave the connection open, as [`TcpStream::send_file`] does.
+    pub fn new() -> Self {
+        SendFileOptions::default()
+    }
+
+    /// Start a transport-level disconnect once everything has been queued
+    /// (`TF_DISCONNECT`).
+    ///
+    /// The stream is unusable for further transfers afterwards.
+    pub fn disconnect(&mut self, disconnect: bool) -> &mut Self {
+        self.disconnect = disconnect;
+        self
+    }
+
+    /// Keep the socket handle reusable after the disconnect
+    /// (`TF_REUSE_SOCKET`).
+    ///
+    /// The handle can then be passed to `AcceptEx` or `ConnectEx` again
+    /// instead of being closed and recreated. Only meaningful together with
+    /// [`SendFileOptions::disconnect`]; asking for it alone is rejected with
+    /// `ERROR_INVALID_PARAMETER`, as Winsock would.
+    pub fn reuse_socket(&mut self, reuse_socket: bool) -> &mut Self {
+        self.reuse_socket = reuse_socket;
+        self
+    }
+
+    /// The `dwFlags` word, or `None` for a combination Winsock rejects.
+    fn flags(&self) -> Option<u32> {
+        if self.reuse_socket && !self.disconnect {
+            return None;
+        }
+        let mut flags = 0;
+        if self.disconnect {
+            flags |= TF_DISCONNECT;
+        }
+        if self.reuse_socket {
+            flags |= TF_REUSE_SOCKET;
+        }
+        Some(flags)
+    }
+}
+
 /// A connected TCP stream registered with a completion backend.
 ///
 /// Generic over the *submitter*, like [`crate::fs::File`]: a stream only ever
@@ -35,12 +98,17 @@ struct Inner<S> {
 /// references held by operations the kernel has not finished with.
 pub struct TcpStream<S: Submitter> {
     inner: Option<Inner<S>>,
+    timeouts: Timeouts,
+    /// Passed on by [`TcpStream::disconnect_for_reuse`].
+    origin: Origin,
 }
 
 impl<S: Submitter> TcpStream<S> {
-    pub(crate) fn from_parts(socket: Socket, submitter: S) -> Self {
+    pub(crate) fn from_parts(socket: Socket, submitter: S, origin: Origin) -> Self {
         TcpStream {
             inner: Some(Inner { socket, submitter }),
+            timeouts: Timeouts::default(),
+            origin,
         }
     }
 
@@ -80,6 +148,63 @@ impl<S: Submitter> TcpStream<S> {
             .map_err(SocketError::from_win32)
     }
 
+    /// Disable or enable Nagle's algorithm. See [`Socket::set_nodelay`]; the
+    /// other options are on [`TcpStream::socket`].
+    pub fn set_nodelay(&self, nodelay: bool) -> Result<(), SocketError> {
+        self.open().socket.set_nodelay(nodelay)
+    }
+
+    /// Whether Nagle's algorithm is disabled.
+    pub fn nodelay(&self) -> Result<bool, SocketError> {
+        self.open().socket.nodelay()
+    }
+
+    /// Set what dropping the stream does with unsent data. See
+    /// [`Socket::set_linger`], including why a nonzero timeout blocks.
+    pub fn set_linger(&self, linger: Option<Duration>) -> Result<(), SocketError> {
+        self.open().socket.set_linger(linger)
+    }
+
+    /// The current `SO_LINGER` setting.
+    pub fn linger(&self) -> Result<Option<Duration>, SocketError> {
+        self.open().socket.linger()
+    }
+
+    /// Set a deadline for each [`read`](TcpStream::read), or `None` for none.
+    ///
+    /// Applies to every read started afterwards, including each one the
+    /// whole-payload helpers and a split read half make; it bounds a single
+    /// read, not a whole `read_to_end`. A read that runs out of time is
+    /// cancelled and still resolves with its buffer, failing with an error
+    /// that [`SocketError::from_win32`] classifies as
+    /// [`SocketError::TimedOut`]. The connection stays usable: nothing was
+    /// received, so nothing was lost.
+    ///
+    /// A zero duration is refused, as `std` refuses it.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), SocketError> {
+        self.timeouts.set_read(timeout)
+    }
+
+    /// The current read timeout.
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.timeouts.read()
+    }
+
+    /// Set a deadline for each [`write`](TcpStream::write), or `None` for none.
+    ///
+    /// As [`TcpStream::set_read_timeout`], with one difference worth knowing:
+    /// a send cancelled part-way may have put some of its bytes on the wire,
+    /// and the count is not reported. After a write timeout, treat the
+    /// connection's framing as lost.
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> Result<(), SocketError> {
+        self.timeouts.set_write(timeout)
+    }
+
+    /// The current write timeout.
+    pub fn write_timeout(&self) -> Option<Duration> {
+        self.timeouts.write()
+    }
+
     /// Shut down one or both directions of the connection.
     ///
     /// This is `shutdown(2)`, not a close: it sends FIN (or stops receiving),
@@ -92,6 +217,41 @@ impl<S: Submitter> TcpStream<S> {
             .map_err(SocketError::from_win32)
     }
 
+    /// Close the connection in order, so the peer can tell it finished from
+    /// it vanishing.
+    ///
+    /// Shuts down writes, so the peer reads the end of the stream, then reads
+    /// and discards whatever the peer still sends until it closes its own
+    /// side, and only then closes the socket. The peer is given `timeout` to
+    /// close and [`DEFAULT_DRAIN_LIMIT`] bytes to send; past either, the
+    /// connection is reset instead, as dropping the stream under
+    /// `SO_LINGER {1, 0}` does. [`CloseOutcome`] says which happened.
+    ///
+    /// The [read timeout](TcpStream::set_read_timeout) does not apply to the
+    /// drain; `timeout` replaces it.
+    ///
+    /// An `Err` is the connection failing during the drain — typically the
+    /// peer resetting it, or a keepalive timing out. The socket is closed
+    /// either way.
+    pub async fn close_gracefully(self, timeout: Duration) -> Result<CloseOutcome, SocketError> {
+        self.close_gracefully_with(timeout, DEFAULT_DRAIN_LIMIT)
+            .await
+    }
+
+    /// [`close_gracefully`](TcpStream::close_gracefully), discarding at most
+    /// `drain_limit` bytes from the peer before resetting the connection.
+    pub async fn close_gracefully_with(
+        self,
+        timeout: Duration,
+        drain_limit: u64,
+    ) -> Result<CloseOutcome, SocketError> {
+        // The drain's deadline is `timeout`. With a read timeout left in
+        // place, its `WSAETIMEDOUT` could not be told from the connection's
+        // own, such as a keepalive failure.
+        self.timeouts.set_read(None)?;
+        close_gracefully(&self, &self.open().socket, timeout, drain_limit).await
+    }
+
     /// Start a read.
     ///
     /// The buffer is moved into the operation and handed back when it resolves,
@@ -99,17 +259,20 @@ impl<S: Submitter> TcpStream<S> {
     /// caller has reclaimed.
     ///
     /// If the returned future is dropped before resolving, cancellation is
-    /// requested and the buffer is not returned.
+    /// requested and the buffer is not returned. A
+    /// [read timeout](TcpStream::set_read_timeout) is different: it cancels
+    /// the read but still returns the buffer.
     pub fn read<B>(&self, buffer: B) -> impl Future<Output = OpResult<ReadOutcome, B>>
     where
         B: IoBufMut + Send,
     {
         let open = self.open();
+        let timeout = self.timeouts.read();
         let submitted = open
             .submitter
             .submit(RecvSocket::new(open.socket.clone(), buffer));
         async move {
-            let OpResult(result, op) = submitted.await;
+            let OpResult(result, op) = within(submitted, timeout).await;
             let (result, buffer) = op.finish(result);
             OpResult(result, buffer)
         }
@@ -127,15 +290,92 @@ impl<S: Submitter> TcpStream<S> {
         B: IoBuf + Send,
     {
         let open = self.open();
+        let timeout = self.timeouts.write();
         let submitted = open
             .submitter
             .submit(SendSocket::new(open.socket.clone(), buffer));
         async move {
-            let OpResult(result, op) = submitted.await;
+            let OpResult(result, op) = within(submitted, timeout).await;
             OpResult(result, op.into_inner())
         }
     }
 
+    /// Send `range` of `file`, preceded by `head` and followed by `tail`, with
+    /// `TransmitFile`.
+    ///
+    /// The file's bytes go from the system cache to the socket without being
+    /// copied through user memory. Resolves to the total number of bytes sent,
+    /// header and trailer included, and hands back both buffers. The file may
+    /// be registered with any backend; it is only read, and it stays open for
+    /// as long as the operation needs it.
+    ///
+    /// A range longer than 2,147,483,646 bytes — the most one `TransmitFile`
+    /// call may send — is rejected with `ERROR_INVALID_PARAMETER`, as is an
+    /// inverted one; split larger ranges across several calls. A range past
+    /// the end of the file sends only what the file holds.
+    ///
+    /// If the returned future is dropped before resolving, cancellation is
+    /// requested and the buffers are not returned.
+    pub fn send_file<F, B>(
+        &self,
+        file: &File<F>,
+        range: Range<u64>,
+        head: Option<B>,
+        tail: Option<B>,
+    ) -> impl Future<Output = OpResult<usize, (Option<B>, Option<B>)>>
+    where
+        F: Submitter,
+        B: IoBuf + Send,
+    {
+        self.send_file_with(file, range, head, tail, &SendFileOptions::new())
+    }
+
+    /// [`TcpStream::send_file`], disconnecting afterwards as `options` asks.
+    pub fn send_file_with<F, B>(
+        &self,
+        file: &File<F>,
+        range: Range<u64>,
+        head: Option<B>,
+        tail: Option<B>,
+        options: &SendFileOptions,
+    ) -> impl Future<Output = OpResult<usize, (Option<B>, Option<B>)>>
+    where
+        F: Submitter,
+        B: IoBuf + Send,
+    {
+        let len = range
+            .end
+            .checked_sub(range.start)
+            .filter(|len| *len <= MAX_TRANSMIT_LEN);
+        let submitted = match (len, options.flags()) {
+            (Some(len), Some(flags)) => {
+                let open = self.open();
+                Ok(open.submitter.submit(TransmitSocket::new(
+                    open.socket.clone(),
+                    file.shared_handle(),
+                    range.start,
+                    len as u32,
+                    head,
+                    tail,
+                    flags,
+                )))
+            }
+            _ => Err((head, tail)),
+        };
+        async move {
+            match submitted {
+                Ok(submitted) => {
+                    let OpResult(result, op) = submitted.await;
+                    OpResult(result, op.into_inner())
+                }
+                Err(buffers) => OpResult(
+                    Err(Error::from_hresult(ERROR_INVALID_PARAMETER.to_hresult())),
+                    buffers,
+                ),
+            }
+        }
+    }
+
     /// Read until the buffer is full, the peer closes, or a read fails.
     pub fn read_exact<B>(
         &self,
@@ -162,6 +402,60 @@ impl<S: Submitter> TcpStream<S> {
     {
         crate::io::write_all(self, 0, buffer)
     }
+
+    /// End the connection but keep the socket, for another connection.
+    ///
+    /// Issues `DisconnectEx(TF_REUSE_SOCKET)`: the peer sees a graceful
+    /// close, and the socket comes back as a [`ReusableSocket`] — still open,
+    /// still registered — for [`TcpListener::recycle`](super::TcpListener::recycle)
+    /// or [`TcpStream::connect_reusing`]. That saves creating, configuring
+    /// and registering a socket per connection.
+    ///
+    /// Whichever side closes first holds the connection in `TIME_WAIT`. When
+    /// that is this side, the disconnect itself completes at once, but the
+    /// socket's next accept or connect waits the state out, which by default
+    /// is minutes. Reuse pays off when the peer closes first — read to
+    /// [`ReadOutcome::ClosedPeer`], then disconnect.
+    ///
+    /// Nothing else may be using the socket: an operation still in flight, a
+    /// dropped one the kernel has not finished with, or a clone taken
+    /// through [`TcpStream::socket`] fails this with `WSAEINVAL`, as
+    /// `DisconnectEx` would with I/O pending. On any failure the stream is
+    /// closed as if dropped.
+    pub async fn disconnect_for_reuse(mut self) -> Result<ReusableSocket<S>, SocketError> {
+        // Read while the socket still has an address; a disconnected one has
+        // none to ask.
+        let family = family_of(&self.local_addr()?);
+        if !self.open().socket.is_unique() {
+            return Err(SocketError::from_win32(Error::from_hresult(
+                HRESULT::from_win32(WSAEINVAL.0 as u32),
+            )));
+        }
+        // The socket stays in `self` until the disconnect succeeds, so a
+        // failure, or this future being dropped, tears it down the way `Drop`
+        // does: operations cancelled, then the submitter, then the socket.
+        let open = self.open();
+        let op = DisconnectSocket::new(open.socket.clone(), TF_REUSE_SOCKET);
+        let OpResult(result, _) = open.submitter.submit(op).await;
+        result.map_err(SocketError::from_win32)?;
+        let inner = self.inner.take().expect("checked open above");
+        Ok(ReusableSocket::new(
+            inner.socket,
+            inner.submitter,
+            family,
+            self.origin,
+        ))
+    }
+
+    /// Split into a read half and a write half that can be moved to different
+    /// tasks.
+    ///
+    /// Dropping the write half shuts the socket down for sending, so the peer
+    /// reads a graceful close while the read half keeps receiving. The halves
+    /// go back together with [`OwnedReadHalf::reunite`].
+    pub fn into_split(self) -> (OwnedReadHalf<Self>, OwnedWriteHalf<Self>) {
+        crate::io::split(self)
+    }
 }
 
 impl<S: Submitter> TcpStream<S> {
@@ -171,6 +465,62 @@ impl<S: Submitter> TcpStream<S> {
     /// first. That is not a convenience: `ConnectEx` fails with `WSAEINVAL` on
     /// an unbound socket, and the error names nothing that would lead you here.
     pub async fn connect<R>(registrar: &R, addr: SocketAddr) -> Result<Self, SocketError>
+    where
+        R: Registrar<Io = S>,
+    {
+        Self::connect_within(registrar, addr, None).await
+    }
+
+    /// [`TcpStream::connect`], giving up after `timeout`.
+    ///
+    /// Without a deadline a connect to a host that drops the SYN waits out
+    /// the system's retransmissions, about 21 seconds by default. When
+    /// `timeout` passes first, the `ConnectEx` is cancelled and this fails
+    /// with [`SocketError::TimedOut`]; a connect that completed in the same
+    /// instant is kept.
+    pub async fn connect_timeout<R>(
+        registrar: &R,
+        addr: SocketAddr,
+        timeout: Duration,
+    ) -> Result<Self, SocketError>
+    where
+        R: Registrar<Io = S>,
+    {
+        Self::connect_within(registrar, addr, Some(timeout)).await
+    }
+
+    /// [`TcpStream::connect`] on a socket kept by
+    /// [`TcpStream::disconnect_for_reuse`], instead of a new one.
+    ///
+    /// The socket keeps its registration, so this needs no registrar. It must
+    /// be of `addr`'s family, or this fails with `WSAEAFNOSUPPORT`. A socket
+    /// that last made an outgoing connection is still bound to its old local
+    /// port and connects from it again; that port may still be in
+    /// `TIME_WAIT` towards the same peer, in which case the connect waits it
+    /// out.
+    pub async fn connect_reusing(
+        socket: ReusableSocket<S>,
+        addr: SocketAddr,
+    ) -> Result<Self, SocketError> {
+        if socket.family() != family_of(&addr) {
+            return Err(SocketError::from_win32(unsupported_family()));
+        }
+        let (socket, submitter) = socket.into_parts();
+        // An accepted socket comes back from `DisconnectEx` unbound, and a
+        // connected one still bound; `WSAEINVAL` is the latter, and fine.
+        if let Err(e) = socket.bind_to(wildcard_for(&addr)) {
+            if e.code() != HRESULT::from_win32(WSAEINVAL.0 as u32) {
+                return Err(SocketError::from_win32(e));
+            }
+        }
+        Self::connect_on(socket, submitter, addr, None).await
+    }
+
+    async fn connect_within<R>(
+        registrar: &R,
+        addr: SocketAddr,
+        timeout: Option<Duration>,
+    ) -> Result<Self, SocketError>
     where
         R: Registrar<Io = S>,
     {
@@ -185,11 +535,49 @@ impl<S: Submitter> TcpStream<S> {
         // `socket` is still local and its `Drop` closes it.
         let submitter = registrar.register(socket.as_handle())?;
 
+        Self::connect_on(socket, submitter, addr, timeout).await
+    }
+
+    /// Connect a bound, registered socket.
+    async fn connect_on(
+        socket: Socket,
+        submitter: S,
+        addr: SocketAddr,
+        timeout: Option<Duration>,
+    ) -> Result<Self, SocketError> {
         let op = ConnectSocket::new(socket.clone(), SockAddrBytes::from_socket_addr(addr));
-        let OpResult(result, op) = submitter.submit(op).await;
+        let OpResult(result, op) = within(submitter.submit(op), timeout).await;
         op.finish(result).map_err(SocketError::from_win32)?;
 
-        Ok(TcpStream::from_parts(socket, submitter))
+        Ok(TcpStream::from_parts(socket, submitter, Origin::Connected))
+    }
+
+    /// Resolve `host` with [`lookup_host`](super::lookup_host) and connect to
+    /// the first address that accepts.
+    ///
+    /// Addresses are tried one at a time, in the resolver's order, each with
+    /// [`TcpStream::connect`]. Nothing is raced: a host whose first address
+    /// silently drops packets costs the full connect timeout before the next
+    /// is tried. Every failure is kept, so an error names each address and
+    /// why it failed — see [`ConnectError`].
+    pub async fn connect_host<R>(registrar: &R, host: &str, port: u16) -> Result<Self, ConnectError>
+    where
+        R: Registrar<Io = S>,
+    {
+        let addrs = lookup_host(host, port)
+            .await
+            .map_err(ConnectError::Resolve)?;
+        if addrs.is_empty() {
+            return Err(ConnectError::NoAddresses);
+        }
+        let mut attempts = Vec::with_capacity(addrs.len());
+        for addr in addrs {
+            match TcpStream::connect(registrar, addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => attempts.push((addr, e)),
+            }
+        }
+        Err(ConnectError::Attempts(attempts))
     }
 }
 
@@ -215,3 +603,26 @@ impl<S: Submitter> std::fmt::Debug for TcpStream<S> {
             .finish_non_exhaustive()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_file_flags_follow_the_options() {
+        assert_eq!(SendFileOptions::new().flags(), Some(0));
+
+        let mut options = SendFileOptions::new();
+        options.disconnect(true);
+        assert_eq!(options.flags(), Some(TF_DISCONNECT));
+        options.reuse_socket(true);
+        assert_eq!(options.flags(), Some(TF_DISCONNECT | TF_REUSE_SOCKET));
+    }
+
+    #[test]
+    fn reuse_without_disconnect_is_refused() {
+        let mut options = SendFileOptions::new();
+        options.reuse_socket(true);
+        assert_eq!(options.flags(), None);
+    }
+}
@@ -0,0 +1,412 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! A UDP socket.
+
+use std::future::Future;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use windows::Win32::Networking::WinSock::{
+    IPPROTO_IP, IPPROTO_IPV6, IPV6_ADD_MEMBERSHIP, IPV6_DROP_MEMBERSHIP, IPV6_HOPLIMIT, IPV6_MREQ,
+    IPV6_MULTICAST_HOPS, IPV6_MULTICAST_IF, IPV6_MULTICAST_LOOP, IPV6_PKTINFO, IPV6_RECVECN,
+    IP_ADD_MEMBERSHIP, IP_DROP_MEMBERSHIP, IP_MREQ, IP_MULTICAST_IF, IP_MULTICAST_LOOP,
+    IP_MULTICAST_TTL, IP_PKTINFO, IP_RECVECN, IP_RECVTTL, SOL_SOCKET, SO_BROADCAST,
+};
+
+use crate::iocp::{IntoInner, IoBuf, IoBufMut, OpResult, Registrar, Submitter};
+
+use super::addr::{family_of, in6_addr, in_addr, SockAddrBytes};
+use super::ancillary::RecvMeta;
+use super::error::SocketError;
+use super::ops::datagram::{RecvFromSocket, RecvMsgSocket, SendToSocket};
+use super::ops::io::SendSocket;
+use super::socket::Socket;
+
+struct Inner<S> {
+    socket: Socket,
+    submitter: S,
+}
+
+/// A UDP socket registered with a completion backend.
+///
+/// Generic over the submitter like [`super::TcpStream`], and torn down the
+/// same way: dropping it requests cancellation of anything in flight, and the
+/// socket closes once the last operation holding it has completed.
+///
+/// Every receive returns exactly one datagram. A zero-length datagram is a
+/// successful receive of zero bytes, not an end of stream — there is none. A
+/// datagram larger than the buffer fails with `WSAEMSGSIZE`; the part that
+/// fitted is in the returned buffer and the rest is lost.
+///
+/// # `WSAECONNRESET` on receive
+///
+/// When a datagram this socket sent draws an ICMP port-unreachable reply,
+/// Windows fails the *next receive* with `WSAECONNRESET`, connected or not.
+/// The socket is still usable; the error only reports that some earlier
+/// destination had nobody listening. Treat it as a per-datagram failure and
+/// receive again.
+///
+/// # Multicast and packet information
+///
+/// Group membership, broadcast permission and the ancillary data
+/// [`UdpSocket::recv_msg`] reports are socket options, set synchronously. The
+/// `_v4` and `_v6` methods act on the IP level of that family, and fail with
+/// `WSAEINVAL` or `WSAENOPROTOOPT` on a socket of the other one. The
+/// `set_recv_*` switches pick the level from the family the socket was bound
+/// with.
+pub struct UdpSocket<S: Submitter> {
+    inner: Option<Inner<S>>,
+    /// Whether the socket is `AF_INET6`, for the options that exist on both
+    /// levels under different names.
+    v6: bool,
+}
+
+impl<S: Submitter> UdpSocket<S> {
+    /// Bind a socket to `addr` and register it with `registrar`.
+    ///
+    /// Port zero asks for an ephemeral port; [`UdpSocket::local_addr`]
+    /// reports the one chosen.
+    pub fn bind<R>(registrar: &R, addr: SocketAddr) -> Result<Self, SocketError>
+    where
+        R: Registrar<Io = S>,
+    {
+        let socket =
+            Socket::new_overlapped_udp(family_of(&addr)).map_err(SocketError::from_win32)?;
+        socket.bind_to(addr).map_err(SocketError::from_win32)?;
+
+        // As in `TcpStream::connect`: registration is the last fallible step,
+        // so a failure leaves `socket` local and its `Drop` closes it.
+        let submitter = registrar.register(socket.as_handle())?;
+
+        Ok(UdpSocket {
+            inner: Some(Inner { socket, submitter }),
+            v6: addr.is_ipv6(),
+        })
+    }
+
+    fn open(&self) -> &Inner<S> {
+        self.inner
+            .as_ref()
+            .expect("the socket is only torn down in `Drop`")
+    }
+
+    /// The socket this value owns.
+    pub fn socket(&self) -> &Socket {
+        &self.open().socket
+    }
+
+    /// The local address the socket is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, SocketError> {
+        self.open()
+            .socket
+            .local_addr()
+            .map_err(SocketError::from_win32)
+    }
+
+    /// The peer set by [`UdpSocket::connect`].
+    ///
+    /// Fails with `WSAENOTCONN` on a socket that has not been connected.
+    pub fn peer_addr(&self) -> Result<SocketAddr, SocketError> {
+        self.open()
+            .socket
+            .peer_addr()
+            .map_err(SocketError::from_win32)
+    }
+
+    /// Fix the peer for [`UdpSocket::send`] and [`UdpSocket::recv`].
+    ///
+    /// Nothing is sent, so this completes immediately. Afterwards datagrams
+    /// from any other address are discarded by the kernel. Connecting again
+    /// replaces the peer.
+    pub fn connect(&self, addr: SocketAddr) -> Result<(), SocketError> {
+        self.open()
+            .socket
+            .connect_to(addr)
+            .map_err(SocketError::from_win32)
+    }
+
+    /// Send `buffer`'s initialised bytes as one datagram to `addr`.
+    ///
+    /// Resolves to the number of bytes sent, which for a datagram is all of
+    /// them. A payload too large for the transport fails with `WSAEMSGSIZE`.
+    ///
+    /// If the returned future is dropped before resolving, cancellation is
+    /// requested and the buffer is not returned.
+    pub fn send_to<B>(
+        &self,
+        buffer: B,
+        addr: SocketAddr,
+    ) -> impl Future<Output = OpResult<usize, B>>
+    where
+        B: IoBuf + Send,
+    {
+        let open = self.open();
+        let submitted = open.submitter.submit(SendToSocket::new(
+            open.socket.clone(),
+            buffer,
+            SockAddrBytes::from_socket_addr(addr),
+        ));
+        async move {
+            let OpResult(result, op) = submitted.await;
+            OpResult(result, op.into_inner())
+        }
+    }
+
+    /// Receive one datagram into `buffer`, resolving to its length and the
+    /// address it came from.
+    ///
+    /// If the returned future is dropped before resolving, cancellation is
+    /// requested and the buffer is not returned.
+    pub fn recv_from<B>(&self, buffer: B) -> impl Future<Output = OpResult<(usize, SocketAddr), B>>
+    where
+        B: IoBufMut + Send,
+    {
+        let open = self.open();
+        let submitted = open
+            .submitter
+            .submit(RecvFromSocket::new(open.socket.clone(), buffer));
+        async move {
+            let OpResult(result, op) = submitted.await;
+            let (result, buffer) = op.finish(result);
+            OpResult(result, buffer)
+        }
+    }
+
+    /// Receive one datagram into `buffer` with `WSARecvMsg`, resolving to its
+    /// length, its sender and whatever ancillary data the socket was asked to
+    /// report.
+    ///
+    /// Nothing beyond the sender is reported until one of
+    /// [`UdpSocket::set_recv_packet_info`], [`UdpSocket::set_recv_hop_limit`]
+    /// or [`UdpSocket::set_recv_ecn`] is switched on. Truncation is reported
+    /// as for [`UdpSocket::recv_from`].
+    ///
+    /// If the returned future is dropped before resolving, cancellation is
+    /// requested and the buffer is not returned.
+    pub fn recv_msg<B>(&self, buffer: B) -> impl Future<Output = OpResult<RecvMeta, B>>
+    where
+        B: IoBufMut + Send,
+    {
+        let open = self.open();
+        let submitted = open
+            .submitter
+            .submit(RecvMsgSocket::new(open.socket.clone(), buffer));
+        async move {
+            let OpResult(result, op) = submitted.await;
+            let (result, buffer) = op.finish(result);
+            OpResult(result, buffer)
+        }
+    }
+
+    /// Allow or forbid sending to broadcast addresses (`SO_BROADCAST`).
+    ///
+    /// Off by default; a send to a broadcast address without it fails with
+    /// `WSAEACCES`.
+    pub fn set_broadcast(&self, broadcast: bool) -> Result<(), SocketError> {
+        self.set_u32(SOL_SOCKET, SO_BROADCAST, u32::from(broadcast))
+    }
+
+    /// Join the IPv4 multicast `group` on the interface with address
+    /// `interface`.
+    ///
+    /// [`Ipv4Addr::UNSPECIFIED`] lets the stack choose the interface.
+    pub fn join_multicast_v4(
+        &self,
+        group: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), SocketError> {
+        self.set_mreq_v4(IP_ADD_MEMBERSHIP, group, interface)
+    }
+
+    /// Leave a group joined with [`UdpSocket::join_multicast_v4`].
+    pub fn leave_multicast_v4(
+        &self,
+        group: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), SocketError> {
+        self.set_mreq_v4(IP_DROP_MEMBERSHIP, group, interface)
+    }
+
+    /// Join the IPv6 multicast `group` on the interface with index
+    /// `interface`.
+    ///
+    /// Zero lets the stack choose the interface.
+    pub fn join_multicast_v6(&self, group: &Ipv6Addr, interface: u32) -> Result<(), SocketError> {
+        self.set_mreq_v6(IPV6_ADD_MEMBERSHIP, group, interface)
+    }
+
+    /// Leave a group joined with [`UdpSocket::join_multicast_v6`].
+    pub fn leave_multicast_v6(&self, group: &Ipv6Addr, interface: u32) -> Result<(), SocketError> {
+        self.set_mreq_v6(IPV6_DROP_MEMBERSHIP, group, interface)
+    }
+
+    /// Send IPv4 multicast through the interface with address `interface`
+    /// (`IP_MULTICAST_IF`).
+    pub fn set_multicast_interface_v4(&self, interface: Ipv4Addr) -> Result<(), SocketError> {
+        let value = in_addr(interface);
+        // SAFETY: `IN_ADDR` is four bytes with no padding, and is the value
+        // `IP_MULTICAST_IF` takes.
+        unsafe {
+            self.socket()
+                .set_option(IPPROTO_IP.0, IP_MULTICAST_IF, &value)
+        }
+        .map_err(SocketError::from_win32)
+    }
+
+    /// Send IPv6 multicast through the interface with index `interface`
+    /// (`IPV6_MULTICAST_IF`).
+    pub fn set_multicast_interface_v6(&self, interface: u32) -> Result<(), SocketError> {
+        self.set_u32(IPPROTO_IPV6.0, IPV6_MULTICAST_IF, interface)
+    }
+
+    /// Whether IPv4 multicast this socket sends is looped back to local
+    /// members. On by default.
+    pub fn set_multicast_loop_v4(&self, enabled: bool) -> Result<(), SocketError> {
+        self.set_u32(IPPROTO_IP.0, IP_MULTICAST_LOOP, u32::from(enabled))
+    }
+
+    /// Whether IPv6 multicast this socket sends is looped back to local
+    /// members. On by default.
+    pub fn set_multicast_loop_v6(&self, enabled: bool) -> Result<(), SocketError> {
+        self.set_u32(IPPROTO_IPV6.0, IPV6_MULTICAST_LOOP, u32::from(enabled))
+    }
+
+    /// The TTL of outgoing IPv4 multicast. One by default, which keeps it on
+    /// the local link.
+    pub fn set_multicast_ttl_v4(&self, ttl: u32) -> Result<(), SocketError> {
+        self.set_u32(IPPROTO_IP.0, IP_MULTICAST_TTL, ttl)
+    }
+
+    /// The hop limit of outgoing IPv6 multicast. One by default.
+    pub fn set_multicast_hops_v6(&self, hops: u32) -> Result<(), SocketError> {
+        self.set_u32(IPPROTO_IPV6.0, IPV6_MULTICAST_HOPS, hops)
+    }
+
+    /// Report each datagram's destination address and arrival interface to
+    /// [`UdpSocket::recv_msg`] (`IP_PKTINFO` / `IPV6_PKTINFO`).
+    pub fn set_recv_packet_info(&self, enabled: bool) -> Result<(), SocketError> {
+        let (level, name) = if self.v6 {
+            (IPPROTO_IPV6.0, IPV6_PKTINFO)
+        } else {
+            (IPPROTO_IP.0, IP_PKTINFO)
+        };
+        self.set_u32(level, name, u32::from(enabled))
+    }
+
+    /// Report each datagram's TTL or hop limit to [`UdpSocket::recv_msg`]
+    /// (`IP_RECVTTL` / `IPV6_HOPLIMIT`).
+    pub fn set_recv_hop_limit(&self, enabled: bool) -> Result<(), SocketError> {
+        let (level, name) = if self.v6 {
+            (IPPROTO_IPV6.0, IPV6_HOPLIMIT)
+        } else {
+            (IPPROTO_IP.0, IP_RECVTTL)
+        };
+        self.set_u32(level, name, u32::from(enabled))
+    }
+
+    /// Report each datagram's ECN codepoint to [`UdpSocket::recv_msg`]
+    /// (`IP_RECVECN` / `IPV6_RECVECN`).
+    pub fn set_recv_ecn(&self, enabled: bool) -> Result<(), SocketError> {
+        let (level, name) = if self.v6 {
+            (IPPROTO_IPV6.0, IPV6_RECVECN)
+        } else {
+            (IPPROTO_IP.0, IP_RECVECN)
+        };
+        self.set_u32(level, name, u32::from(enabled))
+    }
+
+    fn set_u32(&self, level: i32, name: i32, value: u32) -> Result<(), SocketError> {
+        self.socket()
+            .set_u32_option(level, name, value)
+            .map_err(SocketError::from_win32)
+    }
+
+    fn set_mreq_v4(
+        &self,
+        name: i32,
+        group: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), SocketError> {
+        let request = IP_MREQ {
+            imr_multiaddr: in_addr(group),
+            imr_interface: in_addr(interface),
+        };
+        // SAFETY: `IP_MREQ` is two four-byte addresses with no padding, and is
+        // the value the membership options take on `IPPROTO_IP`.
+        unsafe { self.socket().set_option(IPPROTO_IP.0, name, &request) }
+            .map_err(SocketError::from_win32)
+    }
+
+    fn set_mreq_v6(&self, name: i32, group: &Ipv6Addr, interface: u32) -> Result<(), SocketError> {
+        let request = IPV6_MREQ {
+            ipv6mr_multiaddr: in6_addr(group),
+            ipv6mr_interface: interface,
+        };
+        // SAFETY: `IPV6_MREQ` is a sixteen-byte address and a four-byte index,
+        // twenty bytes with no padding, and is the value the membership
+        // options take on `IPPROTO_IPV6`.
+        unsafe { self.socket().set_option(IPPROTO_IPV6.0, name, &request) }
+            .map_err(SocketError::from_win32)
+    }
+
+    /// Send one datagram to the connected peer.
+    ///
+    /// Fails with `WSAENOTCONN` before [`UdpSocket::connect`].
+    ///
+    /// If the returned future is dropped before resolving, cancellation is
+    /// requested and the buffer is not returned.
+    pub fn send<B>(&self, buffer: B) -> impl Future<Output = OpResult<usize, B>>
+    where
+        B: IoBuf + Send,
+    {
+        let open = self.open();
+        let submitted = open
+            .submitter
+            .submit(SendSocket::new(open.socket.clone(), buffer));
+        async move {
+            let OpResult(result, op) = submitted.await;
+            OpResult(result, op.into_inner())
+        }
+    }
+
+    /// Receive one datagram from the connected peer, resolving to its length.
+    ///
+    /// This is [`UdpSocket::recv_from`] without the address rather than a
+    /// plain `WSARecv`, whose stream-oriented result would read a zero-length
+    /// datagram as the peer closing.
+    ///
+    /// If the returned future is dropped before resolving, cancellation is
+    /// requested and the buffer is not returned.
+    pub fn recv<B>(&self, buffer: B) -> impl Future<Output = OpResult<usize, B>>
+    where
+        B: IoBufMut + Send,
+    {
+        let received = self.recv_from(buffer);
+        async move {
+            let OpResult(result, buffer) = received.await;
+            OpResult(result.map(|(n, _)| n), buffer)
+        }
+    }
+}
+
+impl<S: Submitter> Drop for UdpSocket<S> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            // The same order as `TcpStream`'s drop, for the same reason.
+            let _ = inner.socket.cancel_all();
+            drop(inner.submitter);
+            drop(inner.socket);
+        }
+    }
+}
+
+impl<S: Submitter> std::fmt::Debug for UdpSocket<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UdpSocket")
+            .field("socket", &self.open().socket)
+            .finish_non_exhaustive()
+    }
+}
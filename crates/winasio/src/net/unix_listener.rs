@@ -6,9 +6,13 @@
 
 //! A listening `AF_UNIX` socket.
 
-use crate::iocp::{OpResult, Registrar, Submitter};
+use std::time::Duration;
 
+use crate::iocp::{OpResult, Registrar, Submit, Submitter};
+
+use super::deadline::within;
 use super::error::SocketError;
+use super::incoming::{Accepting, PostedAccept};
 use super::ops::accept::AcceptSocket;
 use super::socket::Socket;
 use super::unix_addr::UnixSocketAddr;
@@ -202,12 +206,60 @@ impl<R: Registrar + Clone> UnixListener<R> {
     /// address: it is the accurate answer, and a caller that needs to know who
     /// connected must carry that in the protocol rather than in the address.
     pub async fn accept(&self) -> Result<(UnixStream<R::Io>, UnixSocketAddr), SocketError> {
+        self.accept_within(None).await
+    }
+
+    /// [`UnixListener::accept`], giving up after `timeout`. See
+    /// [`super::TcpListener::accept_timeout`].
+    pub async fn accept_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<(UnixStream<R::Io>, UnixSocketAddr), SocketError> {
+        self.accept_within(Some(timeout)).await
+    }
+
+    /// Keep `depth` accepts outstanding at once. See
+    /// [`super::TcpListener::incoming`].
+    pub fn incoming(&self, depth: usize) -> UnixIncoming<'_, R> {
+        // `AF_UNIX` sockets are never recycled, so there is no submitter to
+        // carry between the two.
+        Accepting::new(
+            self,
+            depth,
+            |listener| {
+                Ok(PostedAccept {
+                    submitted: listener.submit_accept()?,
+                    reused: None,
+                })
+            },
+            |listener, completed, _| listener.finish_accept(completed),
+        )
+    }
+
+    async fn accept_within(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<(UnixStream<R::Io>, UnixSocketAddr), SocketError> {
+        let submitted = self.submit_accept()?;
+        self.finish_accept(within(submitted, timeout).await)
+    }
+
+    /// Post one `AcceptEx`.
+    fn submit_accept(&self) -> Result<Submit<AcceptSocket>, SocketError> {
         // `AcceptEx` does not create the socket; the caller must supply one of
         // the listener's family, unbound and unconnected.
         let accepted = Socket::new_overlapped_unix().map_err(SocketError::from_win32)?;
+        Ok(self
+            .io
+            .submit(AcceptSocket::new(self.socket.clone(), accepted)))
+    }
 
-        let op = AcceptSocket::new(self.socket.clone(), accepted);
-        let OpResult(result, op) = self.io.submit(op).await;
+    /// Turn a completed `AcceptEx` into a registered stream.
+    fn finish_accept(
+        &self,
+        completed: OpResult<usize, AcceptSocket>,
+    ) -> Result<(UnixStream<R::Io>, UnixSocketAddr), SocketError> {
+        let OpResult(result, op) = completed;
 
         // `finish` applies `SO_UPDATE_ACCEPT_CONTEXT` and copies both addresses
         // out of the provider's buffer before it is dropped.
@@ -231,6 +283,17 @@ impl<R: Registrar + Clone> UnixListener<R> {
     }
 }
 
+/// Connections from a [`UnixListener`] with several accepts outstanding.
+///
+/// Created by [`UnixListener::incoming`]; the `AF_UNIX` counterpart of
+/// [`super::Incoming`], and the same [`Accepting`] type underneath.
+pub type UnixIncoming<'a, R> = Accepting<
+    'a,
+    UnixListener<R>,
+    <R as Registrar>::Io,
+    (UnixStream<<R as Registrar>::Io>, UnixSocketAddr),
+>;
+
 impl<R: Registrar + Clone> std::fmt::Debug for UnixListener<R> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("UnixListener")
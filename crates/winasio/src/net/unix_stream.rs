@@ -8,11 +8,15 @@
 
 use std::future::Future;
 use std::net::Shutdown;
+use std::time::Duration;
 
 use crate::fs::ReadOutcome;
+use crate::io::{OwnedReadHalf, OwnedWriteHalf};
 use crate::iocp::{IntoInner, IoBuf, IoBufMut, OpResult, Registrar, Submitter};
 
 use super::addr::SockAddrBytes;
+use super::close::{close_gracefully, CloseOutcome, DEFAULT_DRAIN_LIMIT};
+use super::deadline::{within, Timeouts};
 use super::error::SocketError;
 use super::ops::connect::ConnectSocket;
 use super::ops::io::{RecvSocket, SendSocket};
@@ -79,12 +83,14 @@ struct Inner<S> {
 ///   variant TCP produces, which is the more useful answer of the two.
 pub struct UnixStream<S: Submitter> {
     inner: Option<Inner<S>>,
+    timeouts: Timeouts,
 }
 
 impl<S: Submitter> UnixStream<S> {
     pub(crate) fn from_parts(socket: Socket, submitter: S) -> Self {
         UnixStream {
             inner: Some(Inner { socket, submitter }),
+            timeouts: Timeouts::default(),
         }
     }
 
@@ -127,6 +133,31 @@ impl<S: Submitter> UnixStream<S> {
             .map_err(SocketError::from_win32)
     }
 
+    /// Set a deadline for each [`read`](UnixStream::read), or `None` for none.
+    /// The same contract as [`super::TcpStream::set_read_timeout`]: a timed-out
+    /// read is cancelled, still returns its buffer, and fails as
+    /// [`SocketError::TimedOut`] once classified.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), SocketError> {
+        self.timeouts.set_read(timeout)
+    }
+
+    /// The current read timeout.
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.timeouts.read()
+    }
+
+    /// Set a deadline for each [`write`](UnixStream::write), or `None` for
+    /// none. See [`super::TcpStream::set_write_timeout`] for what a timed-out
+    /// write leaves behind.
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> Result<(), SocketError> {
+        self.timeouts.set_write(timeout)
+    }
+
+    /// The current write timeout.
+    pub fn write_timeout(&self) -> Option<Duration> {
+        self.timeouts.write()
+    }
+
     /// Shut down one or both directions of the connection.
     ///
     /// This is `shutdown(2)`, not a close: it sends the end-of-stream marker
@@ -141,6 +172,32 @@ impl<S: Submitter> UnixStream<S> {
             .map_err(SocketError::from_win32)
     }
 
+    /// Close the connection in order, so the peer can tell it finished from
+    /// it vanishing.
+    ///
+    /// The same sequence and outcomes as
+    /// [`super::TcpStream::close_gracefully`], drained up to
+    /// [`DEFAULT_DRAIN_LIMIT`] bytes.
+    pub async fn close_gracefully(self, timeout: Duration) -> Result<CloseOutcome, SocketError> {
+        self.close_gracefully_with(timeout, DEFAULT_DRAIN_LIMIT)
+            .await
+    }
+
+    /// [`close_gracefully`](UnixStream::close_gracefully) with a drain limit
+    /// of the caller's choosing, as in
+    /// [`super::TcpStream::close_gracefully_with`].
+    pub async fn close_gracefully_with(
+        self,
+        timeout: Duration,
+        drain_limit: u64,
+    ) -> Result<CloseOutcome, SocketError> {
+        // The drain's deadline is `timeout`. With a read timeout left in
+        // place, its `WSAETIMEDOUT` could not be told from the connection's
+        // own, such as a keepalive failure.
+        self.timeouts.set_read(None)?;
+        close_gracefully(&self, &self.open().socket, timeout, drain_limit).await
+    }
+
     /// Start a read.
     ///
     /// The buffer is moved into the operation and handed back when it resolves,
@@ -160,11 +217,12 @@ impl<S: Submitter> UnixStream<S> {
         B: IoBufMut + Send,
     {
         let open = self.open();
+        let timeout = self.timeouts.read();
         let submitted = open
             .submitter
             .submit(RecvSocket::new(open.socket.clone(), buffer));
         async move {
-            let OpResult(result, op) = submitted.await;
+            let OpResult(result, op) = within(submitted, timeout).await;
             let (result, buffer) = op.finish(result);
             OpResult(result, buffer)
         }
@@ -182,11 +240,12 @@ impl<S: Submitter> UnixStream<S> {
         B: IoBuf + Send,
     {
         let open = self.open();
+        let timeout = self.timeouts.write();
         let submitted = open
             .submitter
             .submit(SendSocket::new(open.socket.clone(), buffer));
         async move {
-            let OpResult(result, op) = submitted.await;
+            let OpResult(result, op) = within(submitted, timeout).await;
             OpResult(result, op.into_inner())
         }
     }
@@ -233,6 +292,16 @@ impl<S: Submitter> UnixStream<S> {
     {
         crate::io::write_all(self, 0, buffer)
     }
+
+    /// Split into a read half and a write half that can be moved to different
+    /// tasks.
+    ///
+    /// Dropping the write half shuts the socket down for sending, so the peer
+    /// reads a graceful close while the read half keeps receiving. The halves
+    /// go back together with [`OwnedReadHalf::reunite`].
+    pub fn into_split(self) -> (OwnedReadHalf<Self>, OwnedWriteHalf<Self>) {
+        crate::io::split(self)
+    }
 }
 
 impl<S: Submitter> UnixStream<S> {
@@ -249,6 +318,33 @@ impl<S: Submitter> UnixStream<S> {
     /// A path with nothing listening on it fails with
     /// [`SocketError::ConnectionRefused`].
     pub async fn connect<R>(registrar: &R, addr: &UnixSocketAddr) -> Result<Self, SocketError>
+    where
+        R: Registrar<Io = S>,
+    {
+        Self::connect_within(registrar, addr, None).await
+    }
+
+    /// [`UnixStream::connect`], giving up after `timeout` with
+    /// [`SocketError::TimedOut`].
+    ///
+    /// A path with no listener is refused inline, so the deadline only comes
+    /// into play when the connect actually pends.
+    pub async fn connect_timeout<R>(
+        registrar: &R,
+        addr: &UnixSocketAddr,
+        timeout: Duration,
+    ) -> Result<Self, SocketError>
+    where
+        R: Registrar<Io = S>,
+    {
+        Self::connect_within(registrar, addr, Some(timeout)).await
+    }
+
+    async fn connect_within<R>(
+        registrar: &R,
+        addr: &UnixSocketAddr,
+        timeout: Option<Duration>,
+    ) -> Result<Self, SocketError>
     where
         R: Registrar<Io = S>,
     {
@@ -266,7 +362,7 @@ impl<S: Submitter> UnixStream<S> {
         let submitter = registrar.register(socket.as_handle())?;
 
         let op = ConnectSocket::new(socket.clone(), SockAddrBytes::from_unix_addr(addr));
-        let OpResult(result, op) = submitter.submit(op).await;
+        let OpResult(result, op) = within(submitter.submit(op), timeout).await;
         op.finish(result).map_err(SocketError::from_win32)?;
 
         Ok(UnixStream::from_parts(socket, submitter))
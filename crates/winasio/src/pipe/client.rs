@@ -6,9 +6,14 @@
 
 //! Named-pipe client builder.
 
+use std::os::windows::io::{FromRawHandle, IntoRawHandle, OwnedHandle};
+use std::time::{Duration, Instant};
+
+use windows::Win32::Foundation::HANDLE;
+use windows::Win32::Security::SECURITY_ATTRIBUTES;
 use windows::Win32::Storage::FileSystem::{
-    CreateFileW, FILE_FLAG_OVERLAPPED, FILE_GENERIC_READ, FILE_GENERIC_WRITE, FILE_SHARE_NONE,
-    OPEN_EXISTING,
+    CreateFileW, FILE_FLAGS_AND_ATTRIBUTES, FILE_FLAG_OVERLAPPED, FILE_GENERIC_READ,
+    FILE_GENERIC_WRITE, FILE_SHARE_NONE, OPEN_EXISTING,
 };
 use windows::Win32::System::Pipes::{
     SetNamedPipeHandleState, PIPE_READMODE_BYTE, PIPE_READMODE_MESSAGE,
@@ -16,6 +21,7 @@ use windows::Win32::System::Pipes::{
 
 use crate::fs::SetupError;
 use crate::iocp::{Handle, Registrar};
+use crate::time::{deadline_after, sleep};
 
 use super::connected::Inner;
 use super::name::local_pipe_path;
@@ -28,6 +34,8 @@ pub struct ClientOptions {
     name: String,
     access: AccessDirection,
     read_mode: PipeMode,
+    initial_backoff: Duration,
+    max_backoff: Duration,
 }
 
 impl ClientOptions {
@@ -37,6 +45,8 @@ impl ClientOptions {
             name: name.into(),
             access: AccessDirection::Duplex,
             read_mode: PipeMode::Byte,
+            initial_backoff: Duration::from_millis(5),
+            max_backoff: Duration::from_millis(250),
         }
     }
 
@@ -61,45 +71,104 @@ impl ClientOptions {
         self
     }
 
+    /// Set the retry delays used by [`ClientOptions::connect_wait`].
+    ///
+    /// The first retry waits `initial`, and each later one waits twice as
+    /// long as the one before, up to `max`. Defaults to 5 ms doubling up to
+    /// 250 ms. A zero `initial` is raised to one millisecond so the loop never
+    /// spins, and `max` is raised to `initial` if it is smaller.
+    pub fn retry_backoff(&mut self, initial: Duration, max: Duration) -> &mut Self {
+        self.initial_backoff = initial.max(Duration::from_millis(1));
+        self.max_backoff = max.max(self.initial_backoff);
+        self
+    }
+
     /// Open and register a connected client endpoint.
     ///
     /// If all server instances are busy, this returns [`SetupError::Busy`]
-    /// immediately; waiting and retrying is the caller's responsibility.
+    /// immediately; use [`ClientOptions::connect_wait`] to wait instead.
     pub fn connect<R: Registrar>(
         &self,
         registrar: &R,
     ) -> std::result::Result<NamedPipe<R::Io>, SetupError> {
+        let owned = self.open(self.desired_access(), FILE_FLAG_OVERLAPPED, false)?;
+        register(owned, registrar)
+    }
+
+    /// Connect, waiting up to `timeout` for the pipe to become available.
+    ///
+    /// [`SetupError::Busy`] — every instance is connected — and
+    /// [`SetupError::NotFound`] — no instance exists, as while a server
+    /// restarts — are retried with the delays set by
+    /// [`ClientOptions::retry_backoff`]. Any other failure is returned at
+    /// once. When `timeout` runs out the result is [`SetupError::TimedOut`];
+    /// at least one attempt is always made. [`Duration::MAX`] waits as good as
+    /// forever.
+    ///
+    /// Waiting uses [`crate::time::sleep`], not `WaitNamedPipeW`, so the
+    /// caller's thread is never blocked. See that module for driving it from a
+    /// [`Proactor`](crate::iocp::Proactor) loop.
+    pub async fn connect_wait<R: Registrar>(
+        &self,
+        registrar: &R,
+        timeout: Duration,
+    ) -> std::result::Result<NamedPipe<R::Io>, SetupError> {
+        let deadline = deadline_after(timeout);
+        let mut delay = self.initial_backoff;
+        loop {
+            match self.connect(registrar) {
+                Err(SetupError::Busy | SetupError::NotFound) => {}
+                done => return done,
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(SetupError::TimedOut);
+            }
+            sleep(delay.min(deadline - now)).await;
+            delay = (delay * 2).min(self.max_backoff);
+        }
+    }
+
+    /// Open the pipe without registering it, and apply the read mode.
+    ///
+    /// `flags` decides whether the handle is overlapped; `inherit` whether
+    /// child processes inherit it.
+    pub(super) fn open(
+        &self,
+        desired_access: u32,
+        flags: FILE_FLAGS_AND_ATTRIBUTES,
+        inherit: bool,
+    ) -> std::result::Result<OwnedHandle, SetupError> {
         let name = local_pipe_path(&self.name)?;
+        let attributes = SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: std::ptr::null_mut(),
+            bInheritHandle: inherit.into(),
+        };
 
         // SAFETY: the name was validated and composed as a local, NUL-free
-        // pipe path. The handle is always opened for overlapped I/O.
+        // pipe path, and the attributes outlive the call.
         let raw = unsafe {
             CreateFileW(
                 &name,
-                self.desired_access(),
+                desired_access,
                 FILE_SHARE_NONE,
-                None,
+                Some(std::ptr::from_ref(&attributes)),
                 OPEN_EXISTING,
-                FILE_FLAG_OVERLAPPED,
+                flags,
                 None,
             )
         }
         .map_err(SetupError::from_windows)?;
 
         // SAFETY: `CreateFileW` returned a newly owned handle, and ownership of
-        // closing it transfers into `Handle`.
-        let handle = unsafe { Handle::from_raw(raw) };
-        if let Err(e) = self.apply_read_mode(handle.raw()) {
+        // closing it transfers into `OwnedHandle`.
+        let handle = unsafe { OwnedHandle::from_raw_handle(raw.0) };
+        if let Err(e) = self.apply_read_mode(raw) {
             drop(handle);
             return Err(SetupError::from_windows(e));
         }
-        match registrar.register(handle.raw()) {
-            Ok(submitter) => Ok(NamedPipe::from_inner(Inner::new(handle, submitter))),
-            Err(e) => {
-                drop(handle);
-                Err(SetupError::from(e))
-            }
-        }
+        Ok(handle)
     }
 
     fn desired_access(&self) -> u32 {
@@ -110,10 +179,7 @@ impl ClientOptions {
         }
     }
 
-    fn apply_read_mode(
-        &self,
-        handle: windows::Win32::Foundation::HANDLE,
-    ) -> windows::core::Result<()> {
+    fn apply_read_mode(&self, handle: HANDLE) -> windows::core::Result<()> {
         let mode = match self.read_mode {
             PipeMode::Byte => PIPE_READMODE_BYTE,
             PipeMode::Message => PIPE_READMODE_MESSAGE,
@@ -127,3 +193,21 @@ impl ClientOptions {
         unsafe { SetNamedPipeHandleState(handle, Some(&mode), None, None) }
     }
 }
+
+/// Register an overlapped handle from [`ClientOptions::open`] as a connected
+/// pipe.
+pub(super) fn register<R: Registrar>(
+    owned: OwnedHandle,
+    registrar: &R,
+) -> std::result::Result<NamedPipe<R::Io>, SetupError> {
+    // SAFETY: ownership of closing the handle moves from `owned` into
+    // `Handle`.
+    let handle = unsafe { Handle::from_raw(HANDLE(owned.into_raw_handle())) };
+    match registrar.register(handle.raw()) {
+        Ok(submitter) => Ok(NamedPipe::from_inner(Inner::new(handle, submitter))),
+        Err(e) => {
+            drop(handle);
+            Err(SetupError::from(e))
+        }
+    }
+}
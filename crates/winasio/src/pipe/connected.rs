@@ -7,16 +7,23 @@
 //! Connected named-pipe endpoint.
 
 use std::future::Future;
+use std::marker::PhantomData;
 
 use windows::core::Result;
 use windows::Win32::Foundation::HANDLE;
-use windows::Win32::System::Pipes::DisconnectNamedPipe;
+use windows::Win32::Security::RevertToSelf;
+use windows::Win32::System::Pipes::{
+    DisconnectNamedPipe, GetNamedPipeClientProcessId, GetNamedPipeClientSessionId,
+    GetNamedPipeServerProcessId, ImpersonateNamedPipeClient,
+};
 use windows::Win32::System::IO::CancelIoEx;
 
+use crate::io::{OwnedReadHalf, OwnedWriteHalf};
 use crate::iocp::{
-    Handle, IntoInner, IoBuf, IoBufMut, OpResult, ReadHandle, Submitter, WriteHandle,
+    Handle, IntoInner, IoBuf, IoBufMut, OpResult, ReadHandle, Submitter, TransactPipe, WriteHandle,
 };
 
+use super::message::{self, MessageError};
 use super::server::NamedPipeServer;
 use super::ReadOutcome;
 
@@ -152,6 +159,16 @@ impl<S: Submitter> NamedPipe<S> {
         crate::io::write_all(self, 0, buffer)
     }
 
+    /// Split into a read half and a write half that can be moved to different
+    /// tasks.
+    ///
+    /// A pipe has no half-close: the peer sees the pipe close once both halves
+    /// are dropped. The halves go back together with
+    /// [`OwnedReadHalf::reunite`].
+    pub fn into_split(self) -> (OwnedReadHalf<Self>, OwnedWriteHalf<Self>) {
+        crate::io::split(self)
+    }
+
     /// Fill the buffer's full capacity from the pipe.
     ///
     /// A closed peer before the capacity is filled is reported as
@@ -187,6 +204,136 @@ impl<S: Submitter> NamedPipe<S> {
         crate::io::read_to_end(self, 0, buffer)
     }
 
+    /// Read one whole message of at most `max_len` bytes.
+    ///
+    /// Reads until the message ends, growing the returned buffer as it goes,
+    /// so the caller needs no [`ReadOutcome::MoreData`] loop. A longer message
+    /// is read to its end and discarded, and reported as
+    /// [`MessageError::TooLarge`]; the next call starts on the following
+    /// message. Requires message read mode on this end; in byte read mode
+    /// every read counts as a complete message.
+    ///
+    /// If the returned future is dropped before resolving, the part of the
+    /// message already read is lost and the pipe is left mid-message.
+    pub fn read_message(
+        &self,
+        max_len: usize,
+    ) -> impl Future<Output = std::result::Result<Vec<u8>, MessageError>> + '_ {
+        message::read_message(self, max_len)
+    }
+
+    /// Write the whole buffer as exactly one message.
+    ///
+    /// The buffer goes out in a single write, never split across several, and
+    /// a successful result always carries its full length. A buffer longer
+    /// than one write can carry fails with `ERROR_INVALID_PARAMETER` before
+    /// anything is sent.
+    ///
+    /// If the returned future is dropped before resolving, cancellation is
+    /// requested and the buffer is not returned.
+    pub fn write_message<B>(&self, buffer: B) -> impl Future<Output = OpResult<usize, B>> + '_
+    where
+        B: IoBuf + Send,
+    {
+        message::write_message(self, buffer)
+    }
+
+    /// The length of the next message waiting to be read, without reading it.
+    ///
+    /// `None` when nothing is waiting; an empty message cannot be told apart
+    /// from no message. Once part of a message has been read, this is what
+    /// remains of it. On a byte-type pipe it is the number of bytes
+    /// available.
+    pub fn peek_message_len(&self) -> Result<Option<usize>> {
+        // SAFETY: the handle is live for `&self`.
+        unsafe { message::peek_message_len(self.open().handle.raw()) }
+    }
+
+    /// Write `request` as one message and read the reply into `response`, in
+    /// a single round trip.
+    ///
+    /// Requires a message-type pipe in message read mode on this end, and
+    /// fails with `ERROR_PIPE_BUSY` if a read is already pending or unread
+    /// data is waiting. A reply longer than `response`'s spare capacity
+    /// resolves to [`ReadOutcome::MoreData`] with the delivered byte count;
+    /// retrieve the rest with [`read`](Self::read) or
+    /// [`read_message`](Self::read_message). A server that closes its end
+    /// instead of replying resolves to [`ReadOutcome::ClosedPeer`], as a
+    /// read does.
+    ///
+    /// Both buffers are returned, whatever the outcome. If the returned future
+    /// is dropped before resolving, cancellation is requested and neither
+    /// buffer is returned; the request may already have been delivered.
+    pub fn transact<I, O>(
+        &self,
+        request: I,
+        response: O,
+    ) -> impl Future<Output = OpResult<ReadOutcome, (I, O)>>
+    where
+        I: IoBuf + Send,
+        O: IoBufMut + Send,
+    {
+        let open = self.open();
+        let submitted =
+            open.submitter
+                .submit(TransactPipe::new(open.handle.clone(), request, response));
+        async move {
+            let OpResult(result, op) = submitted.await;
+            let (result, buffers) = op.finish(result);
+            OpResult(result, buffers)
+        }
+    }
+
+    /// The process id of the client end.
+    ///
+    /// Identifies the process that opened the pipe, as recorded by the pipe
+    /// file system; it is not spoofable by the client. Process ids are reused
+    /// once a process exits, so authorise on the id only while the pipe stays
+    /// connected.
+    pub fn client_process_id(&self) -> Result<u32> {
+        let mut id = 0;
+        // SAFETY: the handle is live for `&self`, and `id` is a valid out
+        // pointer for the call.
+        unsafe { GetNamedPipeClientProcessId(self.open().handle.raw(), &mut id) }?;
+        Ok(id)
+    }
+
+    /// The Terminal Services session id of the client end.
+    pub fn client_session_id(&self) -> Result<u32> {
+        let mut id = 0;
+        // SAFETY: as in `client_process_id`.
+        unsafe { GetNamedPipeClientSessionId(self.open().handle.raw(), &mut id) }?;
+        Ok(id)
+    }
+
+    /// The process id of the server end.
+    ///
+    /// Mostly useful on the client side, to check which process is serving
+    /// the name before trusting it.
+    pub fn server_process_id(&self) -> Result<u32> {
+        let mut id = 0;
+        // SAFETY: as in `client_process_id`.
+        unsafe { GetNamedPipeServerProcessId(self.open().handle.raw(), &mut id) }?;
+        Ok(id)
+    }
+
+    /// Impersonate the client on the calling thread until the guard drops.
+    ///
+    /// Server side only. Windows allows it only after something has been read
+    /// from the pipe, and the level granted is the one the client chose when
+    /// it opened the pipe; below impersonation level, the thread's token can
+    /// be queried but not used to open objects.
+    ///
+    /// Impersonation belongs to the thread, so the guard is neither `Send` nor
+    /// meant to be held across an `.await`: other tasks polled on the same
+    /// thread would run as the client too. Take it, do the synchronous work
+    /// that needs the client's identity, and drop it.
+    pub fn impersonate(&self) -> Result<Impersonation<'_>> {
+        // SAFETY: the handle is live for `&self`.
+        unsafe { ImpersonateNamedPipeClient(self.open().handle.raw()) }?;
+        Ok(Impersonation { _pipe: PhantomData })
+    }
+
     /// Disconnect this server-side pipe and return the reusable server state.
     ///
     /// This consumes the pipe, so it requires exclusive ownership. If the pipe
@@ -214,6 +361,33 @@ impl<S: Submitter> NamedPipe<S> {
     }
 }
 
+/// The calling thread impersonating a pipe client.
+///
+/// Created by [`NamedPipe::impersonate`]. Dropping it reverts the thread to
+/// its own identity.
+pub struct Impersonation<'a> {
+    /// Ties the guard to the pipe, and to the thread: the raw pointer makes
+    /// it `!Send` and `!Sync`.
+    _pipe: PhantomData<(&'a (), *const ())>,
+}
+
+impl Drop for Impersonation<'_> {
+    fn drop(&mut self) {
+        // SAFETY: this thread is impersonating, by construction of the guard.
+        if unsafe { RevertToSelf() }.is_err() {
+            // The thread would go on running as the client, with whatever
+            // access that grants. There is no safe way to continue.
+            std::process::abort();
+        }
+    }
+}
+
+impl std::fmt::Debug for Impersonation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Impersonation").finish_non_exhaustive()
+    }
+}
+
 impl<S: Submitter> std::fmt::Debug for NamedPipe<S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("NamedPipe")
@@ -0,0 +1,167 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! A pool of server instances waiting for clients.
+//!
+//! A pipe name exists only while at least one instance of it is open, and a
+//! client that arrives while every instance is connected sees
+//! `ERROR_PIPE_BUSY`. [`PipeListener`] keeps a fixed number of instances
+//! waiting in `ConnectNamedPipe` and creates each replacement before handing a
+//! connected pipe out, so there is always an instance for the next client.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use windows::Win32::System::Pipes::PIPE_UNLIMITED_INSTANCES;
+
+use crate::fs::SetupError;
+use crate::iocp::{ConnectPipe, OpResult, Registrar, Submit, Submitter};
+
+use super::connected::InnerGuard;
+use super::{NamedPipe, NamedPipeServer, ServerOptions};
+
+/// One instance with a connect in flight.
+///
+/// **Field order is load-bearing.** The connect is dropped first, requesting
+/// its cancellation, and only then does the guard tear the instance down in
+/// the order [`NamedPipeServer::connect`] documents.
+struct Waiting<S: Submitter> {
+    connect: Submit<ConnectPipe>,
+    instance: InnerGuard<S>,
+}
+
+impl<S: Submitter> Waiting<S> {
+    fn start(mut server: NamedPipeServer<S>) -> Self {
+        let inner = server.inner.take().expect("pipe state is present");
+        let connect = inner
+            .submitter
+            .submit(ConnectPipe::new(inner.handle.clone()));
+        Waiting {
+            connect,
+            instance: InnerGuard::new(inner),
+        }
+    }
+}
+
+/// Server instances of one pipe name, kept waiting for clients.
+///
+/// Generic over a [`Registrar`] for the same reason as
+/// [`TcpListener`](crate::net::TcpListener): every accept creates a fresh
+/// instance, which must be registered before anything can be submitted on it.
+pub struct PipeListener<R: Registrar + Clone> {
+    waiting: Vec<Waiting<R::Io>>,
+    registrar: R,
+    options: ServerOptions,
+    instances: usize,
+}
+
+impl<R: Registrar + Clone> PipeListener<R> {
+    /// Create `instances` server instances from `options` and start waiting
+    /// for clients on all of them.
+    ///
+    /// Zero is treated as one. Connected pipes stay instances of the name, so
+    /// `options` must allow more instances than are kept waiting: set
+    /// [`ServerOptions::max_instances`] to cover the waiting instances plus the
+    /// most connections held at once, or to `PIPE_UNLIMITED_INSTANCES`. A limit
+    /// that cannot even cover one connection is reported as
+    /// [`SetupError::InvalidOptions`].
+    ///
+    /// [`ServerOptions::first_instance`] applies to the first instance only.
+    pub fn bind(
+        registrar: &R,
+        options: &ServerOptions,
+        instances: usize,
+    ) -> Result<Self, SetupError> {
+        let instances = instances.max(1);
+        let limit = options.instance_limit();
+        if limit != PIPE_UNLIMITED_INSTANCES && limit as usize <= instances {
+            return Err(SetupError::InvalidOptions(
+                "max_instances must exceed the number of waiting instances",
+            ));
+        }
+
+        let first = options.create(registrar)?;
+        let mut options = options.clone();
+        options.first_instance(false);
+        let mut listener = PipeListener {
+            waiting: Vec::with_capacity(instances),
+            registrar: registrar.clone(),
+            options,
+            instances,
+        };
+        listener.waiting.push(Waiting::start(first));
+        listener.top_up()?;
+        Ok(listener)
+    }
+
+    /// Wait for a client and return its connected pipe.
+    ///
+    /// Clients are served in whatever order their connects complete. A connect
+    /// that fails is reported as an error; its instance is discarded and
+    /// replaced, so the listener stays usable.
+    ///
+    /// Dropping the returned future loses nothing: waiting instances belong to
+    /// the listener, and a client that connects meanwhile is returned by the
+    /// next call.
+    pub async fn accept(&mut self) -> Result<NamedPipe<R::Io>, SetupError> {
+        std::future::poll_fn(|cx| self.poll_accept(cx)).await
+    }
+
+    /// Poll for a connected client.
+    ///
+    /// Each poll first replaces instances that could not be replaced earlier.
+    /// A failure to create one is only reported once no instance at all is
+    /// left waiting; until then the listener runs with fewer instances.
+    pub fn poll_accept(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<NamedPipe<R::Io>, SetupError>> {
+        if let Err(e) = self.top_up() {
+            if self.waiting.is_empty() {
+                return Poll::Ready(Err(e));
+            }
+        }
+        for i in 0..self.waiting.len() {
+            if let Poll::Ready(OpResult(result, _op)) =
+                Pin::new(&mut self.waiting[i].connect).poll(cx)
+            {
+                let mut done = self.waiting.remove(i);
+                // Replace before handing the pipe out. A failure here is
+                // retried by the next poll and reported there if it persists.
+                let _ = self.top_up();
+                return Poll::Ready(match result {
+                    Ok(_) => Ok(NamedPipe::from_inner(done.instance.take())),
+                    Err(e) => Err(SetupError::from_windows(e)),
+                });
+            }
+        }
+        Poll::Pending
+    }
+
+    /// Instances currently waiting for a client.
+    pub fn waiting(&self) -> usize {
+        self.waiting.len()
+    }
+
+    fn top_up(&mut self) -> Result<(), SetupError> {
+        while self.waiting.len() < self.instances {
+            let server = self.options.create(&self.registrar)?;
+            self.waiting.push(Waiting::start(server));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Registrar + Clone> std::fmt::Debug for PipeListener<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PipeListener")
+            .field("options", &self.options)
+            .field("waiting", &self.waiting.len())
+            .field("instances", &self.instances)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,154 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Whole messages on message-mode pipes.
+//!
+//! A message read that does not fit reports [`ReadOutcome::MoreData`] and
+//! leaves the rest of the message for the next read. [`read_message`] runs
+//! that loop to the end of the message, growing an owned buffer up to a
+//! limit, and drains a message over the limit so the next read starts on a
+//! message boundary.
+
+use windows::core::{Error, Result};
+use windows::Win32::Foundation::{ERROR_WRITE_FAULT, HANDLE};
+use windows::Win32::System::Pipes::PeekNamedPipe;
+
+use crate::io::{classify_platform_error, TailBuf, TransferError};
+use crate::iocp::{IoBuf, OpResult, Submitter};
+
+use super::{NamedPipe, ReadOutcome};
+
+/// Growth step, and the scratch size used to drain an oversized message.
+const MESSAGE_CHUNK: usize = 4096;
+
+/// Why [`NamedPipe::read_message`] did not return a message.
+#[derive(Debug)]
+pub enum MessageError {
+    /// The message was longer than the limit. It has been read to its end
+    /// and discarded, so the pipe is positioned at the next message.
+    TooLarge {
+        /// The message's full length.
+        len: usize,
+    },
+    /// The peer closed its end before a message completed.
+    ClosedPeer,
+    /// Any other platform failure.
+    Win32(Error),
+}
+
+impl From<TransferError> for MessageError {
+    fn from(value: TransferError) -> Self {
+        match value {
+            TransferError::ClosedPeer | TransferError::UnexpectedEof => MessageError::ClosedPeer,
+            TransferError::Win32(e) => MessageError::Win32(e),
+        }
+    }
+}
+
+impl std::fmt::Display for MessageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessageError::TooLarge { len } => write!(f, "message of {len} bytes is too large"),
+            MessageError::ClosedPeer => write!(f, "peer closed the pipe"),
+            MessageError::Win32(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for MessageError {}
+
+pub(super) async fn read_message<S: Submitter>(
+    pipe: &NamedPipe<S>,
+    max_len: usize,
+) -> std::result::Result<Vec<u8>, MessageError> {
+    let mut message = Vec::new();
+    while message.len() < max_len {
+        let room = max_len - message.len();
+        if message.len() == message.capacity() {
+            message.reserve_exact(message.len().max(MESSAGE_CHUNK).min(room));
+        }
+        let len = message.len();
+        let OpResult(result, tail) = pipe.read(TailBuf::new(message, len, room)).await;
+        message = tail.into_inner();
+        match result {
+            // `Bytes` on a message-read-mode pipe means the message ended,
+            // including an empty message.
+            Ok(ReadOutcome::Bytes(_)) => return Ok(message),
+            Ok(ReadOutcome::MoreData(_)) => {}
+            Ok(ReadOutcome::Eof | ReadOutcome::ClosedPeer) => {
+                return Err(MessageError::ClosedPeer);
+            }
+            Err(e) => return Err(classify_platform_error(e).into()),
+        }
+    }
+    drain(pipe, message, max_len).await
+}
+
+/// Read the rest of a message that reached `max_len`, keeping only its size.
+///
+/// Only an empty message can end here without exceeding the limit, and only
+/// when the limit is zero.
+async fn drain<S: Submitter>(
+    pipe: &NamedPipe<S>,
+    message: Vec<u8>,
+    max_len: usize,
+) -> std::result::Result<Vec<u8>, MessageError> {
+    let mut total = message.len();
+    let mut scratch = Vec::with_capacity(MESSAGE_CHUNK);
+    loop {
+        scratch.clear();
+        let OpResult(result, returned) = pipe.read(scratch).await;
+        scratch = returned;
+        match result {
+            Ok(ReadOutcome::Bytes(n)) => {
+                total = total.saturating_add(n);
+                return if total > max_len {
+                    Err(MessageError::TooLarge { len: total })
+                } else {
+                    Ok(message)
+                };
+            }
+            Ok(ReadOutcome::MoreData(n)) => total = total.saturating_add(n),
+            Ok(ReadOutcome::Eof | ReadOutcome::ClosedPeer) => {
+                return Err(MessageError::ClosedPeer);
+            }
+            Err(e) => return Err(classify_platform_error(e).into()),
+        }
+    }
+}
+
+pub(super) async fn write_message<S, B>(pipe: &NamedPipe<S>, buffer: B) -> OpResult<usize, B>
+where
+    S: Submitter,
+    B: IoBuf + Send,
+{
+    let len = buffer.bytes_init();
+    let OpResult(result, buffer) = pipe.write(buffer).await;
+    let result = match result {
+        Ok(n) if n != len => Err(Error::from_hresult(ERROR_WRITE_FAULT.to_hresult())),
+        other => other,
+    };
+    OpResult(result, buffer)
+}
+
+/// The length of the next waiting message, without consuming it.
+///
+/// # Safety
+///
+/// `handle` must be a live pipe handle.
+pub(super) unsafe fn peek_message_len(handle: HANDLE) -> Result<Option<usize>> {
+    let mut available = 0u32;
+    let mut left = 0u32;
+    // SAFETY: the caller vouches for the handle; no data buffer is passed and
+    // both counters are valid out pointers.
+    unsafe { PeekNamedPipe(handle, None, 0, None, Some(&mut available), Some(&mut left)) }?;
+    Ok(match (available, left) {
+        (0, _) => None,
+        // A byte-type pipe has no message boundaries to report.
+        (available, 0) => Some(available as usize),
+        (_, left) => Some(left as usize),
+    })
+}
@@ -25,6 +25,9 @@
 //!   mode. Server options can select message type, and client options can
 //!   select message read mode; a message read that does not fit reports
 //!   [`ReadOutcome::MoreData`] with the delivered byte count.
+//!   [`NamedPipe::read_message`] and [`NamedPipe::write_message`] deal in
+//!   whole messages instead, and [`NamedPipe::transact`] sends a request and
+//!   reads its reply in one operation.
 //! * **Access direction is runtime-checked.** Every connected pipe exposes both
 //!   [`NamedPipe::read`] and [`NamedPipe::write`] regardless of the configured
 //!   direction. An operation contrary to that direction resolves as the
@@ -82,38 +85,59 @@
 //! connected. The connect operation treats this condition as immediate success
 //! and does not wait for a completion packet that Windows will never post.
 //!
+//! # Accept loops
+//!
+//! [`PipeListener`] keeps several instances waiting in `ConnectNamedPipe` and
+//! creates a replacement for each one it hands out, so a server never has a
+//! moment with no instance available and clients do not see
+//! [`SetupError::NotFound`] between accepts.
+//!
+//! # Pairs
+//!
+//! [`pair`] returns two connected ends of a private pipe, for wiring up
+//! threads or tests without choosing a name and running a connect.
+//! [`PairOptions::pair_for_child`] keeps one end here and hands out the other
+//! as a [`ChildPipe`] for a child process. [`PairOptions::inheritable`] picks
+//! which end, if any, child processes inherit; by default neither.
+//!
 //! # Busy pipes
 //!
-//! The crate deliberately does not wait or retry when all instances are busy:
-//! `WaitNamedPipeW` is synchronous and this module owns no runtime-agnostic
-//! timer. Callers who want to wait should retry with their own runtime's timer,
-//! treating [`SetupError::Busy`] as the retryable condition.
+//! [`ClientOptions::connect`] does not wait when every instance is busy; it
+//! reports [`SetupError::Busy`] at once. [`ClientOptions::connect_wait`] is the
+//! waiting form: it retries busy and not-yet-created pipes with exponential
+//! backoff until a deadline, sleeping on [`crate::time::sleep`] rather than the
+//! synchronous `WaitNamedPipeW`, and reports [`SetupError::TimedOut`] when the
+//! deadline passes.
 //!
 //! ```no_run
-//! # use std::thread;
 //! # use std::time::Duration;
 //! # use winasio::iocp::ThreadPool;
 //! # use winasio::pipe::{ClientOptions, SetupError};
-//! # fn connect_with_retry(name: &str) -> Result<(), SetupError> {
-//! loop {
-//!     match ClientOptions::new(name).connect(&ThreadPool) {
-//!         Ok(_pipe) => return Ok(()),
-//!         Err(SetupError::Busy) => thread::sleep(Duration::from_millis(10)),
-//!         Err(e) => return Err(e),
-//!     }
-//! }
+//! # async fn connect(name: &str) -> Result<(), SetupError> {
+//! let pipe = ClientOptions::new(name)
+//!     .connect_wait(&ThreadPool, Duration::from_secs(5))
+//!     .await?;
+//! # drop(pipe);
+//! # Ok(())
 //! # }
 //! ```
 
 mod client;
 mod connected;
+mod listener;
+mod message;
 mod name;
+mod pair;
 mod server;
 
 pub use crate::fs::{ReadOutcome, SetupError};
 pub use client::ClientOptions;
-pub use connected::NamedPipe;
+pub(crate) use connected::{drop_inner, Inner};
+pub use connected::{Impersonation, NamedPipe};
+pub use listener::PipeListener;
+pub use message::MessageError;
 pub use name::MAX_NAME_COMPONENT_LEN;
+pub use pair::{pair, ChildPipe, PairEnd, PairOptions};
 pub use server::{AccessDirection, NamedPipeServer, PipeMode, ServerOptions};
 
 /// A [`NamedPipeServer`] using the system thread-pool backend.
@@ -0,0 +1,253 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Two connected ends of a private pipe.
+//!
+//! Anonymous pipes cannot do overlapped I/O, so a pair is a named pipe under
+//! a unique name. The name is created with `FILE_FLAG_FIRST_PIPE_INSTANCE`
+//! and a single instance, and the second end is opened straight away: if
+//! anyone else got to the instance first, opening it fails instead of
+//! silently pairing with a stranger. The descriptor grants access to the
+//! pipe's owner only and remote clients are refused.
+//!
+//! The client end connects as soon as it is opened, which Windows treats as a
+//! complete connection even though `ConnectNamedPipe` is never called, so a
+//! pair is ready without awaiting anything.
+//!
+//! Neither end is inheritable unless [`PairOptions::inheritable`] asks for
+//! one. An inheritable handle goes to every child spawned while it is open,
+//! from any thread, so it is opt-in even for the end meant for a child:
+//! wiring a child's standard streams does not need it, because
+//! [`std::process::Command`] gives the child its own inheritable duplicate.
+
+use std::os::windows::io::{AsHandle, AsRawHandle, BorrowedHandle, OwnedHandle, RawHandle};
+
+use windows::Win32::Foundation::{SetHandleInformation, HANDLE_FLAG_INHERIT};
+use windows::Win32::Storage::FileSystem::{
+    FILE_FLAGS_AND_ATTRIBUTES, FILE_FLAG_OVERLAPPED, FILE_GENERIC_READ, FILE_GENERIC_WRITE,
+    FILE_WRITE_ATTRIBUTES,
+};
+
+use crate::fs::{unique_name, SetupError};
+use crate::iocp::Registrar;
+
+use super::client::register;
+use super::{AccessDirection, ClientOptions, NamedPipe, PipeMode, ServerOptions};
+
+/// Only the owner, which is the creating user, may open the name.
+const OWNER_ONLY: &str = "D:P(A;;GA;;;OW)";
+
+/// Two connected byte-mode ends, both registered with `registrar`.
+///
+/// Shorthand for [`PairOptions::pair`] with default options.
+pub fn pair<R: Registrar>(
+    registrar: &R,
+) -> Result<(NamedPipe<R::Io>, NamedPipe<R::Io>), SetupError> {
+    PairOptions::new().pair(registrar)
+}
+
+/// One end of a pipe pair, as [`PairOptions::pair`] returns them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairEnd {
+    /// The first end returned.
+    First,
+    /// The second end returned: the [`ChildPipe`] of
+    /// [`PairOptions::pair_for_child`].
+    Second,
+}
+
+/// Builder for a connected pipe pair.
+#[derive(Debug, Clone)]
+pub struct PairOptions {
+    access: AccessDirection,
+    mode: PipeMode,
+    buffer_size: u32,
+    child_overlapped: bool,
+    inheritable: Option<PairEnd>,
+}
+
+impl Default for PairOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PairOptions {
+    /// A duplex byte-mode pair with 4096-byte buffers.
+    pub fn new() -> Self {
+        PairOptions {
+            access: AccessDirection::Duplex,
+            mode: PipeMode::Byte,
+            buffer_size: 4096,
+            child_overlapped: false,
+            inheritable: None,
+        }
+    }
+
+    /// Set the direction, as seen from the first end.
+    ///
+    /// [`AccessDirection::Inbound`] makes the first end read and the second
+    /// write; [`AccessDirection::Outbound`] the reverse.
+    pub fn access(&mut self, access: AccessDirection) -> &mut Self {
+        self.access = access;
+        self
+    }
+
+    /// Select byte or message mode for both ends.
+    ///
+    /// [`PipeMode::Message`] makes the pipe message type and puts both ends in
+    /// message read mode.
+    pub fn mode(&mut self, mode: PipeMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the buffer size in each direction.
+    pub fn buffer_size(&mut self, size: u32) -> &mut Self {
+        self.buffer_size = size;
+        self
+    }
+
+    /// Open the end returned by [`PairOptions::pair_for_child`] for
+    /// overlapped I/O.
+    ///
+    /// Off by default: a child using the handle for its standard streams, or
+    /// with ordinary blocking reads and writes, needs a synchronous handle.
+    pub fn child_overlapped(&mut self, overlapped: bool) -> &mut Self {
+        self.child_overlapped = overlapped;
+        self
+    }
+
+    /// Make one end inheritable by child processes, or neither.
+    ///
+    /// `None` by default. The handle stays inheritable until it is closed, so
+    /// every child spawned meanwhile, from any thread, receives a copy.
+    pub fn inheritable(&mut self, end: Option<PairEnd>) -> &mut Self {
+        self.inheritable = end;
+        self
+    }
+
+    /// Create a pair with both ends registered with `registrar`.
+    ///
+    /// Only the end chosen with [`PairOptions::inheritable`] is inheritable.
+    pub fn pair<R: Registrar>(
+        &self,
+        registrar: &R,
+    ) -> Result<(NamedPipe<R::Io>, NamedPipe<R::Io>), SetupError> {
+        let (first, second) = self.create(registrar)?;
+        let handle = second.open(
+            self.second_access(),
+            FILE_FLAG_OVERLAPPED,
+            self.inherits(PairEnd::Second),
+        )?;
+        let second = register(handle, registrar)?;
+        Ok((first, second))
+    }
+
+    /// Create a pair whose second end is meant for a child process.
+    ///
+    /// The first end is registered with `registrar`. The second is not: a
+    /// handle registered here would deliver the child's completions to this
+    /// process. It converts into a [`std::process::Stdio`] for wiring a
+    /// child's standard streams, which works whether or not it is
+    /// inheritable. Choose [`PairEnd::Second`] with
+    /// [`PairOptions::inheritable`] when the child is to find the handle some
+    /// other way, such as its value on the command line.
+    ///
+    /// Drop the [`ChildPipe`] once the child has started. While this process
+    /// still holds it, the first end never sees the child close its side.
+    pub fn pair_for_child<R: Registrar>(
+        &self,
+        registrar: &R,
+    ) -> Result<(NamedPipe<R::Io>, ChildPipe), SetupError> {
+        let (first, second) = self.create(registrar)?;
+        let flags = if self.child_overlapped {
+            FILE_FLAG_OVERLAPPED
+        } else {
+            FILE_FLAGS_AND_ATTRIBUTES(0)
+        };
+        let handle = second.open(self.second_access(), flags, self.inherits(PairEnd::Second))?;
+        Ok((first, ChildPipe { handle }))
+    }
+
+    fn inherits(&self, end: PairEnd) -> bool {
+        self.inheritable == Some(end)
+    }
+
+    /// Create the first end under a fresh name, and options for the second.
+    fn create<R: Registrar>(
+        &self,
+        registrar: &R,
+    ) -> Result<(NamedPipe<R::Io>, ClientOptions), SetupError> {
+        let name = unique_name("winasio-pair");
+        let mut server = ServerOptions::new(name.as_str());
+        server
+            .access(self.access)
+            .pipe_type(self.mode)
+            .max_instances(1)
+            .in_buffer_size(self.buffer_size)
+            .out_buffer_size(self.buffer_size)
+            .first_instance(true)
+            .reject_remote_clients(true)
+            .security_descriptor_sddl(OWNER_ONLY);
+        let mut server = server.create(registrar)?;
+        let first = NamedPipe::from_inner(server.inner.take().expect("pipe state is present"));
+        if self.inherits(PairEnd::First) {
+            // SAFETY: the handle is owned by `first`, alive for the call.
+            // Dropping `first` on failure closes it like any other pipe.
+            unsafe {
+                SetHandleInformation(first.handle(), HANDLE_FLAG_INHERIT.0, HANDLE_FLAG_INHERIT)
+            }
+            .map_err(SetupError::from_windows)?;
+        }
+
+        let mut client = ClientOptions::new(name);
+        client.read_mode(self.mode);
+        Ok((first, client))
+    }
+
+    /// Access rights for the second end, which does whatever the first end
+    /// does not.
+    fn second_access(&self) -> u32 {
+        match self.access {
+            AccessDirection::Inbound => FILE_GENERIC_WRITE.0,
+            // Setting message read mode needs write-attributes access, which
+            // plain read access does not include.
+            AccessDirection::Outbound => FILE_GENERIC_READ.0 | FILE_WRITE_ATTRIBUTES.0,
+            AccessDirection::Duplex => FILE_GENERIC_READ.0 | FILE_GENERIC_WRITE.0,
+        }
+    }
+}
+
+/// The unregistered end from [`PairOptions::pair_for_child`].
+#[derive(Debug)]
+pub struct ChildPipe {
+    handle: OwnedHandle,
+}
+
+impl AsHandle for ChildPipe {
+    fn as_handle(&self) -> BorrowedHandle<'_> {
+        self.handle.as_handle()
+    }
+}
+
+impl AsRawHandle for ChildPipe {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.handle.as_raw_handle()
+    }
+}
+
+impl From<ChildPipe> for OwnedHandle {
+    fn from(value: ChildPipe) -> Self {
+        value.handle
+    }
+}
+
+impl From<ChildPipe> for std::process::Stdio {
+    fn from(value: ChildPipe) -> Self {
+        value.handle.into()
+    }
+}
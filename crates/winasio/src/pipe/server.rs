@@ -26,10 +26,11 @@ use windows::Win32::Storage::FileSystem::{
     PIPE_ACCESS_OUTBOUND,
 };
 use windows::Win32::System::Pipes::{
-    CreateNamedPipeW, NAMED_PIPE_MODE, PIPE_READMODE_BYTE, PIPE_READMODE_MESSAGE, PIPE_TYPE_BYTE,
-    PIPE_TYPE_MESSAGE, PIPE_WAIT,
+    CreateNamedPipeW, NAMED_PIPE_MODE, PIPE_READMODE_BYTE, PIPE_READMODE_MESSAGE,
+    PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_TYPE_MESSAGE, PIPE_WAIT,
 };
 
+use crate::fs::security::SecurityDescriptor;
 use crate::fs::SetupError;
 use crate::iocp::{ConnectPipe, Handle, OpResult, Registrar, Submitter};
 
@@ -71,8 +72,14 @@ pub struct ServerOptions {
     out_buffer_size: u32,
     default_timeout: u32,
     first_instance: bool,
+    reject_remote_clients: bool,
+    low_integrity: bool,
+    security_descriptor: Option<String>,
 }
 
+/// Mandatory label letting low-integrity clients open the pipe for writing.
+const LOW_INTEGRITY_LABEL: &str = "S:(ML;;NW;;;LW)";
+
 impl ServerOptions {
     /// Create options for the bare pipe `name`.
     pub fn new(name: impl Into<String>) -> Self {
@@ -85,6 +92,9 @@ impl ServerOptions {
             out_buffer_size: 4096,
             default_timeout: 0,
             first_instance: false,
+            reject_remote_clients: false,
+            low_integrity: false,
+            security_descriptor: None,
         }
     }
 
@@ -139,6 +149,41 @@ impl ServerOptions {
         self
     }
 
+    /// Refuse clients connecting from another machine.
+    ///
+    /// Sets `PIPE_REJECT_REMOTE_CLIENTS`. Off by default, matching Windows;
+    /// services reachable only by local callers should turn it on.
+    pub fn reject_remote_clients(&mut self, reject: bool) -> &mut Self {
+        self.reject_remote_clients = reject;
+        self
+    }
+
+    /// Let low-integrity (sandboxed) clients write to the pipe.
+    ///
+    /// Adds the mandatory label `S:(ML;;NW;;;LW)` to the security descriptor.
+    /// Without it a pipe carries its creator's integrity level and
+    /// low-integrity processes cannot open it for writing. The DACL still
+    /// decides who may connect. Combined with
+    /// [`ServerOptions::security_descriptor_sddl`], that string must not carry
+    /// its own `S:` part; the conflict is reported as
+    /// [`SetupError::InvalidOptions`].
+    pub fn low_integrity(&mut self, low_integrity: bool) -> &mut Self {
+        self.low_integrity = low_integrity;
+        self
+    }
+
+    /// Apply a security descriptor, written as SDDL, to the pipe.
+    ///
+    /// Without one the pipe gets the Windows default, which lets any local
+    /// user connect for reading. The string is converted when
+    /// [`ServerOptions::create`] runs; a string Windows rejects is reported as
+    /// [`SetupError::InvalidSecurityDescriptor`]. The descriptor is applied by
+    /// the first instance of a name; later instances inherit the first one's.
+    pub fn security_descriptor_sddl(&mut self, sddl: impl Into<String>) -> &mut Self {
+        self.security_descriptor = Some(sddl.into());
+        self
+    }
+
     /// Create and register an unconnected server instance.
     pub fn create<R: Registrar>(
         &self,
@@ -149,10 +194,20 @@ impl ServerOptions {
         if self.first_instance {
             open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
         }
-        let pipe_mode = self.pipe_mode() | PIPE_WAIT;
+        let mut pipe_mode = self.pipe_mode() | PIPE_WAIT;
+        if self.reject_remote_clients {
+            pipe_mode |= PIPE_REJECT_REMOTE_CLIENTS;
+        }
+        let descriptor = compose_sddl(self.security_descriptor.as_deref(), self.low_integrity)?
+            .as_deref()
+            .map(SecurityDescriptor::from_sddl)
+            .transpose()?;
+        let attributes = descriptor.as_ref().map(|d| d.attributes(false));
 
         // SAFETY: the name was validated and composed as a local, NUL-free
-        // pipe path. All other arguments are values owned by this builder.
+        // pipe path. The security attributes and the descriptor they point to
+        // outlive the call. All other arguments are values owned by this
+        // builder.
         let raw = unsafe {
             CreateNamedPipeW(
                 &name,
@@ -162,12 +217,13 @@ impl ServerOptions {
                 self.out_buffer_size,
                 self.in_buffer_size,
                 self.default_timeout,
-                None,
+                attributes.as_ref().map(|a| a as *const _),
             )
         };
         if raw == INVALID_HANDLE_VALUE {
             return Err(SetupError::from_windows(Error::from_thread()));
         }
+        drop(descriptor);
 
         // SAFETY: `CreateNamedPipeW` returned a newly owned handle, and
         // ownership of closing it transfers into `Handle`.
@@ -181,6 +237,10 @@ impl ServerOptions {
         }
     }
 
+    pub(super) fn instance_limit(&self) -> u32 {
+        self.max_instances
+    }
+
     pub(crate) fn server_access_mode(
         &self,
     ) -> windows::Win32::Storage::FileSystem::FILE_FLAGS_AND_ATTRIBUTES {
@@ -199,6 +259,38 @@ impl ServerOptions {
     }
 }
 
+/// The SDDL to convert, if any, with the low-integrity label folded in.
+fn compose_sddl(
+    sddl: Option<&str>,
+    low_integrity: bool,
+) -> std::result::Result<Option<String>, SetupError> {
+    match (sddl, low_integrity) {
+        (sddl, false) => Ok(sddl.map(str::to_owned)),
+        (None, true) => Ok(Some(LOW_INTEGRITY_LABEL.to_owned())),
+        (Some(sddl), true) if has_sacl(sddl) => Err(SetupError::InvalidOptions(
+            "low_integrity cannot be combined with an SDDL string that has an S: part",
+        )),
+        (Some(sddl), true) => Ok(Some(format!("{sddl}{LOW_INTEGRITY_LABEL}"))),
+    }
+}
+
+/// Whether `sddl` has a top-level `S:` component. Component tags are the only
+/// letters followed by a colon outside parentheses.
+fn has_sacl(sddl: &str) -> bool {
+    let mut depth = 0usize;
+    let mut prev = None;
+    for c in sddl.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ':' if depth == 0 && prev == Some('S') => return true,
+            _ => {}
+        }
+        prev = Some(c);
+    }
+    false
+}
+
 /// An unconnected server-side named-pipe instance.
 pub struct NamedPipeServer<S: Submitter> {
     pub(crate) inner: Option<Inner<S>>,
@@ -260,3 +352,42 @@ impl<S: Submitter> Drop for NamedPipeServer<S> {
         drop_inner(inner);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sddl_passes_through_without_low_integrity() {
+        assert_eq!(compose_sddl(None, false).unwrap(), None);
+        assert_eq!(
+            compose_sddl(Some("D:(A;;GA;;;SY)"), false)
+                .unwrap()
+                .as_deref(),
+            Some("D:(A;;GA;;;SY)")
+        );
+    }
+
+    #[test]
+    fn low_integrity_appends_the_mandatory_label() {
+        assert_eq!(
+            compose_sddl(None, true).unwrap().as_deref(),
+            Some("S:(ML;;NW;;;LW)")
+        );
+        assert_eq!(
+            compose_sddl(Some("D:(A;;GA;;;SY)(A;;GRGW;;;AU)"), true)
+                .unwrap()
+                .as_deref(),
+            Some("D:(A;;GA;;;SY)(A;;GRGW;;;AU)S:(ML;;NW;;;LW)")
+        );
+    }
+
+    #[test]
+    fn low_integrity_conflicts_with_an_explicit_sacl() {
+        let err = compose_sddl(Some("D:(A;;GA;;;SY)S:(ML;;NW;;;ME)"), true).unwrap_err();
+        assert!(matches!(err, SetupError::InvalidOptions(_)));
+        // A SID inside an ACE is not a SACL.
+        assert!(!has_sacl("D:(A;;GA;;;S-1-5-18)"));
+        assert!(has_sacl("O:BAS:(ML;;NW;;;LW)"));
+    }
+}
@@ -0,0 +1,317 @@
+// ------------------------------------------------------------
+// Copyright 2023 Youyuan Wu
+// Licensed under the MIT License (MIT). See License.txt in the repo root for
+// license information.
+// ------------------------------------------------------------
+
+//! Runtime-agnostic timers.
+//!
+//! Retry loops and deadlines inside this crate need to wait without blocking
+//! the caller's thread and without assuming a particular runtime. [`Sleep`] is
+//! backed by a Win32 thread-pool timer: the pool thread that fires it invokes
+//! the stored [`Waker`], exactly as a thread-pool I/O completion does.
+//!
+//! On the thread-pool backend nothing more is needed. A loop driving a
+//! [`Proactor`](crate::iocp::Proactor) must either hand out wakers that
+//! interrupt its wait, or call [`Proactor::poll`](crate::iocp::Proactor::poll)
+//! with a timeout so that a fired timer is noticed: a timer is not a
+//! completion packet and does not end a wait on the port by itself.
+
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use windows::Win32::Foundation::FILETIME;
+use windows::Win32::System::Threading::{
+    CloseThreadpoolTimer, CreateThreadpoolTimer, SetThreadpoolTimer,
+    WaitForThreadpoolTimerCallbacks, PTP_CALLBACK_INSTANCE, PTP_TIMER,
+};
+
+/// Far enough ahead to stand for "never", and near enough that `Instant` can
+/// represent it where `now + duration` could not.
+const FOREVER: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+/// The instant `duration` from now.
+///
+/// Saturates rather than overflowing: callers pass [`Duration::MAX`] to mean
+/// "no limit", and `Instant + Duration` panics on a value that large.
+pub(crate) fn deadline_after(duration: Duration) -> Instant {
+    let now = Instant::now();
+    now.checked_add(duration).unwrap_or_else(|| now + FOREVER)
+}
+
+/// Wait until `duration` has elapsed.
+///
+/// A duration too large for an [`Instant`] — [`Duration::MAX`], say — waits
+/// as good as forever instead of panicking.
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(deadline_after(duration))
+}
+
+/// Wait until `deadline`.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        timer: None,
+    }
+}
+
+/// Future returned by [`sleep`] and [`sleep_until`].
+///
+/// The thread-pool timer is created on first poll, so a `Sleep` that is never
+/// polled costs nothing. Dropping it cancels the timer and waits for a
+/// callback already running to return — except when it is dropped from
+/// inside that callback, as happens when the waker polls the task inline and
+/// the task finishes. Waiting there would wait on itself; the callback does
+/// not touch the timer after waking, so the drop skips the wait instead.
+pub struct Sleep {
+    deadline: Instant,
+    timer: Option<Timer>,
+}
+
+impl Sleep {
+    /// The instant this future resolves at.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Poll::Ready(());
+        }
+        if self.timer.is_none() {
+            match Timer::start(self.deadline - now) {
+                Some(timer) => self.timer = Some(timer),
+                None => {
+                    // No pool timer to be had: degrade to being polled again
+                    // rather than never waking.
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+            }
+        }
+        let shared = &self.timer.as_ref().expect("timer was just armed").shared;
+        shared.register(cx.waker());
+        // Checked after registering, so a timer firing in between is not
+        // missed: either it saw the waker, or this load sees the flag.
+        if shared.fired.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+}
+
+impl std::fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sleep")
+            .field("deadline", &self.deadline)
+            .field("armed", &self.timer.is_some())
+            .finish()
+    }
+}
+
+struct Shared {
+    fired: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Shared {
+    fn register(&self, waker: &Waker) {
+        let mut slot = self.waker.lock().unwrap_or_else(PoisonError::into_inner);
+        match slot.as_ref() {
+            Some(current) if current.will_wake(waker) => {}
+            _ => *slot = Some(waker.clone()),
+        }
+    }
+}
+
+thread_local! {
+    /// The `Shared` whose callback is running on this thread, if any.
+    static RUNNING: Cell<*const Shared> = const { Cell::new(std::ptr::null()) };
+}
+
+/// An armed one-shot thread-pool timer.
+struct Timer {
+    raw: PTP_TIMER,
+    /// Also reachable from the callback through the context pointer; `drop`
+    /// waits for callbacks before this reference is released.
+    shared: Arc<Shared>,
+}
+
+impl Timer {
+    fn start(after: Duration) -> Option<Self> {
+        let shared = Arc::new(Shared {
+            fired: AtomicBool::new(false),
+            waker: Mutex::new(None),
+        });
+        let context = Arc::as_ptr(&shared).cast_mut().cast();
+        // SAFETY: the callback only reads `Shared` through `context`, and the
+        // returned timer is owned by `Timer`, whose drop stops and drains it
+        // before `shared` can be released.
+        let raw =
+            unsafe { CreateThreadpoolTimer(Some(timer_callback), Some(context), None) }.ok()?;
+        let timer = Timer { raw, shared };
+        let due = relative_due_time(after);
+        // SAFETY: `raw` is the live timer created above and `due` outlives
+        // the call.
+        unsafe { SetThreadpoolTimer(timer.raw, Some(&due), 0, None) };
+        Some(timer)
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let in_own_callback = RUNNING.with(|running| running.get() == Arc::as_ptr(&self.shared));
+        // SAFETY: `raw` is live until `CloseThreadpoolTimer`. Clearing the due
+        // time stops new callbacks, and the wait drains one that has started,
+        // so nothing touches `shared` once this returns. The wait is skipped
+        // inside this timer's own callback, where it would never return; that
+        // callback is past its last use of `shared`, and the pool frees the
+        // timer object once it returns.
+        unsafe {
+            SetThreadpoolTimer(self.raw, None, 0, None);
+            if !in_own_callback {
+                WaitForThreadpoolTimerCallbacks(self.raw, true);
+            }
+            CloseThreadpoolTimer(self.raw);
+        }
+    }
+}
+
+/// The thread pool's timer callback.
+///
+/// # Safety
+///
+/// `context` is the `Shared` pointer a live [`Timer`] was created with.
+unsafe extern "system" fn timer_callback(
+    _instance: PTP_CALLBACK_INSTANCE,
+    context: *mut core::ffi::c_void,
+    _timer: PTP_TIMER,
+) {
+    // SAFETY: see above; the owning `Timer` outlives every callback, unless
+    // it is dropped by the wake below.
+    let shared = unsafe { &*context.cast::<Shared>() };
+    shared.fired.store(true, Ordering::Release);
+    let waker = shared
+        .waker
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    // `shared` is not used past this point: the wake may drop the `Sleep`,
+    // and with it the `Timer`.
+    if let Some(waker) = waker {
+        let outer = RUNNING.with(|running| running.replace(context.cast_const().cast()));
+        waker.wake();
+        RUNNING.with(|running| running.set(outer));
+    }
+}
+
+/// A `FILETIME` due time `after` from now: negative, in 100 ns units.
+fn relative_due_time(after: Duration) -> FILETIME {
+    // Rounded up so the timer never fires before the deadline, and at least
+    // one unit because zero would mean an absolute time in 1601.
+    let ticks = after.as_nanos().div_ceil(100).clamp(1, i64::MAX as u128) as i64;
+    let raw = -ticks;
+    FILETIME {
+        dwLowDateTime: raw as u32,
+        dwHighDateTime: (raw >> 32) as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn as_i64(time: FILETIME) -> i64 {
+        (((time.dwHighDateTime as u64) << 32) | time.dwLowDateTime as u64) as i64
+    }
+
+    #[test]
+    fn due_times_are_relative_and_rounded_up() {
+        assert_eq!(as_i64(relative_due_time(Duration::from_millis(5))), -50_000);
+        assert_eq!(as_i64(relative_due_time(Duration::from_nanos(101))), -2);
+        assert_eq!(as_i64(relative_due_time(Duration::ZERO)), -1);
+        assert_eq!(as_i64(relative_due_time(Duration::MAX)), -i64::MAX);
+    }
+
+    #[test]
+    fn a_sleep_too_long_for_an_instant_is_pending_not_a_panic() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut forever = Box::pin(sleep(Duration::MAX));
+        assert!(forever.deadline() > Instant::now() + Duration::from_secs(3600));
+        assert!(forever.as_mut().poll(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn sleep_wakes_after_its_deadline() {
+        let started = Instant::now();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let waker = Waker::from(Arc::new(Signal(Mutex::new(tx))));
+        let mut cx = Context::from_waker(&waker);
+        let mut timer = Box::pin(sleep(Duration::from_millis(30)));
+        assert!(timer.as_mut().poll(&mut cx).is_pending());
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(timer.as_mut().poll(&mut cx).is_ready());
+        assert!(started.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn dropping_an_armed_sleep_never_wakes() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let waker = Waker::from(Arc::new(Signal(Mutex::new(tx))));
+        let mut cx = Context::from_waker(&waker);
+        let mut timer = Box::pin(sleep(Duration::from_millis(20)));
+        assert!(timer.as_mut().poll(&mut cx).is_pending());
+        drop(timer);
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn a_sleep_dropped_from_its_own_wake_does_not_deadlock() {
+        // A waker that polls the task inline: it finishes the `Sleep` on the
+        // timer's callback thread and drops it there.
+        struct Inline {
+            sleep: Mutex<Option<Pin<Box<Sleep>>>>,
+            done: Mutex<std::sync::mpsc::Sender<()>>,
+        }
+        impl std::task::Wake for Inline {
+            fn wake(self: Arc<Self>) {
+                let waker = Waker::from(self.clone());
+                let mut cx = Context::from_waker(&waker);
+                let mut slot = self.sleep.lock().unwrap();
+                if let Some(sleep) = slot.as_mut() {
+                    if sleep.as_mut().poll(&mut cx).is_ready() {
+                        *slot = None;
+                        let _ = self.done.lock().unwrap().send(());
+                    }
+                }
+            }
+        }
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let inline = Arc::new(Inline {
+            sleep: Mutex::new(Some(Box::pin(sleep(Duration::from_millis(10))))),
+            done: Mutex::new(tx),
+        });
+        Waker::from(inline.clone()).wake();
+        rx.recv_timeout(Duration::from_secs(5))
+            .expect("the sleep finished and was dropped on the callback");
+    }
+
+    struct Signal(Mutex<std::sync::mpsc::Sender<()>>);
+
+    impl std::task::Wake for Signal {
+        fn wake(self: Arc<Self>) {
+            let _ = self.0.lock().unwrap().send(());
+        }
+    }
+}